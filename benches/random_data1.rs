@@ -82,7 +82,7 @@ a5500b26b3fd3f77c433c0d85978c667898832f12709d5d79b1d90f62510e109
         let mut i = 0;
         while i < 1000 {
             let bytes = data.as_bytes().to_vec();
-            FuzzyHash::new(bytes);
+            FuzzyHash::new(bytes).unwrap();
             i += 1;
         }
     });
@@ -10,7 +10,7 @@ fn main() {
     for i in 1..env::args().len() {
         let path = env::args().nth(i).unwrap();
         let data = std::fs::read(path).expect("Could not read file");
-        let fuzzy_hash = FuzzyHash::new(data);
+        let fuzzy_hash = FuzzyHash::new(data).expect("Could not hash file");
         println!("{}", fuzzy_hash.to_string());
     }
 }
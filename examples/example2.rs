@@ -9,8 +9,16 @@ pub fn main() {
     println!("first: {}", env::args().nth(1).unwrap());
     println!("second: {}", env::args().nth(2).unwrap());
 
-    let first = FuzzyHash::from(env::args().nth(1).unwrap());
-    let second = FuzzyHash::from(env::args().nth(2).unwrap());
+    let first: FuzzyHash = env::args()
+        .nth(1)
+        .unwrap()
+        .parse()
+        .expect("First hash is not a valid fuzzy hash");
+    let second: FuzzyHash = env::args()
+        .nth(2)
+        .unwrap()
+        .parse()
+        .expect("Second hash is not a valid fuzzy hash");
 
     println!(
         "Strings are {}% similar!",
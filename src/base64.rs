@@ -0,0 +1,56 @@
+//! Standard padded base64, used by the digests that serialize binary sketches
+
+use super::{constants::BASE64_CHARS, error::Error, Result};
+
+pub(crate) fn encode(data: &[u8]) -> String {
+    let chars = BASE64_CHARS.as_bytes();
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, byte)| {
+            group | (*byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(chars[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Decode `encoded`, failing with [`Error::MalformedInput`] on invalid characters or padding
+pub(crate) fn decode(encoded: &str) -> Result<Vec<u8>> {
+    let encoded = encoded.as_bytes();
+    if encoded.len() % 4 != 0 {
+        return Err(Error::MalformedInput);
+    }
+
+    let mut data = Vec::with_capacity(encoded.len() / 4 * 3);
+    let groups = encoded.len() / 4;
+    for (n, chunk) in encoded.chunks(4).enumerate() {
+        let padding = chunk.iter().rev().take_while(|c| **c == b'=').count();
+        if padding > 2 || (padding > 0 && n + 1 != groups) {
+            return Err(Error::MalformedInput);
+        }
+
+        let mut group = 0u32;
+        for (i, c) in chunk[..4 - padding].iter().enumerate() {
+            let value = BASE64_CHARS
+                .bytes()
+                .position(|b| b == *c)
+                .ok_or(Error::MalformedInput)?;
+            group |= (value as u32) << (18 - 6 * i);
+        }
+        data.extend(
+            group
+                .to_be_bytes()
+                .iter()
+                .skip(1)
+                .take(3 - padding)
+                .copied(),
+        );
+    }
+    Ok(data)
+}
@@ -0,0 +1,103 @@
+//! Parallel many-to-many comparison of fuzzy hashes, available with the `rayon` feature.
+//!
+//! Every digest of the first collection is compared against the digests of the second
+//! collection with a compatible block size. The second collection is sorted by block size up
+//! front, so the digests a digest can be compared with are a contiguous run of it: pairs which
+//! can never be compared are skipped without being looked at, and the remaining pairs are
+//! scored across the rayon thread pool.
+//!
+//! # Example
+//! ```
+//! use fuzzyhash::{batch, FuzzyHash};
+//! use rayon::prelude::*;
+//!
+//! let known: Vec<FuzzyHash> = vec![
+//!     "96:U57GjXnLt9co6pZwvLhJluvrszNgMFwO6MFG8SvkpjTWf:Hj3BeoEcNJ0TspgIG8SvkpjTg".parse().unwrap(),
+//! ];
+//! let samples: Vec<FuzzyHash> = vec![
+//!     "3:YKKGhR0tn:YRGRmn".parse().unwrap(),
+//!     "96:U57GjXnLt9co6pZwvLhJluvrs1eRTxYARdEallia:Hj3BeoEcNJ0TsI9xYeia3R".parse().unwrap(),
+//! ];
+//!
+//! let pairs: Vec<batch::PairScore> = batch::compare_many(&known, &samples, 50)
+//!     .unwrap()
+//!     .collect();
+//! assert_eq!(pairs, vec![batch::PairScore { first: 0, second: 1, score: 63 }]);
+//! ```
+
+use super::{
+    compare::{self, Signatures},
+    FuzzyHash, Result,
+};
+use rayon::prelude::*;
+use std::sync::Arc;
+
+/// Score of a pair of digests taken from two collections
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PairScore {
+    /// Position of the digest in the first collection
+    pub first: usize,
+    /// Position of the digest in the second collection
+    pub second: usize,
+    /// Similarity score between 1 and 100
+    pub score: u32,
+}
+
+/// Compare every digest of `first` against every digest of `second` in parallel, yielding the
+/// pairs scoring at least `threshold`. Results are produced as they are computed and are not
+/// in any particular order; collect and sort them if a stable order is needed.
+///
+/// All digests are parsed before any comparison is made, so an unfinalized or malformed
+/// digest in either collection is reported as an error up front.
+///
+/// # Arguments
+/// * `first` - finalized fuzzy hashes, usually the larger collection
+/// * `second` - finalized fuzzy hashes to compare against
+/// * `threshold` - minimum score of returned pairs, pairs scoring 0 are never returned
+pub fn compare_many(
+    first: &[FuzzyHash],
+    second: &[FuzzyHash],
+    threshold: u32,
+) -> Result<impl ParallelIterator<Item = PairScore>> {
+    let first = parse_all(first)?;
+
+    let mut sorted: Vec<(usize, Signatures)> = parse_all(second)?.into_iter().enumerate().collect();
+    sorted.sort_by_key(|(_, signatures)| signatures.block_size);
+    let sorted = Arc::new(sorted);
+    let threshold = threshold.max(1);
+
+    Ok(first
+        .into_par_iter()
+        .enumerate()
+        .flat_map_iter(move |(i, signatures)| {
+            // block sizes are 3 times a power of 2, so the only ones between half and twice a
+            // block size are the compatible ones
+            let start =
+                sorted.partition_point(|(_, other)| other.block_size < signatures.block_size / 2);
+            let end = sorted.partition_point(|(_, other)| {
+                other.block_size <= signatures.block_size.saturating_mul(2)
+            });
+            let sorted = Arc::clone(&sorted);
+            (start..end).filter_map(move |k| {
+                let (j, ref other) = sorted[k];
+                let score = compare::compare_signatures(&signatures, other).unwrap_or(0);
+                if score >= threshold {
+                    Some(PairScore {
+                        first: i,
+                        second: j,
+                        score,
+                    })
+                } else {
+                    None
+                }
+            })
+        }))
+}
+
+fn parse_all(hashes: &[FuzzyHash]) -> Result<Vec<Signatures>> {
+    hashes
+        .par_iter()
+        .map(|hash| Signatures::parse(hash.digest()?))
+        .collect()
+}
@@ -0,0 +1,117 @@
+//! Walking and hashing the input files
+
+use fuzzyhash::FuzzyHash;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// How file names are printed
+#[derive(Clone, Copy, PartialEq)]
+pub enum Names {
+    Absolute,
+    Relative,
+    Bare,
+}
+
+/// Hashes input files, reporting errors as they happen
+pub struct Inputs {
+    pub recursive: bool,
+    pub names: Names,
+    pub silent: bool,
+    /// Whether any input could not be processed
+    pub failed: bool,
+}
+
+impl Inputs {
+    /// Hash every input, standard input if there are none, and pass the hashes and their
+    /// display names to `found`. Only errors returned by `found` stop the walk.
+    pub fn hash<F>(&mut self, inputs: &[String], mut found: F) -> io::Result<()>
+    where
+        F: FnMut(FuzzyHash, String) -> io::Result<()>,
+    {
+        if inputs.is_empty() {
+            return self.hash_stdin(&mut found);
+        }
+        for input in inputs {
+            if input == "-" {
+                self.hash_stdin(&mut found)?;
+            } else {
+                self.hash_path(Path::new(input), &mut found)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Report a problem with an input, unless in silent mode
+    pub fn error(&mut self, input: &str, error: &dyn std::fmt::Display) -> io::Result<()> {
+        self.failed = true;
+        if !self.silent {
+            eprintln!("fuzzyhash: {}: {}", input, error);
+        }
+        Ok(())
+    }
+
+    fn hash_stdin<F>(&mut self, found: &mut F) -> io::Result<()>
+    where
+        F: FnMut(FuzzyHash, String) -> io::Result<()>,
+    {
+        match FuzzyHash::read(&mut io::stdin().lock()) {
+            Ok(hash) => found(hash, "stdin".to_string()),
+            Err(e) => self.error("stdin", &e),
+        }
+    }
+
+    fn hash_path<F>(&mut self, path: &Path, found: &mut F) -> io::Result<()>
+    where
+        F: FnMut(FuzzyHash, String) -> io::Result<()>,
+    {
+        let display = path.display().to_string();
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) => return self.error(&display, &e),
+        };
+
+        if !metadata.is_dir() {
+            return match FuzzyHash::file(path) {
+                Ok(hash) => found(hash, self.name(path)),
+                Err(e) => self.error(&display, &e),
+            };
+        }
+
+        if !self.recursive {
+            return self.error(&display, &"Is a directory");
+        }
+        let mut entries: Vec<PathBuf> = match fs::read_dir(path) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .collect(),
+            Err(e) => return self.error(&display, &e),
+        };
+        entries.sort();
+        for entry in entries {
+            // do not follow links to directories, they may form loops
+            let is_linked_dir = fs::symlink_metadata(&entry)
+                .map(|m| m.file_type().is_symlink() && entry.is_dir())
+                .unwrap_or(false);
+            if !is_linked_dir {
+                self.hash_path(&entry, found)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn name(&self, path: &Path) -> String {
+        match self.names {
+            Names::Absolute => fs::canonicalize(path)
+                .unwrap_or_else(|_| path.to_path_buf())
+                .display()
+                .to_string(),
+            Names::Relative => path.display().to_string(),
+            Names::Bare => path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| path.display().to_string()),
+        }
+    }
+}
@@ -0,0 +1,199 @@
+//! `fuzzyhash`, an ssdeep compatible command line tool. Built with the `cli` feature.
+
+mod inputs;
+mod modes;
+
+use inputs::{Inputs, Names};
+use modes::Matching;
+use std::{
+    io::{self, BufWriter},
+    process,
+};
+
+const USAGE: &str = "Usage: fuzzyhash [-m FILE] [-k FILE] [-dpgxrlbsah] [-t N] [-V] [FILES]
+Computes fuzzy hashes of FILES, or of standard input when no files or - are given.
+  -m FILE  Match FILES against the known hashes in signature file FILE
+  -k FILE  Match the signature files FILES against the known hashes in signature file FILE
+  -x       Compare the signature files FILES with each other
+  -d       Compare each of FILES with the files before it
+  -p       Pretty matching mode, list what each of FILES matches followed by a blank line
+  -g       Cluster FILES by their matches
+  -t N     Only show matches scoring above N, from 0 to 100
+  -a       Show every score, including those at or below the threshold
+  -r       Recursive mode, hash every file in the given directories
+  -l       Print file names relative to the working directory, as given
+  -b       Print only the base name of each file
+  -s       Silent mode, do not report errors
+  -h       Display this help message
+  -V       Display the version number and exit";
+
+#[derive(PartialEq)]
+enum Mode {
+    Hash,
+    MatchKnown,
+    MatchSignatures,
+    CompareSignatures,
+    Directory,
+    Pretty,
+    Cluster,
+}
+
+struct Options {
+    mode: Mode,
+    known: Vec<String>,
+    threshold: u32,
+    show_all: bool,
+    recursive: bool,
+    names: Names,
+    silent: bool,
+    inputs: Vec<String>,
+}
+
+enum Command {
+    Run(Options),
+    Help,
+    Version,
+}
+
+fn set_mode(options: &mut Options, mode: Mode) -> Result<(), String> {
+    if options.mode != Mode::Hash && options.mode != mode {
+        return Err("only one of -m, -k, -x, -d, -p and -g may be used".to_string());
+    }
+    options.mode = mode;
+    Ok(())
+}
+
+fn parse_args<I: Iterator<Item = String>>(args: I) -> Result<Command, String> {
+    let mut options = Options {
+        mode: Mode::Hash,
+        known: Vec::new(),
+        threshold: 0,
+        show_all: false,
+        recursive: false,
+        names: Names::Absolute,
+        silent: false,
+        inputs: Vec::new(),
+    };
+
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            options.inputs.extend(args.by_ref());
+            break;
+        }
+        if arg.len() < 2 || !arg.starts_with('-') {
+            options.inputs.push(arg);
+            continue;
+        }
+
+        for (i, flag) in arg.char_indices().skip(1) {
+            // the value of an option is the rest of this argument or the next argument
+            let rest = &arg[i + flag.len_utf8()..];
+            let mut value = || -> Result<String, String> {
+                if rest.is_empty() {
+                    args.next()
+                        .ok_or_else(|| format!("option requires an argument -- '{}'", flag))
+                } else {
+                    Ok(rest.to_string())
+                }
+            };
+
+            match flag {
+                'm' | 'k' => {
+                    let known = value()?;
+                    options.known.push(known);
+                    let mode = if flag == 'm' {
+                        Mode::MatchKnown
+                    } else {
+                        Mode::MatchSignatures
+                    };
+                    set_mode(&mut options, mode)?;
+                    break;
+                }
+                't' => {
+                    let threshold = value()?;
+                    options.threshold = match threshold.parse() {
+                        Ok(threshold) if threshold <= 100 => threshold,
+                        _ => return Err(format!("invalid threshold '{}'", threshold)),
+                    };
+                    break;
+                }
+                'x' => set_mode(&mut options, Mode::CompareSignatures)?,
+                'd' => set_mode(&mut options, Mode::Directory)?,
+                'p' => set_mode(&mut options, Mode::Pretty)?,
+                'g' => set_mode(&mut options, Mode::Cluster)?,
+                'a' => options.show_all = true,
+                'r' => options.recursive = true,
+                'l' => options.names = Names::Relative,
+                'b' => options.names = Names::Bare,
+                's' => options.silent = true,
+                'h' => return Ok(Command::Help),
+                'V' => return Ok(Command::Version),
+                other => return Err(format!("invalid option -- '{}'", other)),
+            }
+        }
+    }
+
+    // these modes read signature files rather than standard input
+    let reads_signatures =
+        options.mode == Mode::MatchSignatures || options.mode == Mode::CompareSignatures;
+    if reads_signatures && options.inputs.is_empty() {
+        return Err("-k and -x need at least one signature file to read".to_string());
+    }
+    Ok(Command::Run(options))
+}
+
+fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(Command::Run(options)) => options,
+        Ok(Command::Help) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Ok(Command::Version) => {
+            println!("{}", env!("CARGO_PKG_VERSION"));
+            return;
+        }
+        Err(message) => {
+            eprintln!("fuzzyhash: {}\n{}", message, USAGE);
+            process::exit(1);
+        }
+    };
+
+    let mut inputs = Inputs {
+        recursive: options.recursive,
+        names: options.names,
+        silent: options.silent,
+        failed: false,
+    };
+    let matching = Matching {
+        threshold: options.threshold,
+        show_all: options.show_all,
+    };
+    let paths = &options.inputs;
+    let stdout = io::stdout();
+    let output = BufWriter::new(stdout.lock());
+
+    let result = match options.mode {
+        Mode::Hash => modes::hash(&mut inputs, paths, output),
+        Mode::MatchKnown => {
+            modes::match_known(&mut inputs, paths, &options.known, &matching, output)
+        }
+        Mode::MatchSignatures => {
+            modes::match_signatures(&mut inputs, paths, &options.known, &matching, output)
+        }
+        Mode::CompareSignatures => modes::compare_signatures(&mut inputs, paths, &matching, output),
+        Mode::Directory => modes::directory(&mut inputs, paths, &matching, output),
+        Mode::Pretty => modes::pretty(&mut inputs, paths, &matching, output),
+        Mode::Cluster => modes::cluster(&mut inputs, paths, &matching, output),
+    };
+
+    match result {
+        Ok(()) if !inputs.failed => {}
+        Ok(()) => process::exit(1),
+        Err(e) => {
+            eprintln!("fuzzyhash: {}", e);
+            process::exit(1);
+        }
+    }
+}
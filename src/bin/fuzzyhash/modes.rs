@@ -0,0 +1,254 @@
+//! The operating modes, each writing its results the way ssdeep does
+
+use crate::inputs::Inputs;
+use fuzzyhash::{
+    cluster,
+    index::Index,
+    matcher::{FileMatches, KnownMatch, Matcher},
+    sigfile::{Header, Reader, Writer},
+    FuzzyHash,
+};
+use std::{
+    fs::File,
+    io::{self, BufReader, Write},
+};
+
+/// Settings shared by the matching modes
+pub struct Matching {
+    /// Matches must score above this
+    pub threshold: u32,
+    /// Show every score, including those at or below the threshold
+    pub show_all: bool,
+}
+
+impl Matching {
+    /// Known files matching `hash`
+    fn matches(
+        &self,
+        matcher: &Matcher,
+        hash: &FuzzyHash,
+        name: String,
+    ) -> io::Result<FileMatches> {
+        if !self.show_all {
+            return matcher
+                .match_hash(hash, name, self.threshold)
+                .map_err(to_io);
+        }
+        Ok(FileMatches {
+            filename: name,
+            matches: matcher
+                .known()
+                .iter()
+                .map(|known| KnownMatch {
+                    filename: known.filename.clone(),
+                    source: known.source.clone(),
+                    score: hash.compare_to(&known.hash).unwrap_or(0),
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Print the inputs as a signature file, the header is only printed once there is a hash
+pub fn hash<W: Write>(inputs: &mut Inputs, paths: &[String], output: W) -> io::Result<()> {
+    let mut output = Some(output);
+    let mut writer = None;
+    inputs.hash(paths, |hash, name| {
+        if writer.is_none() {
+            if let Some(output) = output.take() {
+                writer = Some(Writer::new(output, Header::default()).map_err(to_io)?);
+            }
+        }
+        match writer.as_mut() {
+            Some(writer) => writer.write(&hash, &name).map_err(to_io),
+            None => Ok(()),
+        }
+    })?;
+
+    match (writer, output) {
+        (Some(mut writer), _) => writer.flush().map_err(to_io),
+        (None, Some(mut output)) => output.flush(),
+        (None, None) => Ok(()),
+    }
+}
+
+/// `-m`: match the inputs against the known signature files
+pub fn match_known<W: Write>(
+    inputs: &mut Inputs,
+    paths: &[String],
+    known: &[String],
+    matching: &Matching,
+    mut output: W,
+) -> io::Result<()> {
+    let matcher = load(known)?;
+    inputs.hash(paths, |hash, name| {
+        write!(output, "{}", matching.matches(&matcher, &hash, name)?)
+    })?;
+    output.flush()
+}
+
+/// `-k`: match the entries of the input signature files against the known signature files
+pub fn match_signatures<W: Write>(
+    inputs: &mut Inputs,
+    paths: &[String],
+    known: &[String],
+    matching: &Matching,
+    mut output: W,
+) -> io::Result<()> {
+    let matcher = load(known)?;
+    each_signature(inputs, paths, |hash, source, filename| {
+        let name = format!("{}:{}", source, filename);
+        write!(output, "{}", matching.matches(&matcher, &hash, name)?)
+    })?;
+    output.flush()
+}
+
+/// `-x`: compare the entries of the input signature files with each other
+pub fn compare_signatures<W: Write>(
+    inputs: &mut Inputs,
+    paths: &[String],
+    matching: &Matching,
+    mut output: W,
+) -> io::Result<()> {
+    let mut matcher = Matcher::new();
+    each_signature(inputs, paths, |hash, source, filename| {
+        let name = format!("{}:{}", source, filename);
+        write!(output, "{}", matching.matches(&matcher, &hash, name)?)?;
+        matcher.add(hash, filename, source).map_err(to_io)
+    })?;
+    output.flush()
+}
+
+/// `-d`: compare every input with the inputs before it
+pub fn directory<W: Write>(
+    inputs: &mut Inputs,
+    paths: &[String],
+    matching: &Matching,
+    mut output: W,
+) -> io::Result<()> {
+    let mut matcher = Matcher::new();
+    inputs.hash(paths, |hash, name| {
+        write!(
+            output,
+            "{}",
+            matching.matches(&matcher, &hash, name.clone())?
+        )?;
+        matcher.add(hash, name, "").map_err(to_io)
+    })?;
+    output.flush()
+}
+
+/// `-p`: for every input, list the other inputs it matches followed by a blank line
+pub fn pretty<W: Write>(
+    inputs: &mut Inputs,
+    paths: &[String],
+    matching: &Matching,
+    mut output: W,
+) -> io::Result<()> {
+    let (hashes, names) = hash_all(inputs, paths)?;
+    let mut index = Index::new();
+    for hash in &hashes {
+        index.insert(hash.clone()).map_err(to_io)?;
+    }
+
+    for (i, hash) in hashes.iter().enumerate() {
+        let scores: Vec<(usize, u32)> = if matching.show_all {
+            (0..hashes.len())
+                .map(|j| (j, hash.compare_to(&hashes[j]).unwrap_or(0)))
+                .collect()
+        } else {
+            let mut found = index
+                .query(hash, matching.threshold.saturating_add(1))
+                .map_err(to_io)?;
+            found.sort_by_key(|m| m.id);
+            found.into_iter().map(|m| (m.id, m.score)).collect()
+        };
+
+        let mut printed = false;
+        for (j, score) in scores.into_iter().filter(|(j, _)| *j != i) {
+            writeln!(output, "{} matches {} ({})", names[i], names[j], score)?;
+            printed = true;
+        }
+        if printed {
+            writeln!(output)?;
+        }
+    }
+    output.flush()
+}
+
+/// `-g`: print the clusters of inputs linked by matches
+pub fn cluster<W: Write>(
+    inputs: &mut Inputs,
+    paths: &[String],
+    matching: &Matching,
+    mut output: W,
+) -> io::Result<()> {
+    let (hashes, names) = hash_all(inputs, paths)?;
+    let clustering =
+        cluster::single_linkage(&hashes, matching.threshold.saturating_add(1)).map_err(to_io)?;
+
+    for found in clustering.clusters {
+        writeln!(output, "** Cluster size {}", found.members.len())?;
+        for member in found.members {
+            writeln!(output, "{}", names[member])?;
+        }
+        writeln!(output)?;
+    }
+    output.flush()
+}
+
+fn hash_all(inputs: &mut Inputs, paths: &[String]) -> io::Result<(Vec<FuzzyHash>, Vec<String>)> {
+    let mut hashes = Vec::new();
+    let mut names = Vec::new();
+    inputs.hash(paths, |hash, name| {
+        hashes.push(hash);
+        names.push(name);
+        Ok(())
+    })?;
+    Ok((hashes, names))
+}
+
+/// Load the known signature files, any problem with them is fatal
+fn load(known: &[String]) -> io::Result<Matcher> {
+    let mut matcher = Matcher::new();
+    for path in known {
+        matcher
+            .load(path)
+            .map_err(|e| io::Error::other(format!("{}: {}", path, e)))?;
+    }
+    Ok(matcher)
+}
+
+/// Pass every entry of the input signature files to `found`, reporting unreadable files and
+/// invalid entries as input errors
+fn each_signature<F>(inputs: &mut Inputs, paths: &[String], mut found: F) -> io::Result<()>
+where
+    F: FnMut(FuzzyHash, String, String) -> io::Result<()>,
+{
+    for path in paths {
+        let reader = match File::open(path)
+            .map_err(fuzzyhash::error::Error::from)
+            .and_then(|file| Reader::new(BufReader::new(file)))
+        {
+            Ok(reader) => reader,
+            Err(e) => {
+                inputs.error(path, &e)?;
+                continue;
+            }
+        };
+        for entry in reader {
+            match entry {
+                Ok(entry) => {
+                    let filename = String::from_utf8_lossy(&entry.filename).into_owned();
+                    found(entry.hash, path.clone(), filename)?
+                }
+                Err(e) => inputs.error(path, &e)?,
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn to_io(error: fuzzyhash::error::Error) -> io::Error {
+    io::Error::other(error.to_string())
+}
@@ -0,0 +1,290 @@
+//! Grouping of similar fuzzy hashes, like `ssdeep -g`.
+//!
+//! Clustering needs every pair of digests scoring at or above a threshold. Rather than comparing
+//! all pairs, the digests are put in an [`Index`] and each one is only compared with the
+//! candidates sharing a block size and substring with it.
+//!
+//! # Example
+//! ```
+//! use fuzzyhash::{cluster, FuzzyHash};
+//!
+//! let hashes: Vec<FuzzyHash> = vec![
+//!     "96:U57GjXnLt9co6pZwvLhJluvrszNgMFwO6MFG8SvkpjTWf:Hj3BeoEcNJ0TspgIG8SvkpjTg".parse().unwrap(),
+//!     "3:YKKGhR0tn:YRGRmn".parse().unwrap(),
+//!     "96:U57GjXnLt9co6pZwvLhJluvrs1eRTxYARdEallia:Hj3BeoEcNJ0TsI9xYeia3R".parse().unwrap(),
+//! ];
+//!
+//! let clustering = cluster::single_linkage(&hashes, 50).unwrap();
+//! assert_eq!(clustering.clusters.len(), 1);
+//! assert_eq!(clustering.clusters[0].members, vec![0, 2]);
+//! assert_eq!(clustering.unclustered, vec![1]);
+//! ```
+
+use super::{index::Index, FuzzyHash, Result};
+
+/// A group of similar digests
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Cluster {
+    /// Positions of the member digests in the clustered slice, in ascending order
+    pub members: Vec<usize>,
+    /// The member with the highest total score against the other members it is linked to,
+    /// the lowest position wins ties
+    pub representative: usize,
+    /// Scores of the linked pairs within the cluster
+    pub stats: ClusterStats,
+}
+
+/// Statistics of the pairwise scores which link the members of a cluster
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClusterStats {
+    /// Number of linked pairs, each scoring at least the clustering threshold
+    pub pairs: usize,
+    /// Lowest pairwise score
+    pub min_score: u32,
+    /// Highest pairwise score
+    pub max_score: u32,
+    /// Mean pairwise score
+    pub mean_score: f64,
+}
+
+/// Result of clustering a collection of digests
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Clustering {
+    /// Clusters of two or more digests, ordered by their first member
+    pub clusters: Vec<Cluster>,
+    /// Positions of the digests which do not belong to any cluster
+    pub unclustered: Vec<usize>,
+}
+
+/// Single-linkage clustering: two digests are in the same cluster when they are connected by a
+/// chain of pairs scoring at least `threshold`. Equivalent to `ssdeep -g`.
+///
+/// # Arguments
+/// * `hashes` - finalized fuzzy hashes to cluster
+/// * `threshold` - minimum score, at least 1, for two digests to be linked
+pub fn single_linkage(hashes: &[FuzzyHash], threshold: u32) -> Result<Clustering> {
+    let edges = edges(hashes, threshold)?;
+
+    let mut sets = UnionFind::new(hashes.len());
+    for (first, second, _) in &edges {
+        sets.union(*first, *second);
+    }
+
+    let labels: Vec<Option<usize>> = (0..hashes.len()).map(|i| Some(sets.find(i))).collect();
+    Ok(build(&labels, &edges))
+}
+
+/// Density-based clustering in the style of DBSCAN. A digest with at least `min_neighbours`
+/// other digests scoring `threshold` or more against it is a core digest. Linked core digests
+/// form clusters, other digests join the cluster of the first core digest they are linked to,
+/// and the remainder are left unclustered. This keeps chains of loosely related samples from
+/// merging unrelated families, which single linkage is prone to.
+///
+/// # Arguments
+/// * `hashes` - finalized fuzzy hashes to cluster
+/// * `threshold` - minimum score, at least 1, for two digests to be neighbours
+/// * `min_neighbours` - number of neighbours which makes a digest a core digest
+///
+/// # Example
+/// ```
+/// use fuzzyhash::{cluster, FuzzyHash};
+///
+/// let hashes: Vec<FuzzyHash> = vec![
+///     "96:U57GjXnLt9co6pZwvLhJluvrszNgMFwO6MFG8SvkpjTWf:Hj3BeoEcNJ0TspgIG8SvkpjTg".parse().unwrap(),
+///     "96:U57GjXnLt9co6pZwvLhJluvrs1eRTxYARdEallia:Hj3BeoEcNJ0TsI9xYeia3R".parse().unwrap(),
+/// ];
+///
+/// assert_eq!(cluster::density(&hashes, 50, 1).unwrap().clusters.len(), 1);
+/// assert_eq!(cluster::density(&hashes, 50, 2).unwrap().unclustered, vec![0, 1]);
+/// ```
+pub fn density(hashes: &[FuzzyHash], threshold: u32, min_neighbours: usize) -> Result<Clustering> {
+    let edges = edges(hashes, threshold)?;
+
+    let mut neighbours = vec![Vec::new(); hashes.len()];
+    for (first, second, _) in &edges {
+        neighbours[*first].push(*second);
+        neighbours[*second].push(*first);
+    }
+    let core: Vec<bool> = neighbours
+        .iter()
+        .map(|n| !n.is_empty() && n.len() >= min_neighbours)
+        .collect();
+
+    let mut sets = UnionFind::new(hashes.len());
+    for (first, second, _) in &edges {
+        if core[*first] && core[*second] {
+            sets.union(*first, *second);
+        }
+    }
+
+    let labels: Vec<Option<usize>> = (0..hashes.len())
+        .map(|i| {
+            if core[i] {
+                Some(sets.find(i))
+            } else {
+                neighbours[i]
+                    .iter()
+                    .filter(|n| core[**n])
+                    .min()
+                    .map(|n| sets.find(*n))
+            }
+        })
+        .collect();
+
+    // border digests only keep the links to the cluster they joined
+    let edges: Vec<(usize, usize, u32)> = edges
+        .into_iter()
+        .filter(|(first, second, _)| {
+            labels[*first].is_some()
+                && labels[*first] == labels[*second]
+                && (core[*first] || core[*second])
+        })
+        .collect();
+
+    Ok(build(&labels, &edges))
+}
+
+/// Every pair of digests scoring at least `threshold`, as `(first, second, score)` with
+/// `first < second`
+fn edges(hashes: &[FuzzyHash], threshold: u32) -> Result<Vec<(usize, usize, u32)>> {
+    let mut index = Index::new();
+    for hash in hashes {
+        index.insert(hash.clone())?;
+    }
+
+    let mut edges = Vec::new();
+    for (first, hash) in hashes.iter().enumerate() {
+        for found in index.query(hash, threshold)? {
+            if found.id > first {
+                edges.push((first, found.id, found.score));
+            }
+        }
+    }
+    Ok(edges)
+}
+
+/// Group digests sharing a label into clusters
+fn build(labels: &[Option<usize>], edges: &[(usize, usize, u32)]) -> Clustering {
+    let mut members_by_label: Vec<Vec<usize>> = vec![Vec::new(); labels.len()];
+    for (i, label) in labels.iter().enumerate() {
+        if let Some(label) = label {
+            members_by_label[*label].push(i);
+        }
+    }
+
+    let mut clusters = Vec::new();
+    let mut unclustered = Vec::new();
+    for members in members_by_label {
+        if members.len() == 1 {
+            unclustered.push(members[0]);
+        } else if members.len() > 1 {
+            clusters.push(members);
+        }
+    }
+    unclustered.extend(
+        labels
+            .iter()
+            .enumerate()
+            .filter(|(_, label)| label.is_none())
+            .map(|(i, _)| i),
+    );
+    unclustered.sort_unstable();
+    clusters.sort_by_key(|members| members[0]);
+
+    let mut cluster_of = vec![None; labels.len()];
+    for (c, members) in clusters.iter().enumerate() {
+        for member in members {
+            cluster_of[*member] = Some(c);
+        }
+    }
+
+    let mut totals = vec![0u64; labels.len()];
+    let mut scores: Vec<Vec<u32>> = vec![Vec::new(); clusters.len()];
+    for (first, second, score) in edges {
+        if let Some(c) = cluster_of[*first] {
+            if cluster_of[*second] == Some(c) {
+                totals[*first] += u64::from(*score);
+                totals[*second] += u64::from(*score);
+                scores[c].push(*score);
+            }
+        }
+    }
+
+    let clusters = clusters
+        .into_iter()
+        .zip(scores)
+        .map(|(members, scores)| {
+            let representative = *members
+                .iter()
+                .max_by(|a, b| totals[**a].cmp(&totals[**b]).then(b.cmp(a)))
+                .unwrap_or(&members[0]);
+            let stats = ClusterStats {
+                pairs: scores.len(),
+                min_score: scores.iter().copied().min().unwrap_or(0),
+                max_score: scores.iter().copied().max().unwrap_or(0),
+                mean_score: if scores.is_empty() {
+                    0.0
+                } else {
+                    scores.iter().map(|s| f64::from(*s)).sum::<f64>() / scores.len() as f64
+                },
+            };
+            Cluster {
+                members,
+                representative,
+                stats,
+            }
+        })
+        .collect();
+
+    Clustering {
+        clusters,
+        unclustered,
+    }
+}
+
+/// Disjoint sets with path compression and union by rank
+struct UnionFind {
+    parent: Vec<usize>,
+    rank: Vec<u8>,
+}
+
+impl UnionFind {
+    fn new(len: usize) -> Self {
+        Self {
+            parent: (0..len).collect(),
+            rank: vec![0; len],
+        }
+    }
+
+    fn find(&mut self, i: usize) -> usize {
+        let mut root = i;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut i = i;
+        while self.parent[i] != root {
+            let next = self.parent[i];
+            self.parent[i] = root;
+            i = next;
+        }
+        root
+    }
+
+    fn union(&mut self, first: usize, second: usize) {
+        let (first, second) = (self.find(first), self.find(second));
+        if first == second {
+            return;
+        }
+        if self.rank[first] < self.rank[second] {
+            self.parent[first] = second;
+        } else {
+            self.parent[second] = first;
+            if self.rank[first] == self.rank[second] {
+                self.rank[first] += 1;
+            }
+        }
+    }
+}
@@ -30,21 +30,97 @@ fn compute_distance(s1: &[u8], s2: &[u8]) -> u32 {
     t1[s2.len()]
 }
 
-fn has_common_substring(first: &[u8], second: &[u8]) -> bool {
-    let first_length = first.len();
-    let second_length = second.len();
-    let mut i: usize = 0;
-    let mut hashes: Vec<u32> = vec![0; constants::SPAM_SUM_LENGTH as usize];
-    let mut state = Roll::new();
+/// A step of the alignment of two signatures
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Edit {
+    /// The next characters of both signatures are equal
+    Keep,
+    /// The next character of the first signature is replaced by the next one of the second
+    Replace,
+    /// The next character of the first signature has no counterpart in the second
+    First,
+    /// The next character of the second signature has no counterpart in the first
+    Second,
+}
 
-    while i < first_length && first[i] != 0 {
-        state.hash(first[i]);
-        hashes[i] = state.sum();
-        i += 1;
+/// The same weighted edit distance as `compute_distance`, keeping the whole table to trace back
+/// the edits turning `s1` into `s2`
+pub(crate) fn compute_alignment(s1: &[u8], s2: &[u8]) -> (u32, Vec<Edit>) {
+    let width = s2.len() + 1;
+    let mut table = vec![0; (s1.len() + 1) * width];
+    for (i2, item) in table.iter_mut().enumerate().take(width) {
+        *item = i2 as u32 * REMOVE_COST;
+    }
+    for i1 in 0..s1.len() {
+        let (done, row) = table.split_at_mut((i1 + 1) * width);
+        let previous = &done[i1 * width..];
+        row[0] = (i1 as u32 + 1) * INSERT_COST;
+        for i2 in 0..s2.len() {
+            let cost_a = previous[i2 + 1] + INSERT_COST;
+            let cost_d = row[i2] + REMOVE_COST;
+            let cost_r = previous[i2] + if s1[i1] == s2[i2] { 0 } else { REPLACE_COST };
+            row[i2 + 1] = min(min(cost_a, cost_d), cost_r);
+        }
     }
 
-    let num_hashes = i;
-    state = Roll::new();
+    let mut edits = Vec::with_capacity(s1.len() + s2.len());
+    let (mut i1, mut i2) = (s1.len(), s2.len());
+    while i1 > 0 || i2 > 0 {
+        let cost = table[i1 * width + i2];
+        if i1 > 0 && i2 > 0 {
+            let same = s1[i1 - 1] == s2[i2 - 1];
+            let replace = if same { 0 } else { REPLACE_COST };
+            if table[(i1 - 1) * width + i2 - 1] + replace == cost {
+                edits.push(if same { Edit::Keep } else { Edit::Replace });
+                i1 -= 1;
+                i2 -= 1;
+                continue;
+            }
+        }
+        if i1 > 0 && table[(i1 - 1) * width + i2] + INSERT_COST == cost {
+            edits.push(Edit::First);
+            i1 -= 1;
+        } else {
+            edits.push(Edit::Second);
+            i2 -= 1;
+        }
+    }
+    edits.reverse();
+    (table[table.len() - 1], edits)
+}
+
+/// Rolling hash of the window ending at each position of `signature`, up to the first nul byte
+pub(crate) fn rolling_hashes(signature: &[u8]) -> Vec<u32> {
+    let mut state = Roll::new();
+    signature
+        .iter()
+        .take_while(|c| **c != 0)
+        .map(|c| {
+            state.hash(*c);
+            state.sum()
+        })
+        .collect()
+}
+
+/// Distinct non-zero rolling hashes of every full `ROLLING_WINDOW` sized substring of
+/// `signature`. Two signatures can only have a common substring if these sets intersect.
+pub(crate) fn substring_hashes(signature: &[u8]) -> Vec<u32> {
+    let mut hashes: Vec<u32> = rolling_hashes(signature)
+        .into_iter()
+        .skip(constants::ROLLING_WINDOW - 1)
+        .filter(|h| *h != 0)
+        .collect();
+    hashes.sort_unstable();
+    hashes.dedup();
+    hashes
+}
+
+fn has_common_substring(first: &[u8], second: &[u8]) -> bool {
+    let second_length = second.len();
+    let mut i: usize;
+    let hashes = rolling_hashes(first);
+    let num_hashes = hashes.len();
+    let mut state = Roll::new();
 
     i = 0;
     while i < second_length && second[i] != 0 {
@@ -100,7 +176,7 @@ fn has_common_substring(first: &[u8], second: &[u8]) -> bool {
     false
 }
 
-fn eliminate_sequences(input: Vec<u8>) -> Vec<u8> {
+pub(crate) fn eliminate_sequences(input: Vec<u8>) -> Vec<u8> {
     let mut result: Vec<u8> = vec![0; input.len()];
     let mut i = 0;
 
@@ -117,7 +193,7 @@ fn eliminate_sequences(input: Vec<u8>) -> Vec<u8> {
     let mut j = 3;
 
     while i < input.len() {
-        let current = input[j];
+        let current = input[i];
         if current != input[i - 1] || current != input[i - 2] || current != input[i - 3] {
             result[j] = input[i];
             j += 1;
@@ -131,90 +207,220 @@ fn eliminate_sequences(input: Vec<u8>) -> Vec<u8> {
     result
 }
 
-fn score_strings(first: Vec<u8>, second: Vec<u8>, block_size: u32) -> Result<u32> {
+fn score_strings(first: &[u8], second: &[u8], block_size: u32) -> Result<u32> {
     if first.len() > constants::SPAM_SUM_LENGTH as usize
         || second.len() > constants::SPAM_SUM_LENGTH as usize
     {
         return Ok(0);
     }
 
-    if !has_common_substring(&first, &second) {
+    if !has_common_substring(first, second) {
         return Err(Error::NoCommonSubstrings);
     }
 
-    let mut score = compute_distance(&first, &second);
-    score = (score * constants::SPAM_SUM_LENGTH) / ((first.len() + second.len()) as u32);
+    Ok(distance_score(
+        compute_distance(first, second),
+        first.len(),
+        second.len(),
+        block_size,
+    ))
+}
+
+/// Score two signatures computed at `block_size`, without the shortcut for equal signatures
+/// of `compare_signatures`
+pub(crate) fn score_signatures(first: &str, second: &str, block_size: u32) -> u32 {
+    score_strings(
+        &eliminate_sequences(first.as_bytes().to_vec()),
+        &eliminate_sequences(second.as_bytes().to_vec()),
+        block_size,
+    )
+    .unwrap_or(0)
+}
+
+/// Scale an edit distance between signatures of the given lengths to a score out of 100
+fn distance_score(distance: u32, first_len: usize, second_len: usize, block_size: u32) -> u32 {
+    let mut score = (distance * constants::SPAM_SUM_LENGTH) / ((first_len + second_len) as u32);
     score = (100 * score) / 64;
     if score >= 100 {
-        return Ok(0);
+        return 0;
     }
 
     score = 100 - score;
 
-    let match_size =
-        block_size / constants::MIN_BLOCK_SIZE * (min(first.len(), second.len()) as u32);
+    let match_size = block_size / constants::MIN_BLOCK_SIZE * (min(first_len, second_len) as u32);
 
-    Ok(if score > match_size {
+    if score > match_size {
         match_size
     } else {
         score
-    })
+    }
+}
+
+/// Highest score `score_strings` could give signatures of these lengths. The edit distance is
+/// at least the difference in length, and signatures shorter than the rolling window never
+/// share a substring.
+fn score_strings_bound(first_len: usize, second_len: usize, block_size: u32) -> u32 {
+    if first_len > constants::SPAM_SUM_LENGTH as usize
+        || second_len > constants::SPAM_SUM_LENGTH as usize
+        || first_len < constants::ROLLING_WINDOW
+        || second_len < constants::ROLLING_WINDOW
+    {
+        return 0;
+    }
+
+    let distance = (max(first_len, second_len) - min(first_len, second_len)) as u32;
+    distance_score(distance, first_len, second_len, block_size)
+}
+
+/// Split a fuzzy hash string into its block size and signatures, validating each part
+pub(crate) fn parse(hash: &str) -> Result<(u32, &str, &str)> {
+    let parts: Vec<&str> = hash.split(':').collect();
+    if parts.len() != 3 {
+        return Err(Error::MalformedInput);
+    }
+
+    let block_size = parts[0]
+        .parse::<u32>()
+        .map_err(|_| Error::BlockSizeParse(parts[0].to_string()))?;
+    if block_size.to_string() != parts[0]
+        || !(0..constants::NUM_BLOCKHASHES).any(|i| constants::MIN_BLOCK_SIZE << i == block_size)
+    {
+        return Err(Error::BlockSizeParse(parts[0].to_string()));
+    }
+
+    for signature in &parts[1..] {
+        if signature.len() > constants::SPAM_SUM_LENGTH as usize
+            || !signature
+                .bytes()
+                .all(|c| constants::BASE64_CHARS.as_bytes().contains(&c))
+        {
+            return Err(Error::MalformedInput);
+        }
+    }
+
+    Ok((block_size, parts[1], parts[2]))
+}
+
+/// Whether hashes with these block sizes can be compared: they must be equal or a factor of 2 apart
+pub(crate) fn block_sizes_compatible(first: u32, second: u32) -> bool {
+    first == second || first == second * 2 || second == first * 2
+}
+
+/// Valid block sizes which can be compared against `block_size`, in ascending order
+pub(crate) fn compatible_block_sizes(block_size: u32) -> Vec<u32> {
+    let mut sizes = Vec::with_capacity(3);
+    if block_size > constants::MIN_BLOCK_SIZE {
+        sizes.push(block_size / 2);
+    }
+    sizes.push(block_size);
+    if block_size < constants::MIN_BLOCK_SIZE << (constants::NUM_BLOCKHASHES - 1) {
+        sizes.push(block_size * 2);
+    }
+    sizes
+}
+
+/// A fuzzy hash split into its block size and sequence-eliminated signatures, ready to be scored
+#[derive(Clone, Debug)]
+pub(crate) struct Signatures {
+    pub block_size: u32,
+    pub first: Vec<u8>,
+    pub second: Vec<u8>,
+}
+
+impl Signatures {
+    pub fn new(block_size: u32, first: &str, second: &str) -> Self {
+        Self {
+            block_size,
+            first: eliminate_sequences(first.as_bytes().to_vec()),
+            second: eliminate_sequences(second.as_bytes().to_vec()),
+        }
+    }
+
+    pub fn parse(hash: &str) -> Result<Self> {
+        let (block_size, first, second) = parse(hash)?;
+        Ok(Self::new(block_size, first, second))
+    }
 }
 
 pub(crate) fn compare<S: AsRef<str>, T: AsRef<str>>(first: S, second: T) -> Result<u32> {
     let first_parts: Vec<&str> = first.as_ref().split(':').collect();
     let second_parts: Vec<&str> = second.as_ref().split(':').collect();
 
-    if first_parts.len() != 3 && second_parts.len() != 3 {
+    if first_parts.len() != 3 || second_parts.len() != 3 {
         return Err(Error::MalformedInput);
     }
 
     let first_block_size = match first_parts[0].parse::<u32>() {
         Ok(s) => s,
         Err(_) => {
-            return Err(Error::BlockSizeParse);
+            return Err(Error::BlockSizeParse(first_parts[0].to_string()));
         }
     };
     let second_block_size = match second_parts[0].parse::<u32>() {
         Ok(s) => s,
         Err(_) => {
-            return Err(Error::BlockSizeParse);
+            return Err(Error::BlockSizeParse(second_parts[0].to_string()));
         }
     };
 
-    if first_block_size != second_block_size
-        && first_block_size != second_block_size * 2
-        && second_block_size != first_block_size * 2
-    {
-        return Err(Error::IncompatibleBlockSizes);
+    if !block_sizes_compatible(first_block_size, second_block_size) {
+        return Err(Error::IncompatibleBlockSizes(
+            first_block_size,
+            second_block_size,
+        ));
     }
 
-    let first_block1 = eliminate_sequences(first_parts[1].as_bytes().to_vec());
-    let first_block2 = eliminate_sequences(first_parts[2].as_bytes().to_vec());
+    compare_signatures(
+        &Signatures::new(first_block_size, first_parts[1], first_parts[2]),
+        &Signatures::new(second_block_size, second_parts[1], second_parts[2]),
+    )
+}
 
-    let second_block1 = eliminate_sequences(second_parts[1].as_bytes().to_vec());
-    let second_block2 = eliminate_sequences(second_parts[2].as_bytes().to_vec());
+/// Score two parsed fuzzy hashes, this is the core of `compare`
+pub(crate) fn compare_signatures(first: &Signatures, second: &Signatures) -> Result<u32> {
+    if !block_sizes_compatible(first.block_size, second.block_size) {
+        return Err(Error::IncompatibleBlockSizes(
+            first.block_size,
+            second.block_size,
+        ));
+    }
 
-    if first_block_size == second_block_size && first_block1.len() == second_block1.len() {
-        let mut matched = true;
-        for i in 0..first_block1.len() {
-            if first_block1[i] != second_block1[i] {
-                matched = false;
-                break;
-            }
-        }
-        if matched {
-            return Ok(100);
-        }
+    if first.block_size == second.block_size && first.first == second.first {
+        return Ok(100);
     }
 
-    Ok(if first_block_size == second_block_size {
-        let score1 = score_strings(first_block1, second_block1, first_block_size).unwrap_or(0);
-        let score2 = score_strings(first_block2, second_block2, first_block_size * 2).unwrap_or(0);
+    Ok(if first.block_size == second.block_size {
+        let score1 = score_strings(&first.first, &second.first, first.block_size).unwrap_or(0);
+        let score2 =
+            score_strings(&first.second, &second.second, first.block_size * 2).unwrap_or(0);
         max(score1, score2)
-    } else if first_block_size == second_block_size * 2 {
-        score_strings(first_block1, second_block2, first_block_size)?
+    } else if first.block_size == second.block_size * 2 {
+        score_strings(&first.first, &second.second, first.block_size)?
     } else {
-        score_strings(first_block2, second_block1, second_block_size)?
+        score_strings(&first.second, &second.first, second.block_size)?
     })
 }
+
+/// Highest score `compare_signatures` could give these hashes, computed from the block sizes
+/// and signature lengths without scoring the signatures
+pub(crate) fn score_upper_bound(first: &Signatures, second: &Signatures) -> u32 {
+    if first.block_size == second.block_size {
+        if first.first == second.first {
+            return 100;
+        }
+        max(
+            score_strings_bound(first.first.len(), second.first.len(), first.block_size),
+            score_strings_bound(
+                first.second.len(),
+                second.second.len(),
+                first.block_size * 2,
+            ),
+        )
+    } else if first.block_size == second.block_size * 2 {
+        score_strings_bound(first.first.len(), second.second.len(), first.block_size)
+    } else if second.block_size == first.block_size * 2 {
+        score_strings_bound(first.second.len(), second.first.len(), second.block_size)
+    } else {
+        0
+    }
+}
@@ -0,0 +1,252 @@
+//! Byte ranges two inputs have in common, found by aligning their signatures.
+//!
+//! Every character of a signature is the hash of a chunk of the input (see
+//! [`FuzzyHash::chunks1`]). Aligning the signatures of two inputs with the weighted edit
+//! distance used to score them pairs up equal characters, and so the chunks the inputs most
+//! likely share. The chunks in between were removed from the first input or inserted into the
+//! second one.
+//!
+//! Regions are as precise as the chunks: a few times the block size.
+//!
+//! # Example
+//! ```
+//! use fuzzyhash::diff::{self, Region};
+//!
+//! let first: Vec<u8> = (0..20_000u32).map(|i| (i * i >> 5) as u8 ^ (i >> 8) as u8).collect();
+//! let mut second = first[..10_000].to_vec();
+//! second.extend_from_slice(&[0xaa; 3_000]);
+//! second.extend_from_slice(&first[10_000..]);
+//!
+//! let diff = diff::diff(&first, &second).unwrap();
+//! for region in &diff.regions {
+//!     match region {
+//!         Region::Matched { first, second } => println!("{:?} matches {:?}", first, second),
+//!         Region::Removed(range) => println!("{:?} removed", range),
+//!         Region::Inserted(range) => println!("{:?} inserted", range),
+//!     }
+//! }
+//! ```
+
+use super::{
+    compare::{self, Edit},
+    error::Error,
+    Chunk, FuzzyHash, Result,
+};
+use std::{fs::File, io::Read, ops::Range, path::Path};
+
+/// A region of the inputs, in order of both inputs
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Region {
+    /// Bytes of the first input matching bytes of the second one
+    Matched {
+        /// Range of the first input
+        first: Range<u64>,
+        /// Range of the second input
+        second: Range<u64>,
+    },
+    /// Bytes of the first input with no counterpart in the second one
+    Removed(Range<u64>),
+    /// Bytes of the second input with no counterpart in the first one
+    Inserted(Range<u64>),
+}
+
+/// Regions of two inputs, from the alignment of their signatures at a common block size
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RegionDiff {
+    /// Block size of the aligned signatures
+    pub block_size: u32,
+    /// Weighted edit distance between the aligned signatures
+    pub distance: u32,
+    /// Regions covering both inputs, in order
+    pub regions: Vec<Region>,
+}
+
+impl RegionDiff {
+    /// Number of bytes of the first and of the second input in matched regions
+    pub fn matched_len(&self) -> (u64, u64) {
+        self.regions
+            .iter()
+            .fold((0, 0), |(first_len, second_len), region| match region {
+                Region::Matched { first, second } => (
+                    first_len + first.end - first.start,
+                    second_len + second.end - second.start,
+                ),
+                _ => (first_len, second_len),
+            })
+    }
+}
+
+/// Diff two inputs held in memory
+pub fn diff<A: AsRef<[u8]>, B: AsRef<[u8]>>(first: A, second: B) -> Result<RegionDiff> {
+    diff_hashes(&tracked(first.as_ref())?, &tracked(second.as_ref())?)
+}
+
+/// Diff the files at `first` and `second`
+pub fn diff_files<P: AsRef<Path>, Q: AsRef<Path>>(first: P, second: Q) -> Result<RegionDiff> {
+    diff_hashes(&read(File::open(first)?)?, &read(File::open(second)?)?)
+}
+
+/// Diff two finalized fuzzy hashes built with [`FuzzyHash::tracking_chunks`]
+///
+/// The signatures are aligned at the smallest block size both hashes have a complete
+/// signature for, where chunks are the most precise. Fails with
+/// [`IncompatibleBlockSizes`](Error::IncompatibleBlockSizes) if the inputs have no block size
+/// in common, which happens when their sizes are far apart.
+pub fn diff_hashes(first: &FuzzyHash, second: &FuzzyHash) -> Result<RegionDiff> {
+    let first_chunks = first.all_chunks()?;
+    let second_chunks = second.all_chunks()?;
+    let common: Vec<(u32, &[Chunk], &[Chunk])> = first_chunks
+        .iter()
+        .filter_map(|(block_size, first)| {
+            second_chunks
+                .iter()
+                .find(|(size, _)| size == block_size)
+                .map(|(_, second)| (*block_size, &first[..], &second[..]))
+        })
+        .collect();
+
+    // a full signature leaves everything after its last reset to its last character
+    let full = |chunks: &[Chunk]| chunks.len() >= 64;
+    let (block_size, first_chunks, second_chunks) = match common
+        .iter()
+        .find(|(_, first, second)| !full(first) && !full(second))
+        .or_else(|| common.last())
+    {
+        Some(found) => *found,
+        None => {
+            return Err(Error::IncompatibleBlockSizes(
+                first.block_size()?,
+                second.block_size()?,
+            ))
+        }
+    };
+
+    let (distance, edits) =
+        compare::compute_alignment(&characters(first_chunks), &characters(second_chunks));
+    let mut builder = Builder::default();
+    let (mut first_chunks, mut second_chunks) = (first_chunks.iter(), second_chunks.iter());
+    for edit in edits {
+        match edit {
+            Edit::Keep => {
+                let first = range(first_chunks.next());
+                let second = range(second_chunks.next());
+                builder.matched(first, second);
+            }
+            Edit::Replace => {
+                builder.removed(range(first_chunks.next()));
+                builder.inserted(range(second_chunks.next()));
+            }
+            Edit::First => builder.removed(range(first_chunks.next())),
+            Edit::Second => builder.inserted(range(second_chunks.next())),
+        }
+    }
+
+    // bytes after the last chunk are not behind any character
+    let first_len = first.input_len().unwrap_or(0);
+    let second_len = second.input_len().unwrap_or(0);
+    builder.removed(builder.first_end..first_len);
+    builder.inserted(builder.second_end..second_len);
+    Ok(RegionDiff {
+        block_size,
+        distance,
+        regions: builder.finish(),
+    })
+}
+
+fn tracked(input: &[u8]) -> Result<FuzzyHash> {
+    let mut fuzzy_hash = FuzzyHash::tracking_chunks();
+    fuzzy_hash.update(input);
+    fuzzy_hash.finalize()?;
+    Ok(fuzzy_hash)
+}
+
+fn read<R: Read>(mut reader: R) -> Result<FuzzyHash> {
+    let mut fuzzy_hash = FuzzyHash::tracking_chunks();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(count) => fuzzy_hash.update(&buffer[..count]),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    fuzzy_hash.finalize()?;
+    Ok(fuzzy_hash)
+}
+
+fn characters(chunks: &[Chunk]) -> Vec<u8> {
+    chunks.iter().map(|chunk| chunk.character as u8).collect()
+}
+
+fn range(chunk: Option<&Chunk>) -> Range<u64> {
+    let chunk = chunk.expect("the alignment covers every character");
+    chunk.offset..chunk.offset + chunk.len
+}
+
+/// Collects regions, merging the edits between two matches into one removed and one inserted
+/// region
+#[derive(Default)]
+struct Builder {
+    regions: Vec<Region>,
+    removed: Option<Range<u64>>,
+    inserted: Option<Range<u64>>,
+    first_end: u64,
+    second_end: u64,
+}
+
+impl Builder {
+    fn matched(&mut self, first: Range<u64>, second: Range<u64>) {
+        if first.start == first.end && second.start == second.end {
+            return;
+        }
+        self.flush();
+        self.first_end = first.end;
+        self.second_end = second.end;
+        if let Some(Region::Matched {
+            first: ref mut last_first,
+            second: ref mut last_second,
+        }) = self.regions.last_mut()
+        {
+            last_first.end = first.end;
+            last_second.end = second.end;
+        } else {
+            self.regions.push(Region::Matched { first, second });
+        }
+    }
+
+    fn removed(&mut self, range: Range<u64>) {
+        self.first_end = self.first_end.max(range.end);
+        Self::extend(&mut self.removed, range);
+    }
+
+    fn inserted(&mut self, range: Range<u64>) {
+        self.second_end = self.second_end.max(range.end);
+        Self::extend(&mut self.inserted, range);
+    }
+
+    fn extend(pending: &mut Option<Range<u64>>, range: Range<u64>) {
+        if range.start < range.end {
+            *pending = Some(match pending.take() {
+                Some(pending) => pending.start..range.end,
+                None => range,
+            });
+        }
+    }
+
+    fn flush(&mut self) {
+        if let Some(range) = self.removed.take() {
+            self.regions.push(Region::Removed(range));
+        }
+        if let Some(range) = self.inserted.take() {
+            self.regions.push(Region::Inserted(range));
+        }
+    }
+
+    fn finish(mut self) -> Vec<Region> {
+        self.flush();
+        self.regions
+    }
+}
@@ -0,0 +1,110 @@
+//! A common interface over the similarity digests of this crate.
+//!
+//! [`SimilarityDigest`] is implemented by [`FuzzyHash`] and by every other algorithm, so code
+//! that hashes, stores or compares digests can be written once and used with any of them.
+//! Similarities are always on a 0 to 100 scale, where 100 means the inputs are identical or
+//! nearly so, and 0 means no similarity was found.
+//!
+//! # Example
+//! ```
+//! use fuzzyhash::{digest::SimilarityDigest, tlsh::Tlsh, FuzzyHash};
+//!
+//! fn most_similar<D: SimilarityDigest>(query: &[u8], known: &[&[u8]]) -> Option<usize> {
+//!     let query = D::compute(query).ok()?;
+//!     known
+//!         .iter()
+//!         .enumerate()
+//!         .filter_map(|(i, data)| Some((i, query.similarity(&D::compute(data).ok()?).ok()?)))
+//!         .max_by_key(|&(i, score)| (score, std::cmp::Reverse(i)))
+//!         .map(|(i, _)| i)
+//! }
+//!
+//! let data: Vec<u8> = (0..4096u32).map(|i| (i * i % 251) as u8).collect();
+//! let mut edited = data.clone();
+//! edited[100] ^= 0xff;
+//! let unrelated: Vec<u8> = (0..4096u32).map(|i| (i * 7 % 13) as u8).collect();
+//!
+//! let known: [&[u8]; 2] = [&unrelated, &edited];
+//! assert_eq!(most_similar::<FuzzyHash>(&data, &known), Some(1));
+//! assert_eq!(most_similar::<Tlsh>(&data, &known), Some(1));
+//! ```
+
+use super::{error::Error, FuzzyHash, Result};
+use std::{fmt, fs::File, io::Read, path::Path, str::FromStr};
+
+/// A streaming similarity hasher and the digest it produces
+///
+/// `Default` builds an empty hasher for the algorithm's default parameters. Data is added with
+/// [`update`](SimilarityDigest::update) and the digest is computed by
+/// [`finalize`](SimilarityDigest::finalize). Digests can also be parsed from their string
+/// form, in which case they can be compared and formatted but not updated.
+pub trait SimilarityDigest: Default + Clone + fmt::Display + FromStr<Err = Error> {
+    /// Short name of the algorithm, e.g. `ssdeep`
+    const NAME: &'static str;
+
+    /// Add `input` to the hashed data
+    fn update(&mut self, input: &[u8]);
+
+    /// Compute the digest of the data added so far
+    fn finalize(&mut self) -> Result<()>;
+
+    /// The digest string, the inverse of [`parse`](SimilarityDigest::parse)
+    fn format(&self) -> Result<String>;
+
+    /// Similarity to `other` from 0 to 100, both digests must be finalized
+    fn similarity(&self, other: &Self) -> Result<u32>;
+
+    /// Parse a digest string
+    fn parse(digest: &str) -> Result<Self> {
+        digest.parse()
+    }
+
+    /// Hash `input` with the default parameters
+    fn compute<S: AsRef<[u8]>>(input: S) -> Result<Self> {
+        let mut digest = Self::default();
+        digest.update(input.as_ref());
+        digest.finalize()?;
+        Ok(digest)
+    }
+
+    /// Hash everything `reader` produces with the default parameters
+    fn read<R: Read>(reader: &mut R) -> Result<Self> {
+        let mut digest = Self::default();
+        let mut buffer = vec![0; 64 * 1024];
+        loop {
+            match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(count) => digest.update(&buffer[..count]),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        digest.finalize()?;
+        Ok(digest)
+    }
+
+    /// Hash the file at `path` with the default parameters
+    fn file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::read(&mut File::open(path)?)
+    }
+}
+
+impl SimilarityDigest for FuzzyHash {
+    const NAME: &'static str = "ssdeep";
+
+    fn update(&mut self, input: &[u8]) {
+        FuzzyHash::update(self, input)
+    }
+
+    fn finalize(&mut self) -> Result<()> {
+        FuzzyHash::finalize(self)
+    }
+
+    fn format(&self) -> Result<String> {
+        self.digest().map(str::to_string)
+    }
+
+    fn similarity(&self, other: &Self) -> Result<u32> {
+        FuzzyHash::compare(self.digest()?, other.digest()?)
+    }
+}
@@ -4,49 +4,130 @@ use std::fmt;
 
 /// Errors pertaining to processing fuzzy hashes
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     /// Fuzzy hashes must contain at least one common substring for comparison
     NoCommonSubstrings,
 
-    /// At least one input string is in the wrong format
+    /// At least one input string is not a valid digest of the expected kind
     MalformedInput,
 
-    /// Cannot parse the block size of the string
-    BlockSizeParse,
+    /// Cannot parse the block size of the string, holds the offending token
+    BlockSizeParse(String),
 
     /// Two strings have incompatible block sizes. Sizes must be equal, a multiple or a multiple of
-    /// 2 from each other.
-    IncompatibleBlockSizes,
+    /// 2 from each other. Holds the block sizes of the first and second hash.
+    IncompatibleBlockSizes(u32, u32),
 
     /// String contains too many blocks for comparison
     TooManyBlocks,
 
     /// Unable to produce a valid hash string
     InvalidHashString(std::string::FromUtf8Error),
+
+    /// The fuzzy hash has not been finalized yet
+    NotFinalized,
+
+    /// Reading the data to hash failed
+    Io(std::io::Error),
+
+    /// An on-disk index is damaged, holds a description of the problem
+    CorruptIndex(String),
+
+    /// An on-disk index was written with an unsupported format version
+    UnsupportedIndexVersion(u32),
+
+    /// A line of an ssdeep signature file could not be parsed, holds the line number (starting
+    /// at 1) and a description of the problem
+    InvalidSignatureFile(usize, String),
+
+    /// The input is too short or not varied enough to produce a digest
+    InsufficientData,
+
+    /// Two digests were produced with different algorithm parameters and cannot be compared
+    IncompatibleDigests,
+
+    /// The fuzzy hash was not built to track the chunks behind its signatures
+    NotTracked,
 }
 
 impl std::error::Error for Error {
-    fn description(&self) -> &str {
-        match self {
-            Error::NoCommonSubstrings => "No common substrings were found between two fuzzy hashes",
-            Error::MalformedInput => "Strings are not in proper fuzzy hash format",
-            Error::BlockSizeParse => "Could not parse block sizes in string(s)",
-            Error::IncompatibleBlockSizes => "Fuzzy hashes have incompatible block sizes",
-            Error::TooManyBlocks => "Total number of blocks exceeds limit",
-            Error::InvalidHashString(_) => "Unable to produce a valid hash string",
-        }
-    }
-
-    fn cause(&self) -> Option<&dyn std::error::Error> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             Error::InvalidHashString(e) => Some(e),
+            Error::Io(e) => Some(e),
             _ => None,
         }
     }
 }
 
+/// Errors serialize as the name of their kind, e.g. `"IncompatibleBlockSizes"`
+#[cfg(feature = "serde")]
+impl serde::Serialize for Error {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (index, kind) = match self {
+            Error::NoCommonSubstrings => (0, "NoCommonSubstrings"),
+            Error::MalformedInput => (1, "MalformedInput"),
+            Error::BlockSizeParse(_) => (2, "BlockSizeParse"),
+            Error::IncompatibleBlockSizes(_, _) => (3, "IncompatibleBlockSizes"),
+            Error::TooManyBlocks => (4, "TooManyBlocks"),
+            Error::InvalidHashString(_) => (5, "InvalidHashString"),
+            Error::NotFinalized => (6, "NotFinalized"),
+            Error::Io(_) => (7, "Io"),
+            Error::CorruptIndex(_) => (8, "CorruptIndex"),
+            Error::UnsupportedIndexVersion(_) => (9, "UnsupportedIndexVersion"),
+            Error::InvalidSignatureFile(_, _) => (10, "InvalidSignatureFile"),
+            Error::InsufficientData => (11, "InsufficientData"),
+            Error::IncompatibleDigests => (12, "IncompatibleDigests"),
+            Error::NotTracked => (13, "NotTracked"),
+        };
+        serializer.serialize_unit_variant("Error", index, kind)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Error processing fuzzy hash(es)")
+        match self {
+            Error::NoCommonSubstrings => {
+                write!(
+                    f,
+                    "No common substrings were found between two fuzzy hashes"
+                )
+            }
+            Error::MalformedInput => write!(f, "Malformed digest"),
+            Error::BlockSizeParse(token) => write!(f, "Could not parse block size {:?}", token),
+            Error::IncompatibleBlockSizes(first, second) => write!(
+                f,
+                "Fuzzy hashes have incompatible block sizes {} and {}",
+                first, second
+            ),
+            Error::TooManyBlocks => write!(f, "Total number of blocks exceeds limit"),
+            Error::InvalidHashString(e) => {
+                write!(f, "Unable to produce a valid hash string: {}", e)
+            }
+            Error::NotFinalized => write!(f, "Fuzzy hash has not been finalized"),
+            Error::Io(e) => write!(f, "Unable to read data to hash: {}", e),
+            Error::CorruptIndex(reason) => write!(f, "Index file is corrupt: {}", reason),
+            Error::UnsupportedIndexVersion(version) => {
+                write!(f, "Index file version {} is not supported", version)
+            }
+            Error::InvalidSignatureFile(line, reason) => {
+                write!(f, "Invalid signature file at line {}: {}", line, reason)
+            }
+            Error::InsufficientData => {
+                write!(f, "Input is too short or not varied enough to hash")
+            }
+            Error::IncompatibleDigests => write!(
+                f,
+                "Digests were produced with different parameters and cannot be compared"
+            ),
+            Error::NotTracked => write!(f, "Chunks were not tracked while hashing"),
+        }
     }
 }
@@ -1,12 +1,27 @@
-use super::{blockhash, constants, error::Error, roll, Result};
+use super::{blockhash, constants, error::Error, roll, Chunk, Result};
 
 /// The fuzzy hasher
+#[derive(Clone)]
 pub struct Hasher {
     bh_start: u32,
     bh_end: u32,
     bh: Vec<blockhash::Context>,
     total_size: u32,
     roll: roll::Roll,
+    tracking: Option<Tracking>,
+}
+
+/// Where the characters of each block hash's signature come from
+#[derive(Clone)]
+struct Tracking {
+    /// Offset of the next byte
+    offset: u64,
+    /// Start of the chunk each block hash is currently hashing
+    starts: Vec<u64>,
+    /// End of the last chunk that triggered in each block hash
+    last_trigger: Vec<u64>,
+    /// Chunks whose character is final, one per character of each block hash's digest
+    chunks: Vec<Vec<Chunk>>,
 }
 
 impl Default for Hasher {
@@ -24,11 +39,26 @@ impl Hasher {
             bh: vec![blockhash::Context::new(); constants::NUM_BLOCKHASHES as usize],
             total_size: 0,
             roll: roll::Roll::new(),
+            tracking: None,
         };
         h.bh[0].reset(true);
         h
     }
 
+    /// Build a fuzzy hasher recording the input region behind each signature character
+    pub fn tracking_chunks() -> Hasher {
+        let count = constants::NUM_BLOCKHASHES as usize;
+        Hasher {
+            tracking: Some(Tracking {
+                offset: 0,
+                starts: vec![0; count],
+                last_trigger: vec![0; count],
+                chunks: vec![Vec::new(); count],
+            }),
+            ..Hasher::new()
+        }
+    }
+
     fn memcpy_eliminate_sequences() -> usize {
         // TODO
         0
@@ -42,9 +72,18 @@ impl Hasher {
             self.bh[self.bh_end as usize].digest[0] = 0;
             self.bh[self.bh_end as usize].half_digest = 0;
             self.bh[self.bh_end as usize].d_len = 0;
+            if let Some(ref mut tracking) = self.tracking {
+                let end = self.bh_end as usize;
+                tracking.starts[end] = tracking.starts[end - 1];
+                tracking.chunks[end].clear();
+            }
             self.bh_end += 1;
         } else if self.bh_end == constants::NUM_BLOCKHASHES - 1 {
             self.bh[self.bh_end as usize].h = self.bh[(self.bh_end - 1) as usize].h;
+            if let Some(ref mut tracking) = self.tracking {
+                let end = self.bh_end as usize;
+                tracking.starts[end] = tracking.starts[end - 1];
+            }
         }
     }
 
@@ -88,6 +127,21 @@ impl Hasher {
             self.bh[j as usize].half_digest =
                 constants::get_base64_char((self.bh[j as usize].half_h % 64) as usize);
 
+            if let Some(ref mut tracking) = self.tracking {
+                let end = tracking.offset + 1;
+                tracking.last_trigger[j as usize] = end;
+                // the last character keeps being replaced until the digest is complete
+                if self.bh[j as usize].d_len < constants::SPAM_SUM_LENGTH - 1 {
+                    let start = tracking.starts[j as usize];
+                    tracking.chunks[j as usize].push(Chunk {
+                        offset: start,
+                        len: end - start,
+                        character: self.bh[j as usize].digest[pos] as char,
+                    });
+                    tracking.starts[j as usize] = end;
+                }
+            }
+
             if self.bh[j as usize].d_len < constants::SPAM_SUM_LENGTH - 1 {
                 self.bh[j as usize].reset(false);
             } else {
@@ -95,6 +149,15 @@ impl Hasher {
             }
             j += 1;
         }
+
+        if let Some(ref mut tracking) = self.tracking {
+            tracking.offset += 1;
+        }
+    }
+
+    /// Total number of bytes added to the `Hasher`
+    pub fn total_size(&self) -> u32 {
+        self.total_size
     }
 
     /// Add data to the `Hasher`.
@@ -105,12 +168,9 @@ impl Hasher {
         }
     }
 
-    /// Compute the hash of the data and return a `String` representation
-    pub fn digest(&mut self, flags: constants::Modes) -> Result<String> {
-        let mut result = vec![0; constants::MAX_RESULT_LENGTH as usize];
-        let mut pos = 0;
+    /// Index of the block hash `digest` uses for the first signature
+    fn block_index(&self) -> Result<u32> {
         let mut bi = self.bh_start;
-        let mut h = self.roll.sum();
 
         while (constants::MIN_BLOCK_SIZE << bi) * constants::SPAM_SUM_LENGTH < self.total_size {
             bi += 1;
@@ -126,6 +186,114 @@ impl Hasher {
         while bi > self.bh_start && self.bh[bi as usize].d_len < constants::SPAM_SUM_LENGTH / 2 {
             bi -= 1;
         }
+        Ok(bi)
+    }
+
+    /// Chunks behind the characters of both signatures of `digest`, or `None` if the hasher
+    /// is not tracking chunks
+    pub fn signature_chunks(&self) -> Result<Option<(Vec<Chunk>, Vec<Chunk>)>> {
+        if self.tracking.is_none() {
+            return Ok(None);
+        }
+        let bi = self.block_index()?;
+        let first = self.block_chunks(bi, false);
+        let second = if bi < self.bh_end - 1 {
+            self.block_chunks(bi + 1, true)
+        } else {
+            // `digest` writes a closing character here but does not keep it, so the second
+            // signature is empty
+            Vec::new()
+        };
+        Ok(Some((first, second)))
+    }
+
+    /// Block sizes still being hashed, each with the chunks behind its untruncated signature,
+    /// or `None` if the hasher is not tracking chunks
+    pub fn all_chunks(&self) -> Option<Vec<(u32, Vec<Chunk>)>> {
+        self.tracking.as_ref()?;
+        Some(
+            (self.bh_start..self.bh_end)
+                .map(|bi| {
+                    (
+                        constants::MIN_BLOCK_SIZE << bi,
+                        self.block_chunks(bi, false),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    /// Smallest block size still being hashed, and the untruncated signature of every block
+    /// size from there on, each ending like the first signature of `digest`
+    pub fn signatures(&self) -> (u32, Vec<String>) {
+        let signatures = (self.bh_start..self.bh_end)
+            .map(|bi| {
+                let bh = &self.bh[bi as usize];
+                let mut signature = bh.digest[..bh.d_len as usize].to_vec();
+                if self.roll.sum() != 0 {
+                    signature.push(constants::get_base64_char((bh.h % 64) as usize));
+                } else if bh.digest[bh.d_len as usize] != 0 {
+                    signature.push(bh.digest[bh.d_len as usize]);
+                }
+                signature.into_iter().map(char::from).collect()
+            })
+            .collect();
+        (constants::MIN_BLOCK_SIZE << self.bh_start, signatures)
+    }
+
+    /// Chunks behind the signature of block hash `bi`, truncated like the second signature of
+    /// `digest` if `truncate` is set
+    fn block_chunks(&self, bi: u32, truncate: bool) -> Vec<Chunk> {
+        let tracking = match self.tracking {
+            Some(ref tracking) => tracking,
+            None => return Vec::new(),
+        };
+        let bh = &self.bh[bi as usize];
+        let done = &tracking.chunks[bi as usize];
+        let count = if truncate {
+            done.len()
+                .min((constants::SPAM_SUM_LENGTH / 2 - 1) as usize)
+        } else {
+            done.len()
+        };
+
+        let mut chunks = done[..count].to_vec();
+        let start = done
+            .get(count)
+            .map(|chunk| chunk.offset)
+            .unwrap_or(tracking.starts[bi as usize]);
+        // the closing character hashes everything after the last kept chunk, unless the rolling
+        // hash is empty, then the digest ends with the character of the last trigger
+        let (end, character) = if self.roll.sum() != 0 {
+            let h = if truncate { bh.half_h } else { bh.h };
+            (
+                tracking.offset,
+                constants::get_base64_char((h % 64) as usize),
+            )
+        } else if truncate {
+            (tracking.last_trigger[bi as usize], bh.half_digest)
+        } else {
+            (
+                tracking.last_trigger[bi as usize],
+                bh.digest[bh.d_len as usize],
+            )
+        };
+        if character != 0 {
+            chunks.push(Chunk {
+                offset: start,
+                len: end - start,
+                character: character as char,
+            });
+        }
+        chunks
+    }
+
+    /// Compute the hash of the data and return a `String` representation
+    pub fn digest(&mut self, flags: constants::Modes) -> Result<String> {
+        let mut result = vec![0; constants::MAX_RESULT_LENGTH as usize];
+        let mut pos = 0;
+        let mut bi = self.block_index()?;
+        let mut h = self.roll.sum();
 
         let actual_blocksize = constants::MIN_BLOCK_SIZE << bi;
         let blocksize_string = actual_blocksize.to_string();
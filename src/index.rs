@@ -0,0 +1,232 @@
+//! Similarity search over a collection of fuzzy hashes.
+//!
+//! Comparing a fuzzy hash against every known digest is linear in the size of the collection.
+//! An [`Index`] instead buckets digests by block size and by the rolling hashes of the 7
+//! character substrings of their signatures. Two signatures can only score above zero when
+//! they share such a substring (or are identical), so a query only scores the digests that
+//! share a bucket with it. Scores are computed by the same code as [`FuzzyHash::compare`], so
+//! results are identical to a brute force search.
+//!
+//! # Example
+//! ```
+//! use fuzzyhash::{index::Index, FuzzyHash};
+//!
+//! let mut index = Index::new();
+//! let id = index
+//!     .insert("96:U57GjXnLt9co6pZwvLhJluvrszNgMFwO6MFG8SvkpjTWf:Hj3BeoEcNJ0TspgIG8SvkpjTg".parse().unwrap())
+//!     .unwrap();
+//!
+//! let query: FuzzyHash = "96:U57GjXnLt9co6pZwvLhJluvrs1eRTxYARdEallia:Hj3BeoEcNJ0TsI9xYeia3R"
+//!     .parse()
+//!     .unwrap();
+//! let matches = index.query(&query, 50).unwrap();
+//! assert_eq!(matches[0].id, id);
+//! assert_eq!(matches[0].score, 63);
+//! ```
+
+use super::{
+    compare::{self, Signatures},
+    constants, search, FuzzyHash, Result,
+};
+use std::collections::HashMap;
+
+#[cfg(feature = "mmap")]
+pub mod disk;
+
+/// A digest in the index which is similar to the query
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Match {
+    /// Identifier of the matching digest, as returned by [`Index::insert`]
+    pub id: usize,
+    /// Similarity score between 1 and 100
+    pub score: u32,
+}
+
+/// Sort matches by descending score, breaking ties by ascending id
+pub(crate) fn sort_matches(matches: &mut [Match]) {
+    matches.sort_by(|a, b| b.score.cmp(&a.score).then(a.id.cmp(&b.id)));
+}
+
+/// Bucket keys of a parsed digest. The first signature is keyed under the block size and the
+/// second under twice the block size, so signatures which `compare` scores against each other
+/// always share a block size key.
+pub(crate) fn substring_keys(signatures: &Signatures) -> Vec<(u32, u32)> {
+    let mut keys: Vec<(u32, u32)> = compare::substring_hashes(&signatures.first)
+        .into_iter()
+        .map(|h| (signatures.block_size, h))
+        .chain(
+            compare::substring_hashes(&signatures.second)
+                .into_iter()
+                .map(|h| (signatures.block_size * 2, h)),
+        )
+        .collect();
+    keys.sort_unstable();
+    keys.dedup();
+    keys
+}
+
+/// Whether the first signature is too short to share a substring with anything, in which
+/// case it can only match an identical signature
+pub(crate) fn is_short(signature: &[u8]) -> bool {
+    signature.len() < constants::ROLLING_WINDOW
+}
+
+struct Entry {
+    hash: FuzzyHash,
+    signatures: Signatures,
+}
+
+/// An in-memory similarity index over fuzzy hashes
+#[derive(Default)]
+pub struct Index {
+    entries: Vec<Option<Entry>>,
+    substrings: HashMap<(u32, u32), Vec<usize>>,
+    short: HashMap<(u32, Vec<u8>), Vec<usize>>,
+    len: usize,
+}
+
+impl Index {
+    /// Build an empty index
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of digests in the index
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the index contains no digests
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Add a finalized fuzzy hash to the index, returning its identifier
+    pub fn insert(&mut self, hash: FuzzyHash) -> Result<usize> {
+        let signatures = Signatures::parse(hash.digest()?)?;
+        let id = self.entries.len();
+
+        for key in substring_keys(&signatures) {
+            self.substrings.entry(key).or_default().push(id);
+        }
+        if is_short(&signatures.first) {
+            self.short
+                .entry((signatures.block_size, signatures.first.clone()))
+                .or_default()
+                .push(id);
+        }
+
+        self.entries.push(Some(Entry { hash, signatures }));
+        self.len += 1;
+        Ok(id)
+    }
+
+    /// Remove a digest from the index, returning it if it was present
+    pub fn remove(&mut self, id: usize) -> Option<FuzzyHash> {
+        let entry = self.entries.get_mut(id)?.take()?;
+
+        for key in substring_keys(&entry.signatures) {
+            if let Some(ids) = self.substrings.get_mut(&key) {
+                ids.retain(|i| *i != id);
+                if ids.is_empty() {
+                    self.substrings.remove(&key);
+                }
+            }
+        }
+        let short_key = (entry.signatures.block_size, entry.signatures.first.clone());
+        if let Some(ids) = self.short.get_mut(&short_key) {
+            ids.retain(|i| *i != id);
+            if ids.is_empty() {
+                self.short.remove(&short_key);
+            }
+        }
+
+        self.len -= 1;
+        Some(entry.hash)
+    }
+
+    /// Get the digest with identifier `id`
+    pub fn get(&self, id: usize) -> Option<&FuzzyHash> {
+        self.entries
+            .get(id)
+            .and_then(|entry| entry.as_ref())
+            .map(|entry| &entry.hash)
+    }
+
+    /// Iterate over the identifiers and digests in the index
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &FuzzyHash)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter_map(|(id, entry)| entry.as_ref().map(|entry| (id, &entry.hash)))
+    }
+
+    /// All digests scoring at least `threshold` against `hash`, sorted by descending score and
+    /// then by identifier. Digests scoring 0 are never returned.
+    ///
+    /// # Arguments
+    /// * `hash` - finalized fuzzy hash to search for
+    /// * `threshold` - minimum score of returned matches
+    pub fn query(&self, hash: &FuzzyHash, threshold: u32) -> Result<Vec<Match>> {
+        let signatures = Signatures::parse(hash.digest()?)?;
+        let mut matches = self.score_candidates(&signatures, threshold.max(1));
+        sort_matches(&mut matches);
+        Ok(matches)
+    }
+
+    /// The `k` digests most similar to `hash`, sorted by descending score and then by
+    /// identifier. Digests scoring 0 are never returned. See [`crate::search`] for how
+    /// candidates are pruned.
+    pub fn top_k(&self, hash: &FuzzyHash, k: usize) -> Result<Vec<Match>> {
+        let signatures = Signatures::parse(hash.digest()?)?;
+        let candidates = self
+            .candidates(&signatures)
+            .into_iter()
+            .filter_map(|id| Some((id, self.signatures(id)?)));
+        Ok(search::select(&signatures, candidates, k))
+    }
+
+    /// Identifiers of digests which may score above zero against `signatures`
+    pub(crate) fn candidates(&self, signatures: &Signatures) -> Vec<usize> {
+        let mut candidates: Vec<usize> = substring_keys(signatures)
+            .iter()
+            .filter_map(|key| self.substrings.get(key))
+            .flatten()
+            .copied()
+            .collect();
+        if is_short(&signatures.first) {
+            if let Some(ids) = self
+                .short
+                .get(&(signatures.block_size, signatures.first.clone()))
+            {
+                candidates.extend(ids);
+            }
+        }
+        candidates.sort_unstable();
+        candidates.dedup();
+        candidates
+    }
+
+    pub(crate) fn signatures(&self, id: usize) -> Option<&Signatures> {
+        self.entries
+            .get(id)
+            .and_then(|entry| entry.as_ref())
+            .map(|entry| &entry.signatures)
+    }
+
+    fn score_candidates(&self, signatures: &Signatures, threshold: u32) -> Vec<Match> {
+        self.candidates(signatures)
+            .into_iter()
+            .filter_map(|id| {
+                let score =
+                    compare::compare_signatures(signatures, self.signatures(id)?).unwrap_or(0);
+                if score >= threshold {
+                    Some(Match { id, score })
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
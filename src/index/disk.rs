@@ -0,0 +1,891 @@
+//! A persistent similarity index, memory-mapped for queries.
+//!
+//! Building an [`Index`](super::Index) over millions of digests at every start up is slow. A
+//! [`DiskIndex`] stores the same block size and substring buckets in a file which is memory-mapped
+//! and queried in place, so opening it costs the same regardless of its size.
+//!
+//! # File format
+//!
+//! All integers are little endian. The file starts with a 32 byte header: the magic bytes
+//! `FUZZYIDX`, the format version, the next identifier to assign (8 bytes), the committed
+//! length of the file (8 bytes) and a CRC-32 of the preceding 28 bytes. Records follow up to the
+//! committed length, each written by [`DiskIndex::create`], [`DiskIndex::append`] or
+//! [`DiskIndex::remove`].
+//!
+//! A record is written after the committed end of the file and committed by rewriting the file
+//! header once it is on disk. If writing is interrupted, the partial record is past the committed
+//! length: it is ignored when the file is opened and overwritten by the next write.
+//!
+//! A segment of digests has a 56 byte header:
+//!
+//! | field            | size |
+//! |------------------|------|
+//! | magic `SEGM`     | 4    |
+//! | entry count      | 4    |
+//! | first id         | 8    |
+//! | partition count  | 4    |
+//! | short count      | 4    |
+//! | key count        | 8    |
+//! | posting count    | 8    |
+//! | string pool size | 8    |
+//! | body CRC-32      | 4    |
+//! | header CRC-32    | 4    |
+//!
+//! followed by a body made of these tables, in order:
+//!
+//! * entries, 24 bytes each, sorted by identifier: identifier (8 bytes), offset of the entry in
+//!   the string pool (8 bytes), block size (4 bytes), and the lengths of the digest, first and
+//!   second signatures (1 byte each) followed by a reserved byte
+//! * block size partitions, 16 bytes each: block size (4 bytes), first key (8 bytes) and key
+//!   count (4 bytes), sorted by block size
+//! * keys, 16 bytes each: substring hash (4 bytes), first posting (8 bytes) and posting count
+//!   (4 bytes), sorted by hash within their partition
+//! * postings, 4 bytes each: entry number within the segment
+//! * short signatures, 16 bytes each: block size (big endian, so entries sort bytewise), entry
+//!   number, signature length and the signature padded to 7 bytes, for first signatures too
+//!   short to have any substrings
+//! * the string pool holding, for each entry, the digest followed by its first and second
+//!   signatures with runs of more than 3 identical characters shortened to 3, ready to be
+//!   scored
+//!
+//! Removed digests are recorded by tombstones: the magic `TOMB`, a count (4 bytes), a CRC-32 of
+//! the identifiers and a CRC-32 of the preceding 12 bytes, followed by the removed identifiers,
+//! 8 bytes each.
+//!
+//! Queries score candidates from their stored signatures, and only [`DiskIndex::get`] parses
+//! the digest.
+//!
+//! Offsets and indexes into the string pool and the key and posting tables are 64 bits wide, so
+//! a segment of tens of millions of digests does not outgrow them. A segment holds at most
+//! `u32::MAX` digests, and writing a larger one fails.
+//!
+//! [`DiskIndex::compact`] rewrites the file as a single segment without the removed digests.
+//! Identifiers are never reused, as in [`Index`](super::Index).
+//!
+//! # Example
+//! ```no_run
+//! use fuzzyhash::{index::disk::DiskIndex, FuzzyHash};
+//!
+//! let known = vec![FuzzyHash::file("/bin/ls").unwrap(), FuzzyHash::file("/bin/cat").unwrap()];
+//! DiskIndex::create("known.idx", &known).unwrap();
+//!
+//! let index = DiskIndex::open("known.idx").unwrap();
+//! for found in index.query(&FuzzyHash::file("/bin/dir").unwrap(), 50).unwrap() {
+//!     println!("{} scored {}", index.get(found.id).unwrap().unwrap(), found.score);
+//! }
+//! ```
+
+use super::{is_short, sort_matches, substring_keys, Match};
+use crate::{
+    compare::{self, Signatures},
+    error::Error,
+    search, FuzzyHash, Result,
+};
+use memmap2::Mmap;
+use std::{
+    convert::TryFrom,
+    fs::{File, OpenOptions},
+    io::{BufWriter, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+const FILE_MAGIC: &[u8; 8] = b"FUZZYIDX";
+const SEGMENT_MAGIC: &[u8; 4] = b"SEGM";
+const TOMBSTONE_MAGIC: &[u8; 4] = b"TOMB";
+const VERSION: u32 = 3;
+const FILE_HEADER_LEN: usize = 32;
+const SEGMENT_HEADER_LEN: usize = 56;
+const TOMBSTONE_HEADER_LEN: usize = 16;
+const ENTRY_LEN: usize = 24;
+const PARTITION_LEN: usize = 16;
+const KEY_LEN: usize = 16;
+const POSTING_LEN: usize = 4;
+const SHORT_LEN: usize = 16;
+const ID_LEN: usize = 8;
+
+/// Location of a segment's tables within the mapped file
+struct Segment {
+    ids: std::ops::Range<usize>,
+    entry_count: usize,
+    partition_count: usize,
+    short_count: usize,
+    entries: usize,
+    partitions: usize,
+    keys: usize,
+    postings: usize,
+    shorts: usize,
+    strings: usize,
+    body: std::ops::Range<usize>,
+    body_crc: u32,
+}
+
+/// Location of a tombstone record's identifiers within the mapped file
+struct Tombstones {
+    body: std::ops::Range<usize>,
+    body_crc: u32,
+}
+
+/// A similarity index stored in a memory-mapped file
+pub struct DiskIndex {
+    path: PathBuf,
+    map: Mmap,
+    segments: Vec<Segment>,
+    tombstones: Vec<Tombstones>,
+    removed: Vec<usize>,
+    next_id: usize,
+    committed: usize,
+    len: usize,
+}
+
+impl DiskIndex {
+    /// Write a new index file at `path` holding `hashes`, replacing any existing file. Digests
+    /// are given identifiers in order, starting from 0.
+    pub fn create<P: AsRef<Path>>(path: P, hashes: &[FuzzyHash]) -> Result<Self> {
+        let digests = hashes
+            .iter()
+            .map(|hash| hash.digest())
+            .collect::<Result<Vec<&str>>>()?;
+        let entries: Vec<(usize, &str)> = digests.into_iter().enumerate().collect();
+        write_file(path.as_ref(), &entries, entries.len())?;
+        Self::open(path)
+    }
+
+    /// Open an existing index file. Headers are checked, use [`verify`](DiskIndex::verify) to
+    /// also check the contents of every record. A partially written record left by an
+    /// interrupted write is ignored.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path.as_ref())?;
+        // Safety: the map is read only and every read from it is bounds checked. Index files
+        // must not be truncated or rewritten by other processes while open.
+        let map = unsafe { Mmap::map(&file)? };
+
+        let header = map
+            .get(..FILE_HEADER_LEN)
+            .ok_or_else(|| corrupt("file is too short"))?;
+        if &header[..8] != FILE_MAGIC {
+            return Err(corrupt("not an index file"));
+        }
+        if read_u32(header, 28) != Some(crc32(&header[..28])) {
+            return Err(corrupt("file header checksum mismatch"));
+        }
+        let version = read_u32(header, 8).unwrap_or_default();
+        if version != VERSION {
+            return Err(Error::UnsupportedIndexVersion(version));
+        }
+        let wide_field = |offset| {
+            read_u64(header, offset)
+                .and_then(|value| usize::try_from(value).ok())
+                .ok_or_else(|| corrupt("file header is out of range"))
+        };
+        let next_id = wide_field(12)?;
+        let committed = wide_field(20)?;
+        if committed < FILE_HEADER_LEN || committed > map.len() {
+            return Err(corrupt("truncated file"));
+        }
+
+        let (mut segments, mut tombstones) = (Vec::new(), Vec::new());
+        let mut removed = Vec::new();
+        let mut offset = FILE_HEADER_LEN;
+        let mut entry_count = 0;
+        // records past the committed length were never committed
+        let records = &map[..committed];
+        while offset < committed {
+            if records.get(offset..offset + 4) == Some(&TOMBSTONE_MAGIC[..]) {
+                let record = read_tombstones(records, offset)?;
+                for i in 0..(record.body.len() / ID_LEN) {
+                    removed.push(read_id(records, record.body.start + i * ID_LEN)?);
+                }
+                offset = record.body.end;
+                tombstones.push(record);
+            } else {
+                let first_id = segments
+                    .last()
+                    .map_or(0, |segment: &Segment| segment.ids.end);
+                let segment = read_segment(records, offset, first_id)?;
+                if segment.ids.end > next_id {
+                    return Err(corrupt("identifier is not below the next identifier"));
+                }
+                offset = segment.body.end;
+                entry_count += segment.entry_count;
+                segments.push(segment);
+            }
+        }
+        removed.sort_unstable();
+        removed.dedup();
+
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            map,
+            segments,
+            tombstones,
+            len: entry_count.saturating_sub(removed.len()),
+            removed,
+            next_id,
+            committed,
+        })
+    }
+
+    /// Number of digests in the index
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the index contains no digests
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of segments of digests in the file, see [`compact`](DiskIndex::compact)
+    pub fn segment_count(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// Add `hashes` to the end of the index file as a new segment, returning the identifier of
+    /// the first one. Existing identifiers are unchanged.
+    pub fn append(&mut self, hashes: &[FuzzyHash]) -> Result<usize> {
+        let digests = hashes
+            .iter()
+            .map(|hash| hash.digest())
+            .collect::<Result<Vec<&str>>>()?;
+        let first_id = self.next_id;
+        let entries: Vec<(usize, &str)> = (first_id..).zip(digests).collect();
+
+        self.commit(first_id + entries.len(), |writer| {
+            write_segment(writer, first_id, &entries)
+        })?;
+        Ok(first_id)
+    }
+
+    /// Remove a digest from the index, returning it if it was present. The digest stays in the
+    /// file, marked as removed, until the index is [compacted](DiskIndex::compact).
+    pub fn remove(&mut self, id: usize) -> Result<Option<FuzzyHash>> {
+        let hash = match self.get(id) {
+            Some(hash) => hash?,
+            None => return Ok(None),
+        };
+        self.commit(self.next_id, |writer| write_tombstones(writer, &[id]))?;
+        Ok(Some(hash))
+    }
+
+    /// Rewrite the index file as a single segment without the removed digests, which also
+    /// speeds up queries after many appends. Identifiers are unchanged.
+    pub fn compact(&mut self) -> Result<()> {
+        let mut entries = Vec::with_capacity(self.len);
+        for segment in &self.segments {
+            for entry in 0..segment.entry_count as u32 {
+                let id = self.entry_id(segment, entry)?;
+                if !self.is_removed(id) {
+                    entries.push((id, self.digest(segment, entry)?));
+                }
+            }
+        }
+
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".compact");
+        let temporary = PathBuf::from(temporary);
+        write_file(&temporary, &entries, self.next_id)?;
+        std::fs::rename(&temporary, &self.path)?;
+
+        *self = Self::open(&self.path)?;
+        Ok(())
+    }
+
+    /// Check the checksum of every record
+    pub fn verify(&self) -> Result<()> {
+        for segment in &self.segments {
+            if crc32(&self.map[segment.body.clone()]) != segment.body_crc {
+                return Err(corrupt("segment checksum mismatch"));
+            }
+        }
+        for tombstones in &self.tombstones {
+            if crc32(&self.map[tombstones.body.clone()]) != tombstones.body_crc {
+                return Err(corrupt("tombstone checksum mismatch"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Write a record after the committed end of the file, then commit it by rewriting the
+    /// file header, and reopen the file
+    fn commit<F>(&mut self, next_id: usize, write: F) -> Result<()>
+    where
+        F: FnOnce(&mut BufWriter<&File>) -> Result<()>,
+    {
+        let file = OpenOptions::new().read(true).write(true).open(&self.path)?;
+        // drop what an interrupted write may have left behind
+        file.set_len(self.committed as u64)?;
+        let mut writer = BufWriter::new(&file);
+        writer.seek(SeekFrom::Start(self.committed as u64))?;
+        write(&mut writer)?;
+        let committed = writer.stream_position()?;
+        writer.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+
+        write_file_header(&file, next_id as u64, committed)?;
+        file.sync_all()?;
+
+        *self = Self::open(&self.path)?;
+        Ok(())
+    }
+
+    /// Get the digest with identifier `id`, or `None` if there is no such digest
+    pub fn get(&self, id: usize) -> Option<Result<FuzzyHash>> {
+        if self.is_removed(id) {
+            return None;
+        }
+        let segment = self
+            .segments
+            .iter()
+            .find(|segment| segment.ids.contains(&id))?;
+        let entry = self.binary_search(0, segment.entry_count, |i| {
+            Ok(self.entry_id(segment, i as u32)?.cmp(&id))
+        });
+        match entry {
+            Ok(Some(entry)) => Some(
+                self.digest(segment, entry as u32)
+                    .and_then(|digest| digest.parse()),
+            ),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+
+    /// All digests scoring at least `threshold` against `hash`, sorted by descending score and
+    /// then by identifier, exactly as [`Index::query`](super::Index::query) would.
+    pub fn query(&self, hash: &FuzzyHash, threshold: u32) -> Result<Vec<Match>> {
+        let signatures = Signatures::parse(hash.digest()?)?;
+        let keys = substring_keys(&signatures);
+        let threshold = threshold.max(1);
+
+        let mut matches = Vec::new();
+        for segment in &self.segments {
+            for entry in self.candidates(segment, &signatures, &keys)? {
+                let id = self.entry_id(segment, entry)?;
+                if self.is_removed(id) {
+                    continue;
+                }
+                let other = self.signatures(segment, entry)?;
+                let score = compare::compare_signatures(&signatures, &other).unwrap_or(0);
+                if score >= threshold {
+                    matches.push(Match { id, score });
+                }
+            }
+        }
+        sort_matches(&mut matches);
+        Ok(matches)
+    }
+
+    /// The `k` digests most similar to `hash`, sorted by descending score and then by
+    /// identifier
+    pub fn top_k(&self, hash: &FuzzyHash, k: usize) -> Result<Vec<Match>> {
+        let signatures = Signatures::parse(hash.digest()?)?;
+        let keys = substring_keys(&signatures);
+
+        let mut candidates = Vec::new();
+        for segment in &self.segments {
+            for entry in self.candidates(segment, &signatures, &keys)? {
+                let id = self.entry_id(segment, entry)?;
+                if !self.is_removed(id) {
+                    candidates.push((id, self.signatures(segment, entry)?));
+                }
+            }
+        }
+        Ok(search::select(&signatures, candidates, k))
+    }
+
+    /// Entry numbers within `segment` which may score above zero against `signatures`
+    fn candidates(
+        &self,
+        segment: &Segment,
+        signatures: &Signatures,
+        keys: &[(u32, u32)],
+    ) -> Result<Vec<u32>> {
+        let mut candidates = Vec::new();
+
+        for (block_size, hash) in keys {
+            let (first_key, key_count) = match self.partition(segment, *block_size)? {
+                Some(partition) => partition,
+                None => continue,
+            };
+            let key = self.binary_search(first_key, key_count, |i| {
+                let offset = segment.keys + i * KEY_LEN;
+                Ok(self.u32_at(offset)?.cmp(hash))
+            })?;
+            if let Some(key) = key {
+                let offset = segment.keys + key * KEY_LEN;
+                let first_posting = self.index_at(offset + 4)?;
+                let posting_count = self.u32_at(offset + 12)? as usize;
+                for posting in first_posting..first_posting.saturating_add(posting_count) {
+                    candidates.push(self.u32_at(segment.postings + posting * POSTING_LEN)?);
+                }
+            }
+        }
+
+        if is_short(&signatures.first) {
+            let wanted = short_key(signatures.block_size, &signatures.first);
+            let short = |i: usize| -> Result<[u8; 12]> {
+                let offset = segment.shorts + i * SHORT_LEN;
+                let mut key = [0; 12];
+                key[..4].copy_from_slice(self.bytes_at(offset, 4)?);
+                key[4..].copy_from_slice(self.bytes_at(offset + 8, 8)?);
+                Ok(key)
+            };
+            // several entries may share a short signature, so find the first and scan forward
+            let mut i = self.lower_bound(segment.short_count, |i| Ok(short(i)? < wanted))?;
+            while i < segment.short_count && short(i)? == wanted {
+                candidates.push(self.u32_at(segment.shorts + i * SHORT_LEN + 4)?);
+                i += 1;
+            }
+        }
+
+        candidates.sort_unstable();
+        candidates.dedup();
+        Ok(candidates)
+    }
+
+    /// First key and key count of the partition for `block_size`
+    fn partition(&self, segment: &Segment, block_size: u32) -> Result<Option<(usize, usize)>> {
+        let partition = self.binary_search(0, segment.partition_count, |i| {
+            Ok(self
+                .u32_at(segment.partitions + i * PARTITION_LEN)?
+                .cmp(&block_size))
+        })?;
+        match partition {
+            Some(i) => {
+                let offset = segment.partitions + i * PARTITION_LEN;
+                Ok(Some((
+                    self.index_at(offset + 4)?,
+                    self.u32_at(offset + 12)? as usize,
+                )))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn is_removed(&self, id: usize) -> bool {
+        self.removed.binary_search(&id).is_ok()
+    }
+
+    fn entry_id(&self, segment: &Segment, entry: u32) -> Result<usize> {
+        read_id(&self.map, segment.entries + entry as usize * ENTRY_LEN)
+    }
+
+    /// Start of the entry in the string pool, its block size and the lengths of its digest,
+    /// first and second signatures
+    fn entry(&self, segment: &Segment, entry: u32) -> Result<(usize, u32, [usize; 3])> {
+        let offset = segment.entries + entry as usize * ENTRY_LEN;
+        let start = segment.strings.saturating_add(self.index_at(offset + 8)?);
+        let block_size = self.u32_at(offset + 16)?;
+        let lens = self.bytes_at(offset + 20, 3)?;
+        Ok((
+            start,
+            block_size,
+            [lens[0] as usize, lens[1] as usize, lens[2] as usize],
+        ))
+    }
+
+    fn digest(&self, segment: &Segment, entry: u32) -> Result<&str> {
+        let (start, _, [len, _, _]) = self.entry(segment, entry)?;
+        std::str::from_utf8(self.bytes_at(start, len)?)
+            .map_err(|_| corrupt("digest is not valid UTF-8"))
+    }
+
+    /// The stored signatures of an entry, without parsing its digest
+    fn signatures(&self, segment: &Segment, entry: u32) -> Result<Signatures> {
+        let (start, block_size, [digest_len, first_len, second_len]) =
+            self.entry(segment, entry)?;
+        let first = start.saturating_add(digest_len);
+        let second = first + first_len;
+        Ok(Signatures {
+            block_size,
+            first: self.bytes_at(first, first_len)?.to_vec(),
+            second: self.bytes_at(second, second_len)?.to_vec(),
+        })
+    }
+
+    fn binary_search<F>(&self, first: usize, count: usize, compare: F) -> Result<Option<usize>>
+    where
+        F: Fn(usize) -> Result<std::cmp::Ordering>,
+    {
+        let (mut low, mut high) = (first, first.saturating_add(count));
+        while low < high {
+            let middle = low + (high - low) / 2;
+            match compare(middle)? {
+                std::cmp::Ordering::Less => low = middle + 1,
+                std::cmp::Ordering::Greater => high = middle,
+                std::cmp::Ordering::Equal => return Ok(Some(middle)),
+            }
+        }
+        Ok(None)
+    }
+
+    fn lower_bound<F>(&self, count: usize, is_before: F) -> Result<usize>
+    where
+        F: Fn(usize) -> Result<bool>,
+    {
+        let (mut low, mut high) = (0, count);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if is_before(middle)? {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        Ok(low)
+    }
+
+    fn bytes_at(&self, offset: usize, len: usize) -> Result<&[u8]> {
+        self.map
+            .get(offset..offset.saturating_add(len))
+            .ok_or_else(|| corrupt("table entry is out of bounds"))
+    }
+
+    fn u32_at(&self, offset: usize) -> Result<u32> {
+        read_u32(&self.map, offset).ok_or_else(|| corrupt("table entry is out of bounds"))
+    }
+
+    /// A 64 bit offset or index, which must fit the address space
+    fn index_at(&self, offset: usize) -> Result<usize> {
+        read_u64(&self.map, offset)
+            .and_then(|value| usize::try_from(value).ok())
+            .ok_or_else(|| corrupt("table entry is out of bounds"))
+    }
+}
+
+fn corrupt(reason: &str) -> Error {
+    Error::CorruptIndex(reason.to_string())
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let mut value = [0; 4];
+    value.copy_from_slice(bytes.get(offset..offset.checked_add(4)?)?);
+    Some(u32::from_le_bytes(value))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Option<u64> {
+    let mut value = [0; 8];
+    value.copy_from_slice(bytes.get(offset..offset.checked_add(8)?)?);
+    Some(u64::from_le_bytes(value))
+}
+
+/// Sort key of a short signature table entry: block size then padded signature, big endian so
+/// byte comparison matches numeric comparison
+fn short_key(block_size: u32, signature: &[u8]) -> [u8; 12] {
+    let mut key = [0; 12];
+    key[..4].copy_from_slice(&block_size.to_be_bytes());
+    key[4] = signature.len() as u8;
+    key[5..5 + signature.len()].copy_from_slice(signature);
+    key
+}
+
+/// An identifier, which must fit the address space
+fn read_id(bytes: &[u8], offset: usize) -> Result<usize> {
+    read_u64(bytes, offset)
+        .and_then(|value| usize::try_from(value).ok())
+        .ok_or_else(|| corrupt("table entry is out of bounds"))
+}
+
+/// Read the segment at `offset`, whose identifiers must not be below `min_id`
+fn read_segment(map: &[u8], offset: usize, min_id: usize) -> Result<Segment> {
+    let header = map
+        .get(offset..offset + SEGMENT_HEADER_LEN)
+        .ok_or_else(|| corrupt("truncated segment header"))?;
+    if &header[..4] != SEGMENT_MAGIC {
+        return Err(corrupt("bad segment magic"));
+    }
+    if read_u32(header, 52) != Some(crc32(&header[..52])) {
+        return Err(corrupt("segment header checksum mismatch"));
+    }
+
+    let field = |offset| read_u32(header, offset).unwrap_or_default() as usize;
+    let wide_field = |offset| {
+        read_u64(header, offset)
+            .and_then(|value| usize::try_from(value).ok())
+            .ok_or_else(|| corrupt("truncated segment"))
+    };
+    let entry_count = field(4);
+    let first_id = wide_field(8)?;
+    let partition_count = field(16);
+    let short_count = field(20);
+    let key_count = wide_field(24)?;
+    let posting_count = wide_field(32)?;
+    let strings_len = wide_field(40)?;
+    if first_id < min_id {
+        return Err(corrupt("segment identifiers are out of order"));
+    }
+
+    // counts come from the file, so a corrupt header must not overflow the table offsets
+    let table = |start: usize, count: usize, len: usize| {
+        count
+            .checked_mul(len)
+            .and_then(|size| start.checked_add(size))
+            .ok_or_else(|| corrupt("truncated segment"))
+    };
+    let entries = offset + SEGMENT_HEADER_LEN;
+    let partitions = table(entries, entry_count, ENTRY_LEN)?;
+    let keys = table(partitions, partition_count, PARTITION_LEN)?;
+    let postings = table(keys, key_count, KEY_LEN)?;
+    let shorts = table(postings, posting_count, POSTING_LEN)?;
+    let strings = table(shorts, short_count, SHORT_LEN)?;
+    let end = table(strings, strings_len, 1)?;
+    if end > map.len() {
+        return Err(corrupt("truncated segment"));
+    }
+    let ids = match entry_count {
+        0 => first_id..first_id,
+        _ => {
+            let last_id = read_id(map, entries + (entry_count - 1) * ENTRY_LEN)?;
+            if read_id(map, entries)? != first_id || last_id < first_id {
+                return Err(corrupt("segment identifiers are out of order"));
+            }
+            first_id..last_id + 1
+        }
+    };
+
+    Ok(Segment {
+        ids,
+        entry_count,
+        partition_count,
+        short_count,
+        entries,
+        partitions,
+        keys,
+        postings,
+        shorts,
+        strings,
+        body: entries..end,
+        body_crc: field(48) as u32,
+    })
+}
+
+fn read_tombstones(map: &[u8], offset: usize) -> Result<Tombstones> {
+    let header = map
+        .get(offset..offset + TOMBSTONE_HEADER_LEN)
+        .ok_or_else(|| corrupt("truncated tombstone header"))?;
+    if read_u32(header, 12) != Some(crc32(&header[..12])) {
+        return Err(corrupt("tombstone header checksum mismatch"));
+    }
+    let count = read_u32(header, 4).unwrap_or_default() as usize;
+    let start = offset + TOMBSTONE_HEADER_LEN;
+    let end = count
+        .checked_mul(ID_LEN)
+        .and_then(|size| start.checked_add(size))
+        .filter(|end| *end <= map.len())
+        .ok_or_else(|| corrupt("truncated tombstones"))?;
+
+    Ok(Tombstones {
+        body: start..end,
+        body_crc: read_u32(header, 8).unwrap_or_default(),
+    })
+}
+
+/// Write a complete index file holding `entries`, sorted by identifier
+fn write_file(path: &Path, entries: &[(usize, &str)], next_id: usize) -> Result<()> {
+    let file = File::create(path)?;
+    let mut writer = BufWriter::new(&file);
+    writer.write_all(&[0; FILE_HEADER_LEN])?;
+    let first_id = entries.first().map_or(next_id, |(id, _)| *id);
+    write_segment(&mut writer, first_id, entries)?;
+    let committed = writer.stream_position()?;
+    writer.into_inner().map_err(|e| e.into_error())?;
+
+    write_file_header(&file, next_id as u64, committed)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+fn write_file_header(mut file: &File, next_id: u64, committed: u64) -> Result<()> {
+    let mut header = Vec::with_capacity(FILE_HEADER_LEN);
+    header.extend_from_slice(FILE_MAGIC);
+    header.extend_from_slice(&VERSION.to_le_bytes());
+    header.extend_from_slice(&next_id.to_le_bytes());
+    header.extend_from_slice(&committed.to_le_bytes());
+    header.extend_from_slice(&crc32(&header).to_le_bytes());
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(&header)?;
+    Ok(())
+}
+
+fn write_tombstones<W: Write>(writer: &mut W, ids: &[usize]) -> Result<()> {
+    let body: Vec<u8> = ids
+        .iter()
+        .flat_map(|id| (*id as u64).to_le_bytes())
+        .collect();
+
+    let mut header = Vec::with_capacity(TOMBSTONE_HEADER_LEN);
+    header.extend_from_slice(TOMBSTONE_MAGIC);
+    header.extend_from_slice(&to_u32(ids.len())?.to_le_bytes());
+    header.extend_from_slice(&crc32(&body).to_le_bytes());
+    header.extend_from_slice(&crc32(&header).to_le_bytes());
+
+    writer.write_all(&header)?;
+    writer.write_all(&body)?;
+    Ok(())
+}
+
+/// Write a segment holding `digests` with their identifiers, which must be sorted and not
+/// below `first_id`
+fn write_segment<W: Write>(
+    writer: &mut W,
+    first_id: usize,
+    digests: &[(usize, &str)],
+) -> Result<()> {
+    let entry_count = to_u32(digests.len())?;
+    let mut entries = Vec::with_capacity(digests.len() * ENTRY_LEN);
+    let mut strings = Vec::new();
+    let mut postings_by_key: Vec<(u32, u32, u32)> = Vec::new();
+    let mut shorts: Vec<([u8; 12], u32)> = Vec::new();
+
+    for (entry, (id, digest)) in (0..entry_count).zip(digests) {
+        let signatures = Signatures::parse(digest)?;
+        for (block_size, hash) in substring_keys(&signatures) {
+            postings_by_key.push((block_size, hash, entry));
+        }
+        if is_short(&signatures.first) {
+            shorts.push((short_key(signatures.block_size, &signatures.first), entry));
+        }
+
+        // a valid digest is at most 140 bytes and its signatures at most 64
+        let digest_len = u8::try_from(digest.len()).map_err(|_| Error::MalformedInput)?;
+        entries.extend_from_slice(&(*id as u64).to_le_bytes());
+        entries.extend_from_slice(&(strings.len() as u64).to_le_bytes());
+        entries.extend_from_slice(&signatures.block_size.to_le_bytes());
+        entries.extend_from_slice(&[
+            digest_len,
+            signatures.first.len() as u8,
+            signatures.second.len() as u8,
+            0,
+        ]);
+        strings.extend_from_slice(digest.as_bytes());
+        strings.extend_from_slice(&signatures.first);
+        strings.extend_from_slice(&signatures.second);
+    }
+    postings_by_key.sort_unstable();
+    shorts.sort_unstable();
+
+    let mut partitions: Vec<u8> = Vec::new();
+    let mut keys: Vec<u8> = Vec::new();
+    let mut postings: Vec<u8> = Vec::new();
+    let (mut partition_count, mut key_count) = (0u32, 0u64);
+    let mut i = 0;
+    while i < postings_by_key.len() {
+        let block_size = postings_by_key[i].0;
+        let first_key = key_count;
+        while i < postings_by_key.len() && postings_by_key[i].0 == block_size {
+            let hash = postings_by_key[i].1;
+            let first_posting = postings.len() / POSTING_LEN;
+            while i < postings_by_key.len()
+                && postings_by_key[i].0 == block_size
+                && postings_by_key[i].1 == hash
+            {
+                postings.extend_from_slice(&postings_by_key[i].2.to_le_bytes());
+                i += 1;
+            }
+            let posting_count = to_u32(postings.len() / POSTING_LEN - first_posting)?;
+            keys.extend_from_slice(&hash.to_le_bytes());
+            keys.extend_from_slice(&(first_posting as u64).to_le_bytes());
+            keys.extend_from_slice(&posting_count.to_le_bytes());
+            key_count += 1;
+        }
+        let partition_key_count = u32::try_from(key_count - first_key).map_err(|_| too_large())?;
+        partitions.extend_from_slice(&block_size.to_le_bytes());
+        partitions.extend_from_slice(&first_key.to_le_bytes());
+        partitions.extend_from_slice(&partition_key_count.to_le_bytes());
+        partition_count += 1;
+    }
+
+    let mut short_table = Vec::with_capacity(shorts.len() * SHORT_LEN);
+    for (key, entry) in &shorts {
+        short_table.extend_from_slice(&key[..4]);
+        short_table.extend_from_slice(&entry.to_le_bytes());
+        short_table.extend_from_slice(&key[4..]);
+    }
+
+    let body = [
+        &entries[..],
+        &partitions,
+        &keys,
+        &postings,
+        &short_table,
+        &strings,
+    ];
+    let mut body_crc = Crc32::new();
+    for table in &body {
+        body_crc.update(table);
+    }
+
+    let mut header = Vec::with_capacity(SEGMENT_HEADER_LEN);
+    header.extend_from_slice(SEGMENT_MAGIC);
+    header.extend_from_slice(&entry_count.to_le_bytes());
+    header.extend_from_slice(&(first_id as u64).to_le_bytes());
+    header.extend_from_slice(&partition_count.to_le_bytes());
+    header.extend_from_slice(&to_u32(shorts.len())?.to_le_bytes());
+    header.extend_from_slice(&key_count.to_le_bytes());
+    header.extend_from_slice(&((postings.len() / POSTING_LEN) as u64).to_le_bytes());
+    header.extend_from_slice(&(strings.len() as u64).to_le_bytes());
+    header.extend_from_slice(&body_crc.finish().to_le_bytes());
+    header.extend_from_slice(&crc32(&header).to_le_bytes());
+
+    writer.write_all(&header)?;
+    for table in &body {
+        writer.write_all(table)?;
+    }
+    Ok(())
+}
+
+/// Convert a count stored in 32 bits, failing rather than writing a truncated value
+fn to_u32(value: usize) -> Result<u32> {
+    u32::try_from(value).map_err(|_| too_large())
+}
+
+fn too_large() -> Error {
+    Error::Io(std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        "too many digests for one index segment",
+    ))
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(bytes);
+    crc.finish()
+}
+
+/// CRC-32 (IEEE 802.3), as used by zlib
+struct Crc32 {
+    table: [u32; 256],
+    value: u32,
+}
+
+impl Crc32 {
+    fn new() -> Self {
+        let mut table = [0; 256];
+        for (i, item) in table.iter_mut().enumerate() {
+            let mut c = i as u32;
+            for _ in 0..8 {
+                c = if c & 1 == 1 {
+                    0xedb8_8320 ^ (c >> 1)
+                } else {
+                    c >> 1
+                };
+            }
+            *item = c;
+        }
+        Self {
+            table,
+            value: 0xffff_ffff,
+        }
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.value =
+                self.table[((self.value ^ *byte as u32) & 0xff) as usize] ^ (self.value >> 8);
+        }
+    }
+
+    fn finish(&self) -> u32 {
+        self.value ^ 0xffff_ffff
+    }
+}
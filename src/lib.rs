@@ -24,7 +24,7 @@
 //!     }
 //! }
 //!
-//! fuzzy_hash.finalize();
+//! fuzzy_hash.finalize().unwrap();
 //!
 //! println!("Fuzzy hash of data: {}", fuzzy_hash);
 //! ```
@@ -41,41 +41,90 @@
 //! buffer.push(0xef);
 //! // ...
 //!
-//! println!("Fuzzy hash of data: {}", FuzzyHash::new(buffer));
+//! println!("Fuzzy hash of data: {}", FuzzyHash::new(buffer).unwrap());
 //! ```
 //!
 
 #![warn(missing_docs)]
 
+mod base64;
+#[cfg(feature = "rayon")]
+pub mod batch;
 mod blockhash;
+pub mod cluster;
 mod compare;
 mod constants;
+pub mod diff;
+pub mod digest;
 pub mod error;
 mod hasher;
+pub mod index;
+pub mod lzjd;
+pub mod matcher;
+pub mod mrsh;
+pub mod multi;
+pub mod nilsimsa;
+pub mod piecewise;
 mod roll;
+#[cfg(feature = "sdhash")]
+pub mod sdhash;
+pub mod search;
+pub mod sigfile;
+pub mod tlsh;
 
 pub use constants::Modes;
 use hasher::Hasher;
 use std::{
+    cmp::Ordering,
+    convert::TryFrom,
     ffi::{CStr, CString},
     fmt,
+    hash::Hash,
     os::raw::c_char,
     path::Path,
+    str::FromStr,
 };
 
 /// Result of fuzzy hash operations
 pub type Result<T> = std::result::Result<T, error::Error>;
 
+/// The region of the input behind one character of a signature
+///
+/// Each character of a signature is a hash of the bytes since the previous character's chunk
+/// ended, so two digests sharing a character most likely share the bytes of its chunk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Chunk {
+    /// Offset of the chunk in the input
+    pub offset: u64,
+    /// Length of the chunk
+    pub len: u64,
+    /// The signature character computed from the chunk
+    pub character: char,
+}
+
 /// Hasher for fuzzy algorithm
+///
+/// A `FuzzyHash` is either built by hashing data, or parsed from an existing digest string with
+/// [`str::parse`] or [`FuzzyHash::try_from`]. A parsed digest has no hashing state, so further
+/// calls to [`update`](FuzzyHash::update) have no effect on it.
+///
+/// Equality, hashing and ordering use the digest, so fuzzy hashes can be deduplicated in a
+/// `HashSet` or sorted by block size. A fuzzy hash that has not been finalized has no digest
+/// yet: whatever data it was given, it is equal to, hashes like and sorts together with every
+/// other unfinalized fuzzy hash, before all finalized ones. Finalize fuzzy hashes before
+/// putting them in a set or using them as map keys, or every in-progress hash collapses into
+/// one entry.
+#[derive(Clone)]
 pub struct FuzzyHash {
-    hasher: Hasher,
+    hasher: Option<Hasher>,
     hash: Option<String>,
 }
 
 impl Default for FuzzyHash {
     fn default() -> Self {
         Self {
-            hasher: Hasher::new(),
+            hasher: Some(Hasher::new()),
             hash: None,
         }
     }
@@ -92,15 +141,26 @@ impl FuzzyHash {
     /// use fuzzyhash::FuzzyHash;
     ///
     /// let mut data = read("/usr/bin/bash").unwrap();
-    /// let mut fuzzy_hash = FuzzyHash::new(data);
+    /// let mut fuzzy_hash = FuzzyHash::new(data).unwrap();
     /// ```
     ///
-    pub fn new<S: AsRef<[u8]>>(input: S) -> Self {
+    pub fn new<S: AsRef<[u8]>>(input: S) -> Result<Self> {
         let input = input.as_ref();
         let mut this = Self::default();
-        this.hasher.update(input, input.len());
-        this.finalize();
-        this
+        this.update(input);
+        this.finalize()?;
+        Ok(this)
+    }
+
+    /// Build an empty fuzzy hash that records the input region behind each character of its
+    /// signatures, see [`chunks1`](FuzzyHash::chunks1)
+    ///
+    /// Tracking costs a few kilobytes per fuzzy hash, whatever the size of the input.
+    pub fn tracking_chunks() -> Self {
+        Self {
+            hasher: Some(Hasher::tracking_chunks()),
+            hash: None,
+        }
     }
 
     /// Hash a file pointed to by `path`.
@@ -111,7 +171,7 @@ impl FuzzyHash {
     /// let hash = FuzzyHash::file("/home/me/a_large_file.bin").unwrap();
     /// ```
     ///
-    pub fn file<P: AsRef<Path>>(path: P) -> std::result::Result<Self, std::io::Error> {
+    pub fn file<P: AsRef<Path>>(path: P) -> Result<Self> {
         let mut file = std::fs::File::open(path.as_ref())?;
         FuzzyHash::read(&mut file)
     }
@@ -124,35 +184,246 @@ impl FuzzyHash {
     /// use std::io::{Cursor, Read};
     ///
     /// let mut cursor = Cursor::new(vec![1,2,3,4,5,6,7,8,9,10]);
-    /// let fuzzy = FuzzyHash::read(&mut cursor);
+    /// let fuzzy = FuzzyHash::read(&mut cursor).unwrap();
     /// ```
-    pub fn read<R: std::io::Read>(reader: &mut R) -> std::result::Result<Self, std::io::Error> {
+    pub fn read<R: std::io::Read>(reader: &mut R) -> Result<Self> {
         let mut hasher = Hasher::new();
+        let mut buffer = [0; 1024];
+        // pipes and sockets return short reads, only an empty read ends the input
         loop {
-            let mut buffer = [0; 1024];
-            let len = reader.read(&mut buffer)?;
-            hasher.update(&buffer, len);
-
-            if len < 1024 {
-                break;
+            match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(len) => hasher.update(&buffer, len),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
             }
         }
 
-        let mut this = Self { hasher, hash: None };
-        this.finalize();
+        let mut this = Self {
+            hasher: Some(hasher),
+            hash: None,
+        };
+        this.finalize()?;
         Ok(this)
     }
 
     /// Add chunk to the data source
     pub fn update<S: AsRef<[u8]>>(&mut self, input: S) {
         let input = input.as_ref();
-        self.hasher.update(input, input.len());
+        if let Some(ref mut hasher) = self.hasher {
+            hasher.update(input, input.len());
+        }
     }
 
     /// Called to finalize the hashing and generate a string value
-    pub fn finalize(&mut self) {
+    ///
+    /// Finalizing more than once is a no-op; the digest computed by the first successful call is
+    /// kept.
+    pub fn finalize(&mut self) -> Result<()> {
         if self.hash.is_none() {
-            self.hash = self.hasher.digest(constants::Modes::None).ok();
+            if let Some(ref mut hasher) = self.hasher {
+                self.hash = Some(hasher.digest(constants::Modes::None)?);
+            }
+        }
+        Ok(())
+    }
+
+    /// The digest string of a finalized fuzzy hash
+    ///
+    /// # Example
+    /// ```
+    /// use fuzzyhash::FuzzyHash;
+    ///
+    /// let mut fuzzy_hash = FuzzyHash::default();
+    /// fuzzy_hash.update("this is our test data!");
+    /// assert!(fuzzy_hash.digest().is_err());
+    ///
+    /// fuzzy_hash.finalize().unwrap();
+    /// assert_eq!(fuzzy_hash.digest().unwrap(), "3:YKKGhR0tn:YRGRmn");
+    /// ```
+    pub fn digest(&self) -> Result<&str> {
+        self.hash.as_deref().ok_or(error::Error::NotFinalized)
+    }
+
+    /// Returns `true` if this fuzzy hash was parsed from a digest string rather than computed
+    /// from data
+    ///
+    /// # Example
+    /// ```
+    /// use fuzzyhash::FuzzyHash;
+    ///
+    /// let parsed: FuzzyHash = "3:YKKGhR0tn:YRGRmn".parse().unwrap();
+    /// assert!(parsed.is_parsed());
+    /// assert!(!FuzzyHash::new("this is our test data!").unwrap().is_parsed());
+    /// ```
+    pub fn is_parsed(&self) -> bool {
+        self.hasher.is_none()
+    }
+
+    /// Block size of the digest
+    ///
+    /// # Example
+    /// ```
+    /// use fuzzyhash::FuzzyHash;
+    ///
+    /// let fuzzy_hash: FuzzyHash = "96:U57GjXnLt9co6pZwvLhJluvrszNgMFwO6MFG8SvkpjTWf:Hj3BeoEcNJ0TspgIG8SvkpjTg"
+    ///     .parse()
+    ///     .unwrap();
+    /// assert_eq!(fuzzy_hash.block_size().unwrap(), 96);
+    /// assert_eq!(fuzzy_hash.signature1().unwrap(), "U57GjXnLt9co6pZwvLhJluvrszNgMFwO6MFG8SvkpjTWf");
+    /// assert_eq!(fuzzy_hash.signature2().unwrap(), "Hj3BeoEcNJ0TspgIG8SvkpjTg");
+    /// ```
+    pub fn block_size(&self) -> Result<u32> {
+        Ok(compare::parse(self.digest()?)?.0)
+    }
+
+    /// First signature of the digest, computed at `block_size()`
+    pub fn signature1(&self) -> Result<&str> {
+        Ok(compare::parse(self.digest()?)?.1)
+    }
+
+    /// Second signature of the digest, computed at twice `block_size()`
+    pub fn signature2(&self) -> Result<&str> {
+        Ok(compare::parse(self.digest()?)?.2)
+    }
+
+    /// First signature with runs of more than three identical characters shortened to three, as
+    /// done before comparing
+    ///
+    /// # Example
+    /// ```
+    /// use fuzzyhash::FuzzyHash;
+    ///
+    /// let fuzzy_hash: FuzzyHash = "3:AAAAAAB:CCCCD".parse().unwrap();
+    /// assert_eq!(fuzzy_hash.normalized_signature1().unwrap(), "AAAB");
+    /// assert_eq!(fuzzy_hash.normalized_signature2().unwrap(), "CCCD");
+    /// ```
+    pub fn normalized_signature1(&self) -> Result<String> {
+        Ok(normalize(self.signature1()?))
+    }
+
+    /// Second signature with runs of more than three identical characters shortened to three, as
+    /// done before comparing
+    pub fn normalized_signature2(&self) -> Result<String> {
+        Ok(normalize(self.signature2()?))
+    }
+
+    /// Chunks behind the characters of the first signature, in order
+    ///
+    /// Requires a fuzzy hash built with [`tracking_chunks`](FuzzyHash::tracking_chunks). The
+    /// chunks follow each other from the start of the input; the last one may be empty, as the
+    /// digest always ends with the hash of whatever follows the last trigger.
+    ///
+    /// # Example
+    /// ```
+    /// use fuzzyhash::FuzzyHash;
+    ///
+    /// let mut fuzzy_hash = FuzzyHash::tracking_chunks();
+    /// fuzzy_hash.update("this is our test data!");
+    /// fuzzy_hash.finalize().unwrap();
+    ///
+    /// let chunks = fuzzy_hash.chunks1().unwrap();
+    /// let characters: String = chunks.iter().map(|chunk| chunk.character).collect();
+    /// assert_eq!(characters, fuzzy_hash.signature1().unwrap());
+    /// assert_eq!(chunks[0].offset, 0);
+    /// ```
+    pub fn chunks1(&self) -> Result<Vec<Chunk>> {
+        Ok(self.signature_chunks()?.0)
+    }
+
+    /// Chunks behind the characters of the second signature, computed at twice `block_size()`,
+    /// in order. See [`chunks1`](FuzzyHash::chunks1).
+    pub fn chunks2(&self) -> Result<Vec<Chunk>> {
+        Ok(self.signature_chunks()?.1)
+    }
+
+    /// Chunks behind the untruncated signature of every block size still being hashed, from
+    /// the smallest block size
+    ///
+    /// The digest only keeps two of these signatures, the second one truncated to half its
+    /// length. Requires a fuzzy hash built with
+    /// [`tracking_chunks`](FuzzyHash::tracking_chunks).
+    pub fn all_chunks(&self) -> Result<Vec<(u32, Vec<Chunk>)>> {
+        self.digest()?;
+        self.hasher
+            .as_ref()
+            .and_then(Hasher::all_chunks)
+            .ok_or(error::Error::NotTracked)
+    }
+
+    fn signature_chunks(&self) -> Result<(Vec<Chunk>, Vec<Chunk>)> {
+        self.digest()?;
+        match self.hasher {
+            Some(ref hasher) => hasher.signature_chunks()?.ok_or(error::Error::NotTracked),
+            None => Err(error::Error::NotTracked),
+        }
+    }
+
+    /// Number of bytes hashed, or `None` if this fuzzy hash was parsed from a digest string
+    ///
+    /// # Example
+    /// ```
+    /// use fuzzyhash::FuzzyHash;
+    ///
+    /// assert_eq!(FuzzyHash::new("this is our test data!").unwrap().input_len(), Some(22));
+    /// ```
+    pub fn input_len(&self) -> Option<u64> {
+        self.hasher
+            .as_ref()
+            .map(|hasher| u64::from(hasher.total_size()))
+    }
+
+    /// Block sizes of digests this fuzzy hash can be compared against: half, equal to and double
+    /// its own block size, where those are valid
+    ///
+    /// # Example
+    /// ```
+    /// use fuzzyhash::FuzzyHash;
+    ///
+    /// let fuzzy_hash: FuzzyHash = "96:U57GjXnLt9co6pZwvLhJluvrszNgMFwO6MFG8SvkpjTWf:Hj3BeoEcNJ0TspgIG8SvkpjTg"
+    ///     .parse()
+    ///     .unwrap();
+    /// assert_eq!(fuzzy_hash.compatible_block_sizes().unwrap(), vec![48, 96, 192]);
+    /// ```
+    pub fn compatible_block_sizes(&self) -> Result<Vec<u32>> {
+        Ok(compare::compatible_block_sizes(self.block_size()?))
+    }
+
+    /// The digest with both signatures normalized, see
+    /// [`normalized_signature1`](FuzzyHash::normalized_signature1)
+    ///
+    /// # Example
+    /// ```
+    /// use fuzzyhash::FuzzyHash;
+    ///
+    /// let fuzzy_hash: FuzzyHash = "3:AAAAAAB:CCCCD".parse().unwrap();
+    /// assert_eq!(fuzzy_hash.normalized().unwrap().to_string(), "3:AAAB:CCCD");
+    /// ```
+    pub fn normalized(&self) -> Result<FuzzyHash> {
+        format!(
+            "{}:{}:{}",
+            self.block_size()?,
+            self.normalized_signature1()?,
+            self.normalized_signature2()?
+        )
+        .parse()
+    }
+
+    /// Whether two fuzzy hashes are equal once runs of repeated characters are normalized
+    ///
+    /// # Example
+    /// ```
+    /// use fuzzyhash::FuzzyHash;
+    ///
+    /// let first: FuzzyHash = "3:AAAAAAB:CCCCD".parse().unwrap();
+    /// let second: FuzzyHash = "3:AAAAB:CCCD".parse().unwrap();
+    /// assert_ne!(first, second);
+    /// assert!(first.eq_normalized(&second));
+    /// ```
+    pub fn eq_normalized(&self, other: &FuzzyHash) -> bool {
+        match (self.normalized(), other.normalized()) {
+            (Ok(first), Ok(second)) => first == second,
+            _ => false,
         }
     }
 
@@ -182,15 +453,61 @@ impl FuzzyHash {
     /// # Example
     /// ```
     /// use fuzzyhash::FuzzyHash;
-    /// let mut fuzzy_hash = FuzzyHash::new("some data to hash for the purposes of running a test");
+    /// let mut fuzzy_hash = FuzzyHash::new("some data to hash for the purposes of running a test").unwrap();
     /// assert_eq!(fuzzy_hash.compare_to(
-    ///            &"3:HEREar5MFUul0U0KMP:knl8lkKMP".into()),
+    ///            &"3:HEREar5MFUul0U0KMP:knl8lkKMP".parse().unwrap()),
     ///            Some(18));
     /// ```
     pub fn compare_to(&self, other: &FuzzyHash) -> Option<u32> {
         self.hash
             .as_ref()
-            .and_then(|ref hash| FuzzyHash::compare(hash, &other.to_string()).ok())
+            .and_then(|hash| FuzzyHash::compare(hash, other.to_string()).ok())
+    }
+}
+
+fn normalize(signature: &str) -> String {
+    String::from_utf8(compare::eliminate_sequences(signature.as_bytes().to_vec()))
+        .expect("signatures are base64")
+}
+
+/// Compares digests, so all unfinalized fuzzy hashes are equal to each other
+impl PartialEq for FuzzyHash {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash
+    }
+}
+
+impl Eq for FuzzyHash {}
+
+impl Hash for FuzzyHash {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.hash.hash(state);
+    }
+}
+
+impl PartialOrd for FuzzyHash {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Orders by block size, then by the first and second signatures. Unfinalized fuzzy hashes
+/// are equal to each other and sort first.
+impl Ord for FuzzyHash {
+    fn cmp(&self, other: &Self) -> Ordering {
+        fn key(fuzzy_hash: &FuzzyHash) -> Option<(u32, &str, &str)> {
+            fuzzy_hash
+                .hash
+                .as_deref()
+                .map(|hash| compare::parse(hash).unwrap_or((0, hash, "")))
+        }
+        key(self).cmp(&key(other))
+    }
+}
+
+impl fmt::Debug for FuzzyHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("FuzzyHash").field(&self.hash).finish()
     }
 }
 
@@ -200,24 +517,85 @@ impl fmt::Display for FuzzyHash {
     }
 }
 
-impl From<&str> for FuzzyHash {
-    fn from(s: &str) -> Self {
-        Self {
-            hasher: Hasher::new(),
-            hash: Some(s.to_string()),
-        }
+/// Parse a digest string, validating its block size and signatures
+///
+/// # Example
+/// ```
+/// use fuzzyhash::FuzzyHash;
+///
+/// let fuzzy_hash: FuzzyHash = "3:YKKGhR0tn:YRGRmn".parse().unwrap();
+/// assert!("garbage".parse::<FuzzyHash>().is_err());
+/// ```
+impl FromStr for FuzzyHash {
+    type Err = error::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        FuzzyHash::try_from(s.to_string())
     }
 }
 
-impl From<String> for FuzzyHash {
-    fn from(s: String) -> Self {
-        Self {
-            hasher: Hasher::new(),
+/// Replaces the infallible `From<&str>` conversion removed in 0.3.0, which accepted any text
+impl TryFrom<&str> for FuzzyHash {
+    type Error = error::Error;
+
+    fn try_from(s: &str) -> Result<Self> {
+        s.parse()
+    }
+}
+
+/// Replaces the infallible `From<String>` conversion removed in 0.3.0, which accepted any text
+impl TryFrom<String> for FuzzyHash {
+    type Error = error::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        compare::parse(&s)?;
+        Ok(Self {
+            hasher: None,
             hash: Some(s),
+        })
+    }
+}
+
+/// Serializes as the canonical ssdeep string, e.g. `"3:YKKGhR0tn:YRGRmn"`
+#[cfg(feature = "serde")]
+impl serde::Serialize for FuzzyHash {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        match self.hash {
+            Some(ref hash) => serializer.serialize_str(hash),
+            None => Err(serde::ser::Error::custom(
+                "fuzzy hash must be finalized before serializing",
+            )),
         }
     }
 }
 
+/// Deserializes from an ssdeep string, rejecting strings that are not valid fuzzy hashes
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for FuzzyHash {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = FuzzyHash;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a fuzzy hash string in the form blocksize:hash:hash")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, s: &str) -> std::result::Result<FuzzyHash, E> {
+                s.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(Visitor)
+    }
+}
+
 /// Returns the fuzzy hash of arbitrary data. This method provides better FFI compatibility.
 ///
 /// # Arguments
@@ -243,10 +621,11 @@ impl From<String> for FuzzyHash {
 #[no_mangle]
 pub unsafe extern "C" fn fuzzyhash(buf: *const u8, length: usize) -> *mut c_char {
     let data = std::slice::from_raw_parts(buf, length);
-    let mut fuzzy_hash = FuzzyHash::new(data);
-    fuzzy_hash.finalize();
+    let fuzzy_hash = FuzzyHash::new(data)
+        .map(|fuzzy_hash| fuzzy_hash.to_string())
+        .unwrap_or_default();
 
-    let s = CString::new(fuzzy_hash.to_string()).unwrap();
+    let s = CString::new(fuzzy_hash).unwrap();
 
     s.into_raw()
 }
@@ -277,5 +656,8 @@ pub unsafe extern "C" fn fuzzyhash_compare(first: *const c_char, second: *const
     let f = FuzzyHash::new(CStr::from_ptr(first).to_string_lossy().into_owned());
     let s = FuzzyHash::new(CStr::from_ptr(second).to_string_lossy().into_owned());
 
-    f.compare_to(&s).unwrap_or(0)
+    match (f, s) {
+        (Ok(f), Ok(s)) => f.compare_to(&s).unwrap_or(0),
+        _ => 0,
+    }
 }
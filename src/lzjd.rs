@@ -0,0 +1,329 @@
+//! LZJD, the Lempel-Ziv Jaccard Distance.
+//!
+//! The input is split into the phrases of a Lempel-Ziv set: reading byte by byte, the current
+//! phrase grows until it is one that has not been seen before, which is then added to the set
+//! and a new phrase begins. Two inputs are similar when their sets overlap. Phrases do not
+//! depend on where content appears, so reordered sections still share many of their phrases.
+//!
+//! Sets are summarized by a min-hash sketch, the smallest hashes of their phrases, and the
+//! Jaccard similarity of two sets is estimated from their sketches. Larger sketches give more
+//! accurate estimates, see [`Lzjd::with_sketch_size`].
+//!
+//! Digests are written as `lzjd:<sketch size>:<base64 hashes>`. The phrase hash is specific to
+//! this crate, so digests cannot be compared with those of other LZJD implementations.
+//!
+//! # Example
+//! ```
+//! use fuzzyhash::lzjd::Lzjd;
+//!
+//! let data: Vec<u8> = (0..4096u32).map(|i| (i * i % 251) as u8).collect();
+//! let mut reordered = data[2048..].to_vec();
+//! reordered.extend_from_slice(&data[..2048]);
+//!
+//! let mut lzjd = Lzjd::default();
+//! lzjd.update(&data[..1000]);
+//! lzjd.update(&data[1000..]);
+//! lzjd.finalize().unwrap();
+//!
+//! let parsed: Lzjd = lzjd.digest().unwrap().parse().unwrap();
+//! assert_eq!(parsed.jaccard(&lzjd).unwrap(), 1.0);
+//! assert!(lzjd.jaccard(&Lzjd::new(&reordered).unwrap()).unwrap() > 0.8);
+//! ```
+
+use super::{base64, digest::SimilarityDigest, error::Error, Result};
+use std::{
+    collections::HashSet,
+    fmt,
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
+
+/// Number of hashes kept by [`Lzjd::default`]
+pub const DEFAULT_SKETCH_SIZE: usize = 1024;
+
+const PREFIX: &str = "lzjd";
+const FNV_OFFSET: u32 = 0x811c_9dc5;
+const FNV_PRIME: u32 = 0x0100_0193;
+
+/// Hashing state, dropped once the digest is computed
+#[derive(Clone)]
+struct State {
+    /// Hashes of the phrases seen so far
+    phrases: HashSet<u32>,
+    /// Hash of the phrase being read
+    current: u32,
+}
+
+/// An LZJD digest, built by hashing data or parsed from its string form
+///
+/// As with [`FuzzyHash`](crate::FuzzyHash), equality and hashing use the digest string.
+#[derive(Clone)]
+pub struct Lzjd {
+    sketch_size: usize,
+    parsed: bool,
+    state: Option<State>,
+    sketch: Option<Vec<u32>>,
+    hash: Option<String>,
+}
+
+impl Default for Lzjd {
+    fn default() -> Self {
+        Self::with_sketch_size(DEFAULT_SKETCH_SIZE)
+    }
+}
+
+impl Lzjd {
+    /// Hash `input` with the default sketch size
+    pub fn new<S: AsRef<[u8]>>(input: S) -> Result<Self> {
+        let mut this = Self::default();
+        this.update(input);
+        this.finalize()?;
+        Ok(this)
+    }
+
+    /// Build an empty hasher keeping up to `sketch_size` phrase hashes
+    ///
+    /// # Panics
+    /// If `sketch_size` is 0
+    pub fn with_sketch_size(sketch_size: usize) -> Self {
+        assert!(sketch_size > 0, "LZJD sketch size must be at least 1");
+        Self {
+            sketch_size,
+            parsed: false,
+            state: Some(State {
+                phrases: HashSet::new(),
+                current: FNV_OFFSET,
+            }),
+            sketch: None,
+            hash: None,
+        }
+    }
+
+    /// Maximum number of phrase hashes in the sketch
+    pub fn sketch_size(&self) -> usize {
+        self.sketch_size
+    }
+
+    /// Add `input` to the hashed data, ignored once finalized or for parsed digests
+    pub fn update<S: AsRef<[u8]>>(&mut self, input: S) {
+        let state = match self.state {
+            Some(ref mut state) => state,
+            None => return,
+        };
+
+        for byte in input.as_ref() {
+            state.current = (state.current ^ *byte as u32).wrapping_mul(FNV_PRIME);
+            if state.phrases.insert(state.current) {
+                state.current = FNV_OFFSET;
+            }
+        }
+    }
+
+    /// Compute the digest. Fails with [`Error::InsufficientData`] if no data was hashed.
+    ///
+    /// Finalizing more than once is a no-op.
+    pub fn finalize(&mut self) -> Result<()> {
+        if self.hash.is_some() {
+            return Ok(());
+        }
+        let state = self.state.as_ref().ok_or(Error::NotFinalized)?;
+        if state.phrases.is_empty() {
+            return Err(Error::InsufficientData);
+        }
+
+        let mut sketch: Vec<u32> = state.phrases.iter().map(|h| mix(*h)).collect();
+        sketch.sort_unstable();
+        sketch.dedup();
+        sketch.truncate(self.sketch_size);
+
+        self.hash = Some(format(self.sketch_size, &sketch));
+        self.sketch = Some(sketch);
+        self.state = None;
+        Ok(())
+    }
+
+    /// The digest string, e.g. `lzjd:1024:AAAx...`
+    pub fn digest(&self) -> Result<&str> {
+        self.hash.as_deref().ok_or(Error::NotFinalized)
+    }
+
+    /// The smallest phrase hashes in ascending order
+    pub fn sketch(&self) -> Result<&[u32]> {
+        self.sketch.as_deref().ok_or(Error::NotFinalized)
+    }
+
+    /// Whether this digest was parsed from a string rather than computed from data
+    pub fn is_parsed(&self) -> bool {
+        self.parsed
+    }
+
+    /// Estimated Jaccard similarity of the phrase sets, from 0.0 to 1.0. Sketches of different
+    /// sizes are compared using the smaller size.
+    pub fn jaccard(&self, other: &Lzjd) -> Result<f64> {
+        let (first, second) = (self.sketch()?, other.sketch()?);
+        let size = self.sketch_size.min(other.sketch_size);
+
+        // the smallest hashes of the union, counting those present in both sketches
+        let (mut i, mut j, mut union, mut shared) = (0, 0, 0, 0);
+        while union < size && (i < first.len() || j < second.len()) {
+            match (first.get(i), second.get(j)) {
+                (Some(a), Some(b)) if a == b => {
+                    shared += 1;
+                    i += 1;
+                    j += 1;
+                }
+                (Some(a), Some(b)) if a < b => i += 1,
+                (Some(_), None) => i += 1,
+                _ => j += 1,
+            }
+            union += 1;
+        }
+        Ok(shared as f64 / union as f64)
+    }
+}
+
+/// Spread phrase hashes over the whole range before selecting the smallest, the MurmurHash3
+/// finalizer
+fn mix(mut h: u32) -> u32 {
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85eb_ca6b);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xc2b2_ae35);
+    h ^ (h >> 16)
+}
+
+fn format(sketch_size: usize, sketch: &[u32]) -> String {
+    let bytes: Vec<u8> = sketch.iter().flat_map(|h| h.to_be_bytes()).collect();
+    format!("{}:{}:{}", PREFIX, sketch_size, base64::encode(&bytes))
+}
+
+impl PartialEq for Lzjd {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash
+    }
+}
+
+impl Eq for Lzjd {}
+
+impl Hash for Lzjd {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.hash.hash(state);
+    }
+}
+
+impl fmt::Debug for Lzjd {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Lzjd").field(&self.hash).finish()
+    }
+}
+
+impl fmt::Display for Lzjd {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.hash.as_deref().unwrap_or(""))
+    }
+}
+
+/// Parse a digest in the `lzjd:<sketch size>:<base64 hashes>` form. The hashes must be in
+/// ascending order and no more than the sketch size.
+///
+/// # Example
+/// ```
+/// use fuzzyhash::lzjd::Lzjd;
+///
+/// let lzjd: Lzjd = "lzjd:4:AAAAAQAAAAIAAAAD".parse().unwrap();
+/// assert_eq!(lzjd.sketch().unwrap(), &[1, 2, 3]);
+/// assert!("lzjd:2:AAAAAQAAAAIAAAAD".parse::<Lzjd>().is_err());
+/// ```
+impl FromStr for Lzjd {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.splitn(3, ':');
+        let (prefix, size, encoded) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(prefix), Some(size), Some(encoded)) => (prefix, size, encoded),
+            _ => return Err(Error::MalformedInput),
+        };
+        let sketch_size: usize = size.parse().map_err(|_| Error::MalformedInput)?;
+        let bytes = base64::decode(encoded)?;
+        if prefix != PREFIX || sketch_size == 0 || bytes.is_empty() || bytes.len() % 4 != 0 {
+            return Err(Error::MalformedInput);
+        }
+
+        let sketch: Vec<u32> = bytes
+            .chunks(4)
+            .map(|h| u32::from_be_bytes([h[0], h[1], h[2], h[3]]))
+            .collect();
+        if sketch.len() > sketch_size || sketch.windows(2).any(|w| w[0] >= w[1]) {
+            return Err(Error::MalformedInput);
+        }
+
+        Ok(Self {
+            sketch_size,
+            parsed: true,
+            state: None,
+            hash: Some(format(sketch_size, &sketch)),
+            sketch: Some(sketch),
+        })
+    }
+}
+
+/// Similarities are the estimated Jaccard similarity as a percentage, rounded down
+impl SimilarityDigest for Lzjd {
+    const NAME: &'static str = "lzjd";
+
+    fn update(&mut self, input: &[u8]) {
+        Lzjd::update(self, input)
+    }
+
+    fn finalize(&mut self) -> Result<()> {
+        Lzjd::finalize(self)
+    }
+
+    fn format(&self) -> Result<String> {
+        self.digest().map(str::to_string)
+    }
+
+    fn similarity(&self, other: &Self) -> Result<u32> {
+        Ok((self.jaccard(other)? * 100.0) as u32)
+    }
+}
+
+/// Serializes as the digest string
+#[cfg(feature = "serde")]
+impl serde::Serialize for Lzjd {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        match self.hash {
+            Some(ref hash) => serializer.serialize_str(hash),
+            None => Err(serde::ser::Error::custom(
+                "LZJD must be finalized before serializing",
+            )),
+        }
+    }
+}
+
+/// Deserializes from a digest string, rejecting strings that are not valid LZJD digests
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Lzjd {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = Lzjd;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "an LZJD digest")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, s: &str) -> std::result::Result<Lzjd, E> {
+                s.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(Visitor)
+    }
+}
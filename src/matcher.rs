@@ -0,0 +1,173 @@
+//! Matching files against known fuzzy hashes, like `ssdeep -m` and `ssdeep -k`.
+//!
+//! A [`Matcher`] loads the entries of one or more ssdeep signature files into an [`Index`],
+//! then reports which known files each input matches. As with ssdeep, a known file matches when
+//! its score is above the threshold, so the default threshold of 0 reports every non-zero
+//! score, and matches are listed in the order the known files were loaded.
+//!
+//! # Example
+//! ```no_run
+//! use fuzzyhash::matcher::Matcher;
+//!
+//! let mut matcher = Matcher::new();
+//! matcher.load("known_bad.txt").unwrap();
+//!
+//! let found = matcher.match_file("/tmp/sample.exe", 0).unwrap();
+//! // prints e.g. /tmp/sample.exe matches known_bad.txt:/samples/dropper.exe (63)
+//! print!("{}", found);
+//! ```
+
+use super::{
+    index::Index,
+    sigfile::{self, Reader},
+    FuzzyHash, Result,
+};
+use std::{fmt, fs::File, io::BufReader, path::Path};
+
+/// A known file loaded into a [`Matcher`]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Known {
+    /// Fuzzy hash of the known file
+    pub hash: FuzzyHash,
+    /// Name of the known file, as listed in its signature file
+    pub filename: String,
+    /// Signature file the entry was loaded from, empty if it was added directly
+    pub source: String,
+}
+
+/// A known file matching an input
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KnownMatch {
+    /// Name of the known file
+    pub filename: String,
+    /// Signature file the known file was loaded from, empty if it was added directly
+    pub source: String,
+    /// Similarity score between 1 and 100
+    pub score: u32,
+}
+
+/// The known files matched by one input
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileMatches {
+    /// Name of the input
+    pub filename: String,
+    /// Matching known files, in the order they were loaded
+    pub matches: Vec<KnownMatch>,
+}
+
+/// Formats the matches the way `ssdeep -m` prints them, one line per match
+impl fmt::Display for FileMatches {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for found in &self.matches {
+            write!(f, "{} matches ", self.filename)?;
+            if !found.source.is_empty() {
+                write!(f, "{}:", found.source)?;
+            }
+            writeln!(f, "{} ({})", found.filename, found.score)?;
+        }
+        Ok(())
+    }
+}
+
+/// Matches inputs against a set of known fuzzy hashes
+#[derive(Default)]
+pub struct Matcher {
+    index: Index,
+    known: Vec<Known>,
+}
+
+impl Matcher {
+    /// Build a matcher without any known files
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of known files
+    pub fn len(&self) -> usize {
+        self.known.len()
+    }
+
+    /// Returns `true` if no known files have been loaded
+    pub fn is_empty(&self) -> bool {
+        self.known.is_empty()
+    }
+
+    /// The known files, in the order they were loaded
+    pub fn known(&self) -> &[Known] {
+        &self.known
+    }
+
+    /// Load every entry of the ssdeep signature file at `path`, returning the number of entries.
+    /// Nothing is loaded if any entry is invalid. File names which are not valid UTF-8 are
+    /// reported with replacement characters.
+    pub fn load<P: AsRef<Path>>(&mut self, path: P) -> Result<usize> {
+        let source = path.as_ref().display().to_string();
+        let reader = Reader::new(BufReader::new(File::open(path.as_ref())?))?;
+        let entries = reader.collect::<Result<Vec<sigfile::Entry>>>()?;
+
+        let count = entries.len();
+        for entry in entries {
+            let filename = String::from_utf8_lossy(&entry.filename).into_owned();
+            self.add(entry.hash, filename, source.clone())?;
+        }
+        Ok(count)
+    }
+
+    /// Add a single known file
+    ///
+    /// # Arguments
+    /// * `hash` - finalized fuzzy hash of the known file
+    /// * `filename` - name reported when the known file matches
+    /// * `source` - where the known file came from, reported before the file name if not empty
+    pub fn add<F: Into<String>, S: Into<String>>(
+        &mut self,
+        hash: FuzzyHash,
+        filename: F,
+        source: S,
+    ) -> Result<()> {
+        self.index.insert(hash.clone())?;
+        self.known.push(Known {
+            hash,
+            filename: filename.into(),
+            source: source.into(),
+        });
+        Ok(())
+    }
+
+    /// Known files scoring above `threshold` against `hash`, in the order they were loaded
+    ///
+    /// # Arguments
+    /// * `hash` - finalized fuzzy hash of the input
+    /// * `filename` - name of the input, reported in the result
+    /// * `threshold` - known files must score above this to match
+    pub fn match_hash<F: Into<String>>(
+        &self,
+        hash: &FuzzyHash,
+        filename: F,
+        threshold: u32,
+    ) -> Result<FileMatches> {
+        let mut found = self.index.query(hash, threshold.saturating_add(1))?;
+        found.sort_by_key(|m| m.id);
+
+        Ok(FileMatches {
+            filename: filename.into(),
+            matches: found
+                .into_iter()
+                .map(|m| KnownMatch {
+                    filename: self.known[m.id].filename.clone(),
+                    source: self.known[m.id].source.clone(),
+                    score: m.score,
+                })
+                .collect(),
+        })
+    }
+
+    /// Hash the file at `path` and match it against the known files, see
+    /// [`match_hash`](Self::match_hash)
+    pub fn match_file<P: AsRef<Path>>(&self, path: P, threshold: u32) -> Result<FileMatches> {
+        let hash = FuzzyHash::file(path.as_ref())?;
+        self.match_hash(&hash, path.as_ref().display().to_string(), threshold)
+    }
+}
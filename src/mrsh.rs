@@ -0,0 +1,413 @@
+//! mrsh-v2 style digests: CTPH chunks stored in Bloom filters.
+//!
+//! The input is cut into chunks with the rolling hash of ssdeep, ending a chunk whenever the
+//! rolling hash modulo the block size hits its trigger value, and chunks are at least a quarter
+//! of the block size long. Instead of keeping one character per chunk in a 64 character
+//! signature, the FNV-1a hash of every chunk sets 5 bits of a 256 byte Bloom filter, and a new
+//! filter is started once a filter holds 160 chunks. The digest grows with the input, so
+//! nothing is lost on large inputs, and the filters of a fragment can be found among the
+//! filters of the file it was taken from.
+//!
+//! [`Mrsh::containment`] scores how much of one input is found in another, which is what
+//! finds fragments of files. [`Mrsh::compare`] scores how much the inputs resemble each other
+//! as a whole.
+//!
+//! Digests are written as `mrsh:<block size>:<input length>:<base64 filters>`.
+//!
+//! # Example
+//! ```
+//! use fuzzyhash::mrsh::Mrsh;
+//!
+//! let file: Vec<u8> = (0..200_000u32)
+//!     .map(|i| (i.wrapping_mul(i >> 3) % 251 ^ i >> 9) as u8)
+//!     .collect();
+//! let fragment = Mrsh::new(&file[100_000..140_000]).unwrap();
+//! let file = Mrsh::new(&file).unwrap();
+//!
+//! assert!(fragment.containment(&file).unwrap() > 80);
+//! assert!(fragment.compare(&file).unwrap() < 40);
+//! ```
+
+use super::{base64, digest::SimilarityDigest, error::Error, roll::Roll, Result};
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
+
+/// Block size used by [`Mrsh::default`], the average length of a chunk
+pub const DEFAULT_BLOCK_SIZE: u32 = 160;
+
+const PREFIX: &str = "mrsh";
+const FILTER_SIZE: usize = 256;
+const FILTER_BITS: u32 = FILTER_SIZE as u32 * 8;
+const BITS_PER_HASH: u32 = 11;
+const HASH_COUNT: u32 = 5;
+const MAX_ELEMENTS: u32 = 160;
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Hashing state, dropped once the digest is computed
+#[derive(Clone)]
+struct State {
+    roll: Roll,
+    /// FNV-1a hash and length of the current chunk
+    chunk: u64,
+    chunk_len: u64,
+    /// Chunks inserted into the last filter
+    elements: u32,
+}
+
+/// An mrsh-v2 style digest, built by hashing data or parsed from its string form
+///
+/// As with [`FuzzyHash`](crate::FuzzyHash), equality and hashing use the digest string.
+#[derive(Clone)]
+pub struct Mrsh {
+    block_size: u32,
+    parsed: bool,
+    len: u64,
+    filters: Vec<[u8; FILTER_SIZE]>,
+    state: Option<State>,
+    hash: Option<String>,
+}
+
+impl Default for Mrsh {
+    fn default() -> Self {
+        Self::with_block_size(DEFAULT_BLOCK_SIZE)
+    }
+}
+
+impl Mrsh {
+    /// Hash `input` with the default block size
+    pub fn new<S: AsRef<[u8]>>(input: S) -> Result<Self> {
+        let mut this = Self::default();
+        this.update(input);
+        this.finalize()?;
+        Ok(this)
+    }
+
+    /// Build an empty hasher cutting chunks of `block_size` bytes on average. Smaller blocks
+    /// find smaller fragments but give larger digests.
+    ///
+    /// # Panics
+    /// If `block_size` is smaller than 4
+    pub fn with_block_size(block_size: u32) -> Self {
+        assert!(block_size >= 4, "mrsh block size must be at least 4");
+        Self {
+            block_size,
+            parsed: false,
+            len: 0,
+            filters: Vec::new(),
+            state: Some(State {
+                roll: Roll::new(),
+                chunk: FNV_OFFSET,
+                chunk_len: 0,
+                elements: 0,
+            }),
+            hash: None,
+        }
+    }
+
+    /// Average length of a chunk
+    pub fn block_size(&self) -> u32 {
+        self.block_size
+    }
+
+    /// Number of bytes hashed
+    pub fn input_len(&self) -> u64 {
+        self.len
+    }
+
+    /// Number of Bloom filters in the digest
+    pub fn filter_count(&self) -> usize {
+        self.filters.len()
+    }
+
+    /// Add `input` to the hashed data, ignored once finalized or for parsed digests
+    pub fn update<S: AsRef<[u8]>>(&mut self, input: S) {
+        if self.hash.is_some() {
+            return;
+        }
+        let state = match self.state {
+            Some(ref mut state) => state,
+            None => return,
+        };
+
+        let min_chunk_len = u64::from(self.block_size / 4);
+        for byte in input.as_ref() {
+            state.roll.hash(*byte);
+            state.chunk = (state.chunk ^ *byte as u64).wrapping_mul(FNV_PRIME);
+            state.chunk_len += 1;
+
+            if state.chunk_len >= min_chunk_len
+                && state.roll.sum() % self.block_size == self.block_size - 1
+            {
+                end_chunk(state, &mut self.filters);
+            }
+        }
+        self.len += input.as_ref().len() as u64;
+    }
+
+    /// Compute the digest, the last chunk ends with the input. Fails with
+    /// [`Error::InsufficientData`] if no data was hashed.
+    ///
+    /// Finalizing more than once is a no-op.
+    pub fn finalize(&mut self) -> Result<()> {
+        if self.hash.is_some() {
+            return Ok(());
+        }
+        let mut state = self.state.take().ok_or(Error::NotFinalized)?;
+        if self.len == 0 {
+            self.state = Some(state);
+            return Err(Error::InsufficientData);
+        }
+        if state.chunk_len > 0 {
+            end_chunk(&mut state, &mut self.filters);
+        }
+        self.hash = Some(format(self.block_size, self.len, &self.filters));
+        Ok(())
+    }
+
+    /// The digest string, e.g. `mrsh:160:4096:AAAA...`
+    pub fn digest(&self) -> Result<&str> {
+        self.hash.as_deref().ok_or(Error::NotFinalized)
+    }
+
+    /// Whether this digest was parsed from a string rather than computed from data
+    pub fn is_parsed(&self) -> bool {
+        self.parsed
+    }
+
+    /// How much of this input is found in `other`, from 0 to 100
+    ///
+    /// Each filter of this digest is matched against every pair of consecutive filters of
+    /// `other`, since the chunks of a fragment span at most two filters of the file they were
+    /// taken from. The bits found beyond those expected by chance are averaged over the
+    /// filters, weighted by their number of bits, and overlaps that chance could explain score
+    /// 0. Both digests must use the same block size.
+    pub fn containment(&self, other: &Mrsh) -> Result<u32> {
+        if self.hash.is_none() || other.hash.is_none() {
+            return Err(Error::NotFinalized);
+        }
+        if self.block_size != other.block_size {
+            return Err(Error::IncompatibleDigests);
+        }
+
+        let windows: Vec<[u8; FILTER_SIZE]> = match other.filters.len() {
+            0 | 1 => other.filters.clone(),
+            _ => other
+                .filters
+                .windows(2)
+                .map(|pair| {
+                    let mut union = pair[0];
+                    union
+                        .iter_mut()
+                        .zip(pair[1].iter())
+                        .for_each(|(a, b)| *a |= b);
+                    union
+                })
+                .collect(),
+        };
+
+        let (mut found, mut total) = (0.0, 0.0);
+        for filter in &self.filters {
+            let bits = count_ones(filter) as f64;
+            let best = windows
+                .iter()
+                .map(|window| {
+                    let common: u32 = filter
+                        .iter()
+                        .zip(window.iter())
+                        .map(|(a, b)| (a & b).count_ones())
+                        .sum();
+                    // each bit of the filter is set in the window with this probability by
+                    // chance, overlaps within 3 standard deviations of chance are noise
+                    let density = count_ones(window) as f64 / FILTER_BITS as f64;
+                    let chance = bits * density;
+                    let noise = 3.0 * (chance * (1.0 - density)).sqrt();
+                    if common as f64 <= chance + noise || bits <= chance {
+                        0.0
+                    } else {
+                        (common as f64 - chance) / (bits - chance)
+                    }
+                })
+                .fold(0.0, f64::max);
+            found += best * bits;
+            total += bits;
+        }
+        if total == 0.0 {
+            return Err(Error::InsufficientData);
+        }
+        Ok((100.0 * found / total).round() as u32)
+    }
+
+    /// Similarity of the inputs as a whole from 0 to 100, the smaller containment of either
+    /// input in the other
+    pub fn compare(&self, other: &Mrsh) -> Result<u32> {
+        Ok(self.containment(other)?.min(other.containment(self)?))
+    }
+}
+
+/// Insert the finished chunk into the last filter, starting a new filter when it is full
+fn end_chunk(state: &mut State, filters: &mut Vec<[u8; FILTER_SIZE]>) {
+    if filters.is_empty() || state.elements >= MAX_ELEMENTS {
+        filters.push([0; FILTER_SIZE]);
+        state.elements = 0;
+    }
+    if let Some(filter) = filters.last_mut() {
+        let mut new = false;
+        for i in 0..HASH_COUNT {
+            let bit = (state.chunk >> (i * BITS_PER_HASH)) as u32 & (FILTER_BITS - 1);
+            let (byte, mask) = ((bit >> 3) as usize, 1 << (bit & 7));
+            new |= filter[byte] & mask == 0;
+            filter[byte] |= mask;
+        }
+        if new {
+            state.elements += 1;
+        }
+    }
+    state.chunk = FNV_OFFSET;
+    state.chunk_len = 0;
+}
+
+fn count_ones(filter: &[u8; FILTER_SIZE]) -> u32 {
+    filter.iter().map(|b| b.count_ones()).sum()
+}
+
+fn format(block_size: u32, len: u64, filters: &[[u8; FILTER_SIZE]]) -> String {
+    let bits: Vec<u8> = filters.iter().flatten().copied().collect();
+    format!(
+        "{}:{}:{}:{}",
+        PREFIX,
+        block_size,
+        len,
+        base64::encode(&bits)
+    )
+}
+
+impl PartialEq for Mrsh {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash
+    }
+}
+
+impl Eq for Mrsh {}
+
+impl Hash for Mrsh {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.hash.hash(state);
+    }
+}
+
+impl fmt::Debug for Mrsh {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Mrsh").field(&self.hash).finish()
+    }
+}
+
+impl fmt::Display for Mrsh {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.hash.as_deref().unwrap_or(""))
+    }
+}
+
+/// Parse a digest in the `mrsh:<block size>:<input length>:<base64 filters>` form
+impl FromStr for Mrsh {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let (block_size, len, encoded) = match parts.as_slice() {
+            [PREFIX, block_size, len, encoded] => (
+                block_size
+                    .parse::<u32>()
+                    .map_err(|_| Error::MalformedInput)?,
+                len.parse::<u64>().map_err(|_| Error::MalformedInput)?,
+                encoded,
+            ),
+            _ => return Err(Error::MalformedInput),
+        };
+        let bits = base64::decode(encoded)?;
+        if block_size < 4 || len == 0 || bits.is_empty() || bits.len() % FILTER_SIZE != 0 {
+            return Err(Error::MalformedInput);
+        }
+
+        let filters: Vec<[u8; FILTER_SIZE]> = bits
+            .chunks(FILTER_SIZE)
+            .map(|chunk| {
+                let mut filter = [0; FILTER_SIZE];
+                filter.copy_from_slice(chunk);
+                filter
+            })
+            .collect();
+        Ok(Self {
+            block_size,
+            parsed: true,
+            len,
+            hash: Some(format(block_size, len, &filters)),
+            filters,
+            state: None,
+        })
+    }
+}
+
+/// Similarities are resemblance scores from [`Mrsh::compare`]
+impl SimilarityDigest for Mrsh {
+    const NAME: &'static str = "mrsh";
+
+    fn update(&mut self, input: &[u8]) {
+        Mrsh::update(self, input)
+    }
+
+    fn finalize(&mut self) -> Result<()> {
+        Mrsh::finalize(self)
+    }
+
+    fn format(&self) -> Result<String> {
+        self.digest().map(str::to_string)
+    }
+
+    fn similarity(&self, other: &Self) -> Result<u32> {
+        self.compare(other)
+    }
+}
+
+/// Serializes as the digest string
+#[cfg(feature = "serde")]
+impl serde::Serialize for Mrsh {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        match self.hash {
+            Some(ref hash) => serializer.serialize_str(hash),
+            None => Err(serde::ser::Error::custom(
+                "mrsh digest must be finalized before serializing",
+            )),
+        }
+    }
+}
+
+/// Deserializes from a digest string, rejecting strings that are not valid mrsh digests
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Mrsh {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = Mrsh;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "an mrsh digest")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, s: &str) -> std::result::Result<Mrsh, E> {
+                s.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(Visitor)
+    }
+}
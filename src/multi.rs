@@ -0,0 +1,299 @@
+//! Multi-resolution ssdeep digests, keeping the signature of every block size.
+//!
+//! An ssdeep digest keeps the signatures of two block sizes, chosen from the length of the
+//! input, so inputs more than twice as long as each other cannot be compared. The hasher
+//! computes a signature for a range of block sizes while hashing, and a [`MultiHash`] keeps
+//! all of them: inputs of quite different lengths still have block sizes in common, and
+//! [`MultiHash::compare`] scores them at the block size where they match best.
+//!
+//! Digests are written as `multi:<smallest block size>:<signature>:<signature>...`, one
+//! signature for each block size from the smallest one, doubling each time.
+//!
+//! # Example
+//! ```
+//! use fuzzyhash::{multi::MultiHash, FuzzyHash};
+//!
+//! let header: Vec<u8> = (0..10_000u32).map(|i| (i * i >> 5) as u8 ^ (i >> 8) as u8).collect();
+//! let mut file = header.clone();
+//! file.extend((0..30_000u32).map(|i| (i * 7 >> 3) as u8 ^ (i * i >> 11) as u8));
+//!
+//! // the block sizes of ssdeep digests are too far apart...
+//! assert!(FuzzyHash::new(&header)
+//!     .unwrap()
+//!     .compare_to(&FuzzyHash::new(&file).unwrap())
+//!     .is_none());
+//! // ...but both inputs have signatures at common block sizes
+//! let header = MultiHash::new(&header).unwrap();
+//! let file = MultiHash::new(&file).unwrap();
+//! assert!(header.compare(&file).unwrap() > 0);
+//! ```
+
+use super::{compare, constants, digest::SimilarityDigest, error::Error, hasher, Result};
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
+
+const PREFIX: &str = "multi";
+
+/// A multi-resolution ssdeep digest, built by hashing data or parsed from its string form
+///
+/// As with [`FuzzyHash`](crate::FuzzyHash), equality and hashing use the digest string.
+#[derive(Clone)]
+pub struct MultiHash {
+    parsed: bool,
+    block_size: u32,
+    signatures: Vec<String>,
+    state: Option<hasher::Hasher>,
+    hash: Option<String>,
+}
+
+impl Default for MultiHash {
+    fn default() -> Self {
+        Self {
+            parsed: false,
+            block_size: constants::MIN_BLOCK_SIZE,
+            signatures: Vec::new(),
+            state: Some(hasher::Hasher::new()),
+            hash: None,
+        }
+    }
+}
+
+impl MultiHash {
+    /// Hash `input`
+    pub fn new<S: AsRef<[u8]>>(input: S) -> Result<Self> {
+        let mut this = Self::default();
+        this.update(input);
+        this.finalize()?;
+        Ok(this)
+    }
+
+    /// Add `input` to the hashed data, ignored once finalized or for parsed digests
+    pub fn update<S: AsRef<[u8]>>(&mut self, input: S) {
+        if self.hash.is_some() {
+            return;
+        }
+        if let Some(ref mut state) = self.state {
+            let input = input.as_ref();
+            state.update(input, input.len());
+        }
+    }
+
+    /// Compute the digest
+    ///
+    /// Finalizing more than once is a no-op.
+    pub fn finalize(&mut self) -> Result<()> {
+        if self.hash.is_some() {
+            return Ok(());
+        }
+        let state = self.state.take().ok_or(Error::NotFinalized)?;
+        let (block_size, signatures) = state.signatures();
+        self.hash = Some(format(block_size, &signatures));
+        self.block_size = block_size;
+        self.signatures = signatures;
+        Ok(())
+    }
+
+    /// The digest string, e.g. `multi:48:<signature>:<signature>`
+    pub fn digest(&self) -> Result<&str> {
+        self.hash.as_deref().ok_or(Error::NotFinalized)
+    }
+
+    /// Whether this digest was parsed from a string rather than computed from data
+    pub fn is_parsed(&self) -> bool {
+        self.parsed
+    }
+
+    /// Block sizes of the digest, in ascending order
+    pub fn block_sizes(&self) -> Result<Vec<u32>> {
+        self.digest()?;
+        Ok((0..self.signatures.len())
+            .map(|i| self.block_size << i)
+            .collect())
+    }
+
+    /// The untruncated signature computed at `block_size`, if the digest has one
+    ///
+    /// # Example
+    /// ```
+    /// use fuzzyhash::{multi::MultiHash, FuzzyHash};
+    ///
+    /// let data = "this is our test data!";
+    /// let fuzzy_hash = FuzzyHash::new(data).unwrap();
+    /// let multi = MultiHash::new(data).unwrap();
+    /// assert_eq!(
+    ///     multi.signature(fuzzy_hash.block_size().unwrap()).unwrap(),
+    ///     Some(fuzzy_hash.signature1().unwrap())
+    /// );
+    /// ```
+    pub fn signature(&self, block_size: u32) -> Result<Option<&str>> {
+        self.digest()?;
+        Ok(self
+            .block_sizes()?
+            .iter()
+            .position(|size| *size == block_size)
+            .map(|i| self.signatures[i].as_str()))
+    }
+
+    /// Score from 0 to 100, the best ssdeep score of the signatures at any block size both
+    /// digests have. Fails with [`Error::IncompatibleBlockSizes`], holding the smallest block
+    /// size of each digest, if they have no block size in common.
+    ///
+    /// As with ssdeep, signatures need a common substring of 7 characters to score at all, so
+    /// the shorter input must still have a signature of several characters at the smallest
+    /// block size of the longer one. Hashing drops the small block sizes as the input grows,
+    /// which in practice allows inputs about four times as long as each other.
+    pub fn compare(&self, other: &MultiHash) -> Result<u32> {
+        if self.digest()? == other.digest()? {
+            return Ok(100);
+        }
+        let mut best = None;
+        for (i, signature) in self.signatures.iter().enumerate() {
+            let block_size = self.block_size << i;
+            if let Some(other_signature) = other.signature(block_size)? {
+                let score = compare::score_signatures(signature, other_signature, block_size);
+                best = best.max(Some(score));
+            }
+        }
+        best.ok_or(Error::IncompatibleBlockSizes(
+            self.block_size,
+            other.block_size,
+        ))
+    }
+}
+
+fn format(block_size: u32, signatures: &[String]) -> String {
+    format!("{}:{}:{}", PREFIX, block_size, signatures.join(":"))
+}
+
+impl PartialEq for MultiHash {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash
+    }
+}
+
+impl Eq for MultiHash {}
+
+impl Hash for MultiHash {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.hash.hash(state);
+    }
+}
+
+impl fmt::Debug for MultiHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("MultiHash").field(&self.hash).finish()
+    }
+}
+
+impl fmt::Display for MultiHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.hash.as_deref().unwrap_or(""))
+    }
+}
+
+/// Parse a digest in the `multi:<smallest block size>:<signature>...` form
+impl FromStr for MultiHash {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let (block_size, signatures) = match parts.as_slice() {
+            [PREFIX, block_size, signatures @ ..] if !signatures.is_empty() => (
+                block_size
+                    .parse::<u32>()
+                    .map_err(|_| Error::MalformedInput)?,
+                signatures,
+            ),
+            _ => return Err(Error::MalformedInput),
+        };
+        let first = (0..constants::NUM_BLOCKHASHES)
+            .find(|i| constants::MIN_BLOCK_SIZE << i == block_size)
+            .ok_or(Error::MalformedInput)?;
+        if first as usize + signatures.len() > constants::NUM_BLOCKHASHES as usize {
+            return Err(Error::MalformedInput);
+        }
+        for signature in signatures {
+            if signature.len() > constants::SPAM_SUM_LENGTH as usize
+                || !signature
+                    .bytes()
+                    .all(|c| constants::BASE64_CHARS.as_bytes().contains(&c))
+            {
+                return Err(Error::MalformedInput);
+            }
+        }
+
+        let signatures: Vec<String> = signatures.iter().map(|s| s.to_string()).collect();
+        Ok(Self {
+            parsed: true,
+            block_size,
+            hash: Some(format(block_size, &signatures)),
+            signatures,
+            state: None,
+        })
+    }
+}
+
+/// Similarities are scores from [`MultiHash::compare`]
+impl SimilarityDigest for MultiHash {
+    const NAME: &'static str = "ssdeep-multi";
+
+    fn update(&mut self, input: &[u8]) {
+        MultiHash::update(self, input)
+    }
+
+    fn finalize(&mut self) -> Result<()> {
+        MultiHash::finalize(self)
+    }
+
+    fn format(&self) -> Result<String> {
+        self.digest().map(str::to_string)
+    }
+
+    fn similarity(&self, other: &Self) -> Result<u32> {
+        self.compare(other)
+    }
+}
+
+/// Serializes as the digest string
+#[cfg(feature = "serde")]
+impl serde::Serialize for MultiHash {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        match self.hash {
+            Some(ref hash) => serializer.serialize_str(hash),
+            None => Err(serde::ser::Error::custom(
+                "multi-resolution digest must be finalized before serializing",
+            )),
+        }
+    }
+}
+
+/// Deserializes from a digest string, rejecting strings that are not valid multi-resolution
+/// digests
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for MultiHash {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = MultiHash;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a multi-resolution ssdeep digest")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, s: &str) -> std::result::Result<MultiHash, E> {
+                s.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(Visitor)
+    }
+}
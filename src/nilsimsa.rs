@@ -0,0 +1,315 @@
+//! Nilsimsa, a locality sensitive hash for short texts.
+//!
+//! Every trigram that can be formed from a sliding window of 5 bytes is hashed into one of 256
+//! accumulators, and the digest has a bit set for every accumulator above the average. The
+//! 256 bit digest does not depend on the input size, so unlike ssdeep it still distinguishes
+//! messages of a few hundred bytes, which makes it popular for clustering email and spam.
+//!
+//! Digests are compared with [`Nilsimsa::compare`], which counts matching bits and ranges from
+//! -127 to 128 for identical digests. Unrelated inputs score around 0, and negative scores are
+//! rare.
+//!
+//! # Example
+//! ```
+//! use fuzzyhash::nilsimsa::Nilsimsa;
+//!
+//! let first = Nilsimsa::new("Dear friend, you have won a prize of one million dollars").unwrap();
+//! let mut second = Nilsimsa::default();
+//! second.update("Dear friend, you have won a prize ");
+//! second.update("of two million dollars");
+//! second.finalize().unwrap();
+//!
+//! assert_eq!(first.digest().unwrap().len(), 64);
+//! assert!(first.compare(&second).unwrap() > 64);
+//! ```
+
+use super::{digest::SimilarityDigest, error::Error, Result};
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
+
+const DIGEST_SIZE: usize = 32;
+
+/// Byte permutation of the original implementation, generated by its `filltran`
+const TRAN: [u8; 256] = [
+    0x02, 0xd6, 0x9e, 0x6f, 0xf9, 0x1d, 0x04, 0xab, 0xd0, 0x22, 0x16, 0x1f, 0xd8, 0x73, 0xa1, 0xac,
+    0x3b, 0x70, 0x62, 0x96, 0x1e, 0x6e, 0x8f, 0x39, 0x9d, 0x05, 0x14, 0x4a, 0xa6, 0xbe, 0xae, 0x0e,
+    0xcf, 0xb9, 0x9c, 0x9a, 0xc7, 0x68, 0x13, 0xe1, 0x2d, 0xa4, 0xeb, 0x51, 0x8d, 0x64, 0x6b, 0x50,
+    0x23, 0x80, 0x03, 0x41, 0xec, 0xbb, 0x71, 0xcc, 0x7a, 0x86, 0x7f, 0x98, 0xf2, 0x36, 0x5e, 0xee,
+    0x8e, 0xce, 0x4f, 0xb8, 0x32, 0xb6, 0x5f, 0x59, 0xdc, 0x1b, 0x31, 0x4c, 0x7b, 0xf0, 0x63, 0x01,
+    0x6c, 0xba, 0x07, 0xe8, 0x12, 0x77, 0x49, 0x3c, 0xda, 0x46, 0xfe, 0x2f, 0x79, 0x1c, 0x9b, 0x30,
+    0xe3, 0x00, 0x06, 0x7e, 0x2e, 0x0f, 0x38, 0x33, 0x21, 0xad, 0xa5, 0x54, 0xca, 0xa7, 0x29, 0xfc,
+    0x5a, 0x47, 0x69, 0x7d, 0xc5, 0x95, 0xb5, 0xf4, 0x0b, 0x90, 0xa3, 0x81, 0x6d, 0x25, 0x55, 0x35,
+    0xf5, 0x75, 0x74, 0x0a, 0x26, 0xbf, 0x19, 0x5c, 0x1a, 0xc6, 0xff, 0x99, 0x5d, 0x84, 0xaa, 0x66,
+    0x3e, 0xaf, 0x78, 0xb3, 0x20, 0x43, 0xc1, 0xed, 0x24, 0xea, 0xe6, 0x3f, 0x18, 0xf3, 0xa0, 0x42,
+    0x57, 0x08, 0x53, 0x60, 0xc3, 0xc0, 0x83, 0x40, 0x82, 0xd7, 0x09, 0xbd, 0x44, 0x2a, 0x67, 0xa8,
+    0x93, 0xe0, 0xc2, 0x56, 0x9f, 0xd9, 0xdd, 0x85, 0x15, 0xb4, 0x8a, 0x27, 0x28, 0x92, 0x76, 0xde,
+    0xef, 0xf8, 0xb2, 0xb7, 0xc9, 0x3d, 0x45, 0x94, 0x4b, 0x11, 0x0d, 0x65, 0xd5, 0x34, 0x8b, 0x91,
+    0x0c, 0xfa, 0x87, 0xe9, 0x7c, 0x5b, 0xb1, 0x4d, 0xe5, 0xd4, 0xcb, 0x10, 0xa2, 0x17, 0x89, 0xbc,
+    0xdb, 0xb0, 0xe2, 0x97, 0x88, 0x52, 0xf7, 0x48, 0xd3, 0x61, 0x2c, 0x3a, 0x2b, 0xd1, 0x8c, 0xfb,
+    0xf1, 0xcd, 0xe4, 0x6a, 0xe7, 0xa9, 0xfd, 0xc4, 0x37, 0xc8, 0xd2, 0xf6, 0xdf, 0x58, 0x72, 0x4e,
+];
+
+/// Hashing state, dropped once the digest is computed
+#[derive(Clone)]
+struct State {
+    accumulators: [u32; 256],
+    /// The last 4 bytes, most recent first
+    last: [u8; 4],
+    len: u64,
+}
+
+/// A Nilsimsa digest, built by hashing data or parsed from its hex string
+///
+/// As with [`FuzzyHash`](crate::FuzzyHash), equality and hashing use the digest string.
+#[derive(Clone)]
+pub struct Nilsimsa {
+    state: Option<State>,
+    code: Option<[u8; DIGEST_SIZE]>,
+    hash: Option<String>,
+}
+
+impl Default for Nilsimsa {
+    fn default() -> Self {
+        Self {
+            state: Some(State {
+                accumulators: [0; 256],
+                last: [0; 4],
+                len: 0,
+            }),
+            code: None,
+            hash: None,
+        }
+    }
+}
+
+impl Nilsimsa {
+    /// Hash `input`
+    pub fn new<S: AsRef<[u8]>>(input: S) -> Result<Self> {
+        let mut this = Self::default();
+        this.update(input);
+        this.finalize()?;
+        Ok(this)
+    }
+
+    /// Add `input` to the hashed data, ignored once finalized or for parsed digests
+    pub fn update<S: AsRef<[u8]>>(&mut self, input: S) {
+        if self.hash.is_some() {
+            return;
+        }
+        let state = match self.state {
+            Some(ref mut state) => state,
+            None => return,
+        };
+
+        for byte in input.as_ref() {
+            let (c, [l0, l1, l2, l3], len) = (*byte, state.last, state.len);
+            let accumulators = &mut state.accumulators;
+            let mut count = |a, b, c, n| accumulators[tran3(a, b, c, n) as usize] += 1;
+            if len > 1 {
+                count(c, l0, l1, 0);
+            }
+            if len > 2 {
+                count(c, l0, l2, 1);
+                count(c, l1, l2, 2);
+            }
+            if len > 3 {
+                count(c, l0, l3, 3);
+                count(c, l1, l3, 4);
+                count(c, l2, l3, 5);
+                count(l3, l0, c, 6);
+                count(l3, l2, c, 7);
+            }
+            state.last = [c, l0, l1, l2];
+            state.len += 1;
+        }
+    }
+
+    /// Compute the digest. Inputs shorter than 3 bytes have no trigrams and give an all zero
+    /// digest.
+    ///
+    /// Finalizing more than once is a no-op.
+    pub fn finalize(&mut self) -> Result<()> {
+        if self.hash.is_some() {
+            return Ok(());
+        }
+        let state = self.state.as_ref().ok_or(Error::NotFinalized)?;
+
+        let trigrams = match state.len {
+            0..=2 => 0,
+            3 => 1,
+            4 => 4,
+            len => 8 * len - 28,
+        };
+        let threshold = trigrams / 256;
+        let mut code = [0u8; DIGEST_SIZE];
+        for (i, accumulator) in state.accumulators.iter().enumerate() {
+            if *accumulator as u64 > threshold {
+                code[i >> 3] |= 1 << (i & 7);
+            }
+        }
+        // the most significant bytes come first in the digest
+        code.reverse();
+
+        self.hash = Some(code.iter().map(|b| format!("{:02x}", b)).collect());
+        self.code = Some(code);
+        Ok(())
+    }
+
+    /// The digest as 64 lower case hex characters
+    pub fn digest(&self) -> Result<&str> {
+        self.hash.as_deref().ok_or(Error::NotFinalized)
+    }
+
+    /// Whether this digest was parsed from a string rather than computed from data
+    pub fn is_parsed(&self) -> bool {
+        self.state.is_none()
+    }
+
+    /// Number of bits both digests agree on, less 128: 128 for identical digests, down to -127
+    /// for complementary ones, which the reference implementation clamps to stay within a
+    /// signed byte's range around 0
+    pub fn compare(&self, other: &Nilsimsa) -> Result<i32> {
+        let (first, second) = match (&self.code, &other.code) {
+            (Some(first), Some(second)) => (first, second),
+            _ => return Err(Error::NotFinalized),
+        };
+        let differing: u32 = first
+            .iter()
+            .zip(second.iter())
+            .map(|(a, b)| (a ^ b).count_ones())
+            .sum();
+        Ok((128 - differing as i32).max(-127))
+    }
+}
+
+/// Hash a trigram into an accumulator, differently for each of its 8 positions `n`
+fn tran3(a: u8, b: u8, c: u8, n: u8) -> u8 {
+    let first = TRAN[a.wrapping_add(n) as usize] ^ TRAN[b as usize].wrapping_mul(n * 2 + 1);
+    first.wrapping_add(TRAN[(c ^ TRAN[n as usize]) as usize])
+}
+
+impl PartialEq for Nilsimsa {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash
+    }
+}
+
+impl Eq for Nilsimsa {}
+
+impl Hash for Nilsimsa {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.hash.hash(state);
+    }
+}
+
+impl fmt::Debug for Nilsimsa {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Nilsimsa").field(&self.hash).finish()
+    }
+}
+
+impl fmt::Display for Nilsimsa {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.hash.as_deref().unwrap_or(""))
+    }
+}
+
+/// Parse a 64 character hex digest, in either case. Parsed digests are displayed in lower
+/// case.
+///
+/// # Example
+/// ```
+/// use fuzzyhash::nilsimsa::Nilsimsa;
+///
+/// let digest = "14C8118000000000030800000004042004189020001308014088003280000078";
+/// let nilsimsa: Nilsimsa = digest.parse().unwrap();
+/// assert_eq!(nilsimsa.to_string(), digest.to_lowercase());
+/// assert!("14c8".parse::<Nilsimsa>().is_err());
+/// ```
+impl FromStr for Nilsimsa {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.len() != DIGEST_SIZE * 2 {
+            return Err(Error::MalformedInput);
+        }
+        let mut code = [0u8; DIGEST_SIZE];
+        for (byte, pair) in code.iter_mut().zip(s.as_bytes().chunks(2)) {
+            *byte = std::str::from_utf8(pair)
+                .ok()
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or(Error::MalformedInput)?;
+        }
+
+        Ok(Self {
+            state: None,
+            hash: Some(s.to_lowercase()),
+            code: Some(code),
+        })
+    }
+}
+
+/// Similarities are the scores above 0 scaled to 100, lower scores are no better than chance
+/// and give 0
+impl SimilarityDigest for Nilsimsa {
+    const NAME: &'static str = "nilsimsa";
+
+    fn update(&mut self, input: &[u8]) {
+        Nilsimsa::update(self, input)
+    }
+
+    fn finalize(&mut self) -> Result<()> {
+        Nilsimsa::finalize(self)
+    }
+
+    fn format(&self) -> Result<String> {
+        self.digest().map(str::to_string)
+    }
+
+    fn similarity(&self, other: &Self) -> Result<u32> {
+        Ok(self.compare(other)?.max(0) as u32 * 100 / 128)
+    }
+}
+
+/// Serializes as the hex digest
+#[cfg(feature = "serde")]
+impl serde::Serialize for Nilsimsa {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        match self.hash {
+            Some(ref hash) => serializer.serialize_str(hash),
+            None => Err(serde::ser::Error::custom(
+                "Nilsimsa must be finalized before serializing",
+            )),
+        }
+    }
+}
+
+/// Deserializes from a hex digest, rejecting strings that are not valid Nilsimsa digests
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Nilsimsa {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = Nilsimsa;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a Nilsimsa hex digest")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, s: &str) -> std::result::Result<Nilsimsa, E> {
+                s.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(Visitor)
+    }
+}
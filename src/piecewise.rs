@@ -0,0 +1,253 @@
+//! Piecewise hashing of large inputs, like disk images.
+//!
+//! A single digest of a large input only captures a few kilobytes worth of its content, so
+//! [`Piecewise`] splits the input into segments and computes a [`FuzzyHash`] of each, together
+//! with its offset and length. Segments are hashed as the data streams in, nothing is
+//! buffered. [`compare`] then reports which segments of one input match which segments of
+//! another.
+//!
+//! Segments are either of a fixed size, or content defined: they end where the rolling hash
+//! of the data hits a trigger value, so inserting or removing data only moves the boundaries
+//! around the edit and the following segments still line up.
+//!
+//! # Example
+//! ```
+//! use fuzzyhash::piecewise::{self, Piecewise, Segmentation};
+//!
+//! let image: Vec<u8> = (0..1u32 << 20)
+//!     .map(|i| (i.wrapping_mul(i >> 3) % 251 ^ i >> 9) as u8)
+//!     .collect();
+//! let mut edited = b"a new header".to_vec();
+//! edited.extend_from_slice(&image);
+//!
+//! let segmentation = Segmentation::content_defined(64 * 1024);
+//! let mut first = Piecewise::new(segmentation);
+//! first.update(&image[..100_000]).unwrap();
+//! first.update(&image[100_000..]).unwrap();
+//! first.finalize().unwrap();
+//!
+//! let mut second = Piecewise::new(segmentation);
+//! second.update(&edited).unwrap();
+//! second.finalize().unwrap();
+//!
+//! let matches = piecewise::compare(first.segments(), second.segments(), 90).unwrap();
+//! assert!(matches.len() >= first.segments().len() - 1);
+//! ```
+
+use super::{index::Index, roll::Roll, FuzzyHash, Result};
+use std::{fs::File, io::Read, path::Path};
+
+/// How an input is split into segments
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Segmentation {
+    /// Segments of this many bytes, the last one may be shorter
+    Fixed(u64),
+    /// Segments end where the rolling hash modulo `average` hits its trigger value, but are
+    /// at least `min` and at most `max` bytes long
+    ContentDefined {
+        /// Expected length of a segment
+        average: u32,
+        /// Shortest segment, except for the last one
+        min: u64,
+        /// Longest segment
+        max: u64,
+    },
+}
+
+impl Segmentation {
+    /// Content defined segments of `average` bytes on average, from a quarter to four times
+    /// that long
+    pub fn content_defined(average: u32) -> Self {
+        Segmentation::ContentDefined {
+            average,
+            min: average as u64 / 4,
+            max: average as u64 * 4,
+        }
+    }
+}
+
+/// The digest of a segment of the input
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Segment {
+    /// Offset of the segment in the input
+    pub offset: u64,
+    /// Length of the segment
+    pub len: u64,
+    /// Fuzzy hash of the segment
+    pub hash: FuzzyHash,
+}
+
+/// A pair of matching segments found by [`compare`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SegmentMatch {
+    /// Position of the segment in the first input's segments
+    pub first: usize,
+    /// Position of the segment in the second input's segments
+    pub second: usize,
+    /// Similarity of the two segments
+    pub score: u32,
+}
+
+/// Splits a stream into segments and hashes each of them
+pub struct Piecewise {
+    segmentation: Segmentation,
+    roll: Roll,
+    current: FuzzyHash,
+    /// Offset and length of the current segment
+    offset: u64,
+    len: u64,
+    segments: Vec<Segment>,
+    finalized: bool,
+}
+
+impl Piecewise {
+    /// Build an empty piecewise hasher
+    ///
+    /// # Panics
+    /// If fixed segments are empty, or content defined segments have an average of 0 or a
+    /// maximum length of 0 or below their minimum
+    pub fn new(segmentation: Segmentation) -> Self {
+        match segmentation {
+            Segmentation::Fixed(size) => assert!(size > 0, "segments must not be empty"),
+            Segmentation::ContentDefined { average, min, max } => assert!(
+                average > 0 && max > 0 && min <= max,
+                "invalid content defined segmentation"
+            ),
+        }
+        Self {
+            segmentation,
+            roll: Roll::new(),
+            current: FuzzyHash::default(),
+            offset: 0,
+            len: 0,
+            segments: Vec::new(),
+            finalized: false,
+        }
+    }
+
+    /// Hash everything `reader` produces
+    pub fn read<R: Read>(reader: &mut R, segmentation: Segmentation) -> Result<Vec<Segment>> {
+        let mut this = Self::new(segmentation);
+        let mut buffer = vec![0; 64 * 1024];
+        loop {
+            match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(count) => this.update(&buffer[..count])?,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        this.finalize()?;
+        Ok(this.into_segments())
+    }
+
+    /// Hash the file at `path`
+    pub fn file<P: AsRef<Path>>(path: P, segmentation: Segmentation) -> Result<Vec<Segment>> {
+        Self::read(&mut File::open(path)?, segmentation)
+    }
+
+    /// Add `input` to the hashed data, ignored once finalized. Fails if a completed segment
+    /// cannot be hashed.
+    pub fn update<S: AsRef<[u8]>>(&mut self, input: S) -> Result<()> {
+        if self.finalized {
+            return Ok(());
+        }
+        let mut input = input.as_ref();
+        while !input.is_empty() {
+            let (len, ends) = self.boundary(input);
+            self.current.update(&input[..len]);
+            self.len += len as u64;
+            input = &input[len..];
+            if ends {
+                self.end_segment()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Hash the last segment, which ends with the input
+    ///
+    /// Finalizing more than once is a no-op.
+    pub fn finalize(&mut self) -> Result<()> {
+        if !self.finalized && self.len > 0 {
+            self.end_segment()?;
+        }
+        self.finalized = true;
+        Ok(())
+    }
+
+    /// The segments completed so far, in order
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// The completed segments, in order
+    pub fn into_segments(self) -> Vec<Segment> {
+        self.segments
+    }
+
+    /// Number of bytes of `input` belonging to the current segment, and whether the segment
+    /// ends with them
+    fn boundary(&mut self, input: &[u8]) -> (usize, bool) {
+        match self.segmentation {
+            Segmentation::Fixed(size) => {
+                let rest = size - self.len;
+                if (input.len() as u64) < rest {
+                    (input.len(), false)
+                } else {
+                    (rest as usize, true)
+                }
+            }
+            Segmentation::ContentDefined { average, min, max } => {
+                for (i, byte) in input.iter().enumerate() {
+                    self.roll.hash(*byte);
+                    let len = self.len + i as u64 + 1;
+                    if len >= max || (len >= min && self.roll.sum() % average == average - 1) {
+                        return (i + 1, true);
+                    }
+                }
+                (input.len(), false)
+            }
+        }
+    }
+
+    fn end_segment(&mut self) -> Result<()> {
+        let mut hash = std::mem::take(&mut self.current);
+        hash.finalize()?;
+        self.segments.push(Segment {
+            offset: self.offset,
+            len: self.len,
+            hash,
+        });
+        self.offset += self.len;
+        self.len = 0;
+        Ok(())
+    }
+}
+
+/// Every pair of a segment of `first` and a segment of `second` scoring at least `threshold`,
+/// ordered by the first and then the second segment's position
+///
+/// Only segments sharing a block size and a substring are scored, see [`Index`].
+pub fn compare(first: &[Segment], second: &[Segment], threshold: u32) -> Result<Vec<SegmentMatch>> {
+    let mut index = Index::new();
+    for segment in second {
+        index.insert(segment.hash.clone())?;
+    }
+
+    let mut matches = Vec::new();
+    for (i, segment) in first.iter().enumerate() {
+        for found in index.query(&segment.hash, threshold)? {
+            matches.push(SegmentMatch {
+                first: i,
+                second: found.id,
+                score: found.score,
+            });
+        }
+    }
+    matches.sort_by_key(|m| (m.first, m.second));
+    Ok(matches)
+}
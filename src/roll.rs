@@ -1,5 +1,6 @@
 use super::constants;
 
+#[derive(Clone)]
 pub struct Roll {
     pub h1: u32,
     pub h2: u32,
@@ -9,7 +10,7 @@ pub struct Roll {
 }
 
 impl Roll {
-    pub fn sum(&mut self) -> u32 {
+    pub fn sum(&self) -> u32 {
         self.h3.wrapping_add(self.h1.wrapping_add(self.h2))
     }
 
@@ -18,9 +19,9 @@ impl Roll {
         self.h2 += constants::ROLLING_WINDOW as u32 * c as u32;
 
         self.h1 += c as u32;
-        self.h1 -= self.window[(self.n as usize % constants::ROLLING_WINDOW)] as u32;
+        self.h1 -= self.window[self.n as usize % constants::ROLLING_WINDOW] as u32;
 
-        self.window[(self.n as usize % constants::ROLLING_WINDOW)] = c;
+        self.window[self.n as usize % constants::ROLLING_WINDOW] = c;
         self.n += 1;
 
         self.h3 <<= 5;
@@ -0,0 +1,751 @@
+//! sdhash, similarity digests built from statistically improbable features.
+//!
+//! Every 64 byte window of the input is ranked by its entropy, and a window is selected as a
+//! feature when it has the lowest rank of at least 16 of the 64 overlapping neighbourhoods it
+//! belongs to. Windows with very low or very high entropy are never selected, since they are
+//! common in unrelated files. The SHA-1 hash of each feature sets 5 bits of a 256 byte Bloom
+//! filter, and a new filter is started once a filter holds 160 features.
+//!
+//! Because features do not depend on their offsets, a fragment of a file shares its filters'
+//! features with the whole file, so sdhash detects containment as well as resemblance. In
+//! block mode, see [`Sdhash::with_block_size`], every block of the input gets its own filter
+//! and features are selected within each block.
+//!
+//! Digests follow the layout of the reference `sdhash` text format, version 3, and
+//! [`Sdhash::compare`] computes the score of the reference tool. Feature selection is not the
+//! reference one: the reference tool ranks entropies with a table built from the frequency of
+//! each entropy in a training corpus, which is not reproduced here, while this implementation
+//! ranks windows by their entropy itself. The two select different features from the same
+//! data, so their filters cannot be scored against each other. Digests therefore start with
+//! their own magic, `fhsd` in stream mode and `fhsd-dd` in block mode, instead of the
+//! reference `sdbf` and `sdbf-dd`, e.g. `fhsd:03:0::4096:sha1:256:5:7ff:160:1:42:AAAA...`,
+//! and digests from the reference tool are rejected.
+//!
+//! Requires the `sdhash` feature.
+//!
+//! # Example
+//! ```
+//! use fuzzyhash::sdhash::Sdhash;
+//!
+//! let data: Vec<u8> = (0..64 * 1024u32)
+//!     .map(|i| (i.wrapping_mul(i >> 3) % 251 ^ i >> 9) as u8)
+//!     .collect();
+//!
+//! let mut whole = Sdhash::default();
+//! whole.update(&data[..10_000]);
+//! whole.update(&data[10_000..]);
+//! whole.finalize().unwrap();
+//!
+//! // a fragment is found inside the whole input
+//! let fragment = Sdhash::new(&data[20_000..30_000]).unwrap();
+//! assert!(fragment.compare(&whole).unwrap() > 50);
+//!
+//! let parsed: Sdhash = whole.digest().unwrap().parse().unwrap();
+//! assert_eq!(parsed.compare(&whole).unwrap(), 100);
+//! ```
+
+use super::{base64, digest::SimilarityDigest, error::Error, Result};
+use sha1_smol::Sha1;
+use std::{
+    collections::VecDeque,
+    convert::TryFrom,
+    fmt,
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
+
+/// Block size used by [`Mode::Block`] in the reference tool
+pub const DEFAULT_BLOCK_SIZE: usize = 16 * 1024;
+
+const WINDOW_SIZE: usize = 64;
+const POPULARITY_WINDOW: usize = 64;
+const POPULARITY_THRESHOLD: u8 = 16;
+const ENTROPY_SCALE: u64 = 1000;
+const ENTROPY_POWER: u32 = 10;
+const MIN_RANKED_ENTROPY: u64 = 100;
+const MAX_RANKED_ENTROPY: u64 = 990;
+
+const FILTER_SIZE: usize = 256;
+const FILTER_BITS: u32 = FILTER_SIZE as u32 * 8;
+const FILTER_MASK: u32 = FILTER_BITS - 1;
+const HASH_COUNT: usize = 5;
+const MAX_ELEMENTS: u32 = 160;
+const MAX_ELEMENTS_BLOCK: u32 = 192;
+const MIN_ELEMENTS: u32 = 16;
+const MIN_DATA_LENGTH: u64 = 512;
+const SCORE_CUTOFF: f64 = 0.3;
+
+const VERSION: &str = "03";
+const STREAM_MAGIC: &str = "fhsd";
+const BLOCK_MAGIC: &str = "fhsd-dd";
+
+/// How the input is divided into Bloom filters
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Mode {
+    /// Features are selected across the whole input, filling filters in turn
+    Stream,
+    /// Each block of this many bytes gets its own filter
+    Block(usize),
+}
+
+/// A Bloom filter and the number of features inserted into it
+#[derive(Clone)]
+struct Filter {
+    bits: [u8; FILTER_SIZE],
+    elements: u32,
+}
+
+impl Filter {
+    fn new() -> Self {
+        Self {
+            bits: [0; FILTER_SIZE],
+            elements: 0,
+        }
+    }
+
+    /// Insert a feature, returns false if all of its bits were already set
+    fn insert(&mut self, feature: &[u8]) -> bool {
+        let hash = Sha1::from(feature).digest().bytes();
+        let mut new = false;
+        for word in hash.chunks(4).take(HASH_COUNT) {
+            let bit = u32::from_le_bytes([word[0], word[1], word[2], word[3]]) & FILTER_MASK;
+            let (byte, mask) = ((bit >> 3) as usize, 1 << (bit & 7));
+            new |= self.bits[byte] & mask == 0;
+            self.bits[byte] |= mask;
+        }
+        if new {
+            self.elements += 1;
+        }
+        new
+    }
+
+    fn count_ones(&self) -> u32 {
+        self.bits.iter().map(|b| b.count_ones()).sum()
+    }
+
+    /// Score of two filters holding `elements` and `other_elements` features, from 0 to 1, as
+    /// computed by the reference tool: the bits both filters share, above a cut-off 30% of the
+    /// way from the bits expected to be shared by chance to the most they can share
+    fn score(&self, elements: u32, other: &Filter, other_elements: u32) -> f64 {
+        let max = self.count_ones().min(other.count_ones());
+        let min = expected_common_bits(elements, other_elements);
+        if max <= min {
+            return 0.0;
+        }
+        let cut_off = (SCORE_CUTOFF * (max - min) as f64 + min as f64) as u32;
+        let common: u32 = self
+            .bits
+            .iter()
+            .zip(other.bits.iter())
+            .map(|(a, b)| (a & b).count_ones())
+            .sum();
+        if common <= cut_off {
+            0.0
+        } else {
+            (common - cut_off) as f64 / (max - cut_off) as f64
+        }
+    }
+}
+
+/// Number of bits two filters holding `first` and `second` unrelated features are expected to
+/// have in common, `bf_match_est` of the reference tool
+fn expected_common_bits(first: u32, second: u32) -> u32 {
+    let m = FILTER_BITS as f64;
+    let unset = 1.0 - 1.0 / m;
+    let k = HASH_COUNT as f64;
+    (m * (1.0 - unset.powf(k * first as f64) - unset.powf(k * second as f64)
+        + unset.powf(k * (first + second) as f64)))
+    .round() as u32
+}
+
+/// Selects features from a stream of bytes, one position at a time
+#[derive(Clone)]
+struct Selector {
+    /// The last bytes seen, enough to cover every window whose score is not final
+    data: [u8; 2 * WINDOW_SIZE],
+    /// Byte counts of the current window and its scaled entropy
+    counts: [u8; 256],
+    entropy: u64,
+    /// Entropy contribution of each possible byte count
+    contributions: [u64; WINDOW_SIZE + 1],
+    /// Scores of the windows that can still gain popularity
+    scores: [u8; POPULARITY_WINDOW],
+    /// Ranked windows of the current neighbourhood with increasing ranks, the first has the
+    /// lowest rank and is the latest of those with that rank
+    minimums: VecDeque<(u64, u64)>,
+    len: u64,
+}
+
+impl Selector {
+    fn new() -> Self {
+        let mut contributions = [0; WINDOW_SIZE + 1];
+        for (count, contribution) in contributions.iter_mut().enumerate().skip(1) {
+            let p = count as f64 / WINDOW_SIZE as f64;
+            // entropy of a window is at most log2(64) = 6 bits per byte
+            *contribution =
+                (-p * p.log2() / 6.0 * (ENTROPY_SCALE << ENTROPY_POWER) as f64).round() as u64;
+        }
+        Self {
+            data: [0; 2 * WINDOW_SIZE],
+            counts: [0; 256],
+            entropy: 0,
+            contributions,
+            scores: [0; POPULARITY_WINDOW],
+            minimums: VecDeque::new(),
+            len: 0,
+        }
+    }
+
+    fn update<F: FnMut(&[u8])>(&mut self, byte: u8, found: &mut F) {
+        let i = self.len;
+        if i >= WINDOW_SIZE as u64 {
+            let old = self.data[(i - WINDOW_SIZE as u64) as usize % self.data.len()];
+            self.add(old, false);
+        }
+        self.data[i as usize % self.data.len()] = byte;
+        self.add(byte, true);
+        self.len += 1;
+
+        let position = match i.checked_sub(WINDOW_SIZE as u64 - 1) {
+            Some(position) => position,
+            None => return,
+        };
+        self.scores[position as usize % POPULARITY_WINDOW] = 0;
+        let rank = self.rank();
+        if rank > 0 {
+            while self.minimums.back().is_some_and(|(_, r)| *r >= rank) {
+                self.minimums.pop_back();
+            }
+            self.minimums.push_back((position, rank));
+        }
+
+        // the neighbourhood starting at `start` is complete, its lowest rank gains popularity
+        if let Some(start) = position.checked_sub(POPULARITY_WINDOW as u64 - 1) {
+            while self.minimums.front().is_some_and(|(p, _)| *p < start) {
+                self.minimums.pop_front();
+            }
+            if let Some((lowest, _)) = self.minimums.front() {
+                self.scores[*lowest as usize % POPULARITY_WINDOW] += 1;
+            }
+            // and no later neighbourhood includes `start`
+            self.select(start, found);
+        }
+    }
+
+    /// Select the windows whose scores were still pending when the input ended
+    fn finish<F: FnMut(&[u8])>(&mut self, found: &mut F) {
+        let last = match self.len.checked_sub(WINDOW_SIZE as u64) {
+            Some(last) => last,
+            None => return,
+        };
+        let first = (last + 1).saturating_sub(POPULARITY_WINDOW as u64 - 1);
+        for position in first..=last {
+            self.select(position, found);
+        }
+    }
+
+    fn select<F: FnMut(&[u8])>(&self, position: u64, found: &mut F) {
+        if self.scores[position as usize % POPULARITY_WINDOW] < POPULARITY_THRESHOLD {
+            return;
+        }
+        let mut feature = [0; WINDOW_SIZE];
+        for (offset, byte) in feature.iter_mut().enumerate() {
+            *byte = self.data[(position as usize + offset) % self.data.len()];
+        }
+        found(&feature);
+    }
+
+    fn add(&mut self, byte: u8, add: bool) {
+        let count = &mut self.counts[byte as usize];
+        self.entropy -= self.contributions[*count as usize];
+        if add {
+            *count += 1;
+        } else {
+            *count -= 1;
+        }
+        self.entropy += self.contributions[*count as usize];
+    }
+
+    /// Rank of the current window, 0 if it cannot be a feature
+    fn rank(&self) -> u64 {
+        let entropy = (self.entropy >> ENTROPY_POWER).min(ENTROPY_SCALE);
+        if (MIN_RANKED_ENTROPY..=MAX_RANKED_ENTROPY).contains(&entropy) {
+            entropy
+        } else {
+            0
+        }
+    }
+}
+
+/// Hashing state, dropped once the digest is computed
+#[derive(Clone)]
+struct State {
+    selector: Selector,
+    /// Bytes of the current block, in block mode
+    block_len: usize,
+}
+
+/// An sdhash digest, built by hashing data or parsed from its text format
+///
+/// As with [`FuzzyHash`](crate::FuzzyHash), equality and hashing use the digest string, which
+/// includes the name of the input, see [`Sdhash::set_name`].
+#[derive(Clone)]
+pub struct Sdhash {
+    mode: Mode,
+    parsed: bool,
+    name: String,
+    len: u64,
+    filters: Vec<Filter>,
+    state: Option<State>,
+    hash: Option<String>,
+}
+
+impl Default for Sdhash {
+    fn default() -> Self {
+        Self::with_mode(Mode::Stream)
+    }
+}
+
+impl Sdhash {
+    /// Hash `input` in stream mode
+    pub fn new<S: AsRef<[u8]>>(input: S) -> Result<Self> {
+        let mut this = Self::default();
+        this.update(input);
+        this.finalize()?;
+        Ok(this)
+    }
+
+    /// Build an empty hasher in block mode, giving each `block_size` bytes their own filter
+    ///
+    /// # Panics
+    /// If `block_size` is smaller than 512 bytes
+    pub fn with_block_size(block_size: usize) -> Self {
+        assert!(
+            block_size as u64 >= MIN_DATA_LENGTH,
+            "sdhash block size must be at least {} bytes",
+            MIN_DATA_LENGTH
+        );
+        Self::with_mode(Mode::Block(block_size))
+    }
+
+    fn with_mode(mode: Mode) -> Self {
+        Self {
+            mode,
+            parsed: false,
+            name: String::new(),
+            len: 0,
+            filters: vec![Filter::new()],
+            state: Some(State {
+                selector: Selector::new(),
+                block_len: 0,
+            }),
+            hash: None,
+        }
+    }
+
+    /// Whether features are selected across the whole input or block by block
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Name of the input, written into the digest
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Set the name of the input written into the digest, usually its file name
+    pub fn set_name<S: Into<String>>(&mut self, name: S) {
+        self.name = name.into();
+        if self.hash.is_some() {
+            self.hash = Some(self.format());
+        }
+    }
+
+    /// Number of bytes hashed
+    pub fn input_len(&self) -> u64 {
+        self.len
+    }
+
+    /// Number of Bloom filters in the digest
+    pub fn filter_count(&self) -> usize {
+        self.filters.len()
+    }
+
+    /// Add `input` to the hashed data, ignored once finalized or for parsed digests
+    pub fn update<S: AsRef<[u8]>>(&mut self, input: S) {
+        if self.hash.is_some() {
+            return;
+        }
+        let state = match self.state {
+            Some(ref mut state) => state,
+            None => return,
+        };
+        let filters = &mut self.filters;
+
+        for byte in input.as_ref() {
+            match self.mode {
+                Mode::Stream => state
+                    .selector
+                    .update(*byte, &mut |feature| insert(filters, feature, MAX_ELEMENTS)),
+                Mode::Block(block_size) => {
+                    if state.block_len == block_size {
+                        end_block(state, filters);
+                        filters.push(Filter::new());
+                    }
+                    state
+                        .selector
+                        .update(*byte, &mut |feature| insert_block(filters, feature));
+                    state.block_len += 1;
+                }
+            }
+        }
+        self.len += input.as_ref().len() as u64;
+    }
+
+    /// Compute the digest. Fails with [`Error::InsufficientData`] if fewer than 512 bytes were
+    /// hashed.
+    ///
+    /// Finalizing more than once is a no-op.
+    pub fn finalize(&mut self) -> Result<()> {
+        if self.hash.is_some() {
+            return Ok(());
+        }
+        let mut state = self.state.take().ok_or(Error::NotFinalized)?;
+        if self.len < MIN_DATA_LENGTH {
+            self.state = Some(state);
+            return Err(Error::InsufficientData);
+        }
+
+        match self.mode {
+            Mode::Stream => {
+                let filters = &mut self.filters;
+                state
+                    .selector
+                    .finish(&mut |feature| insert(filters, feature, MAX_ELEMENTS));
+            }
+            Mode::Block(_) => end_block(&mut state, &mut self.filters),
+        }
+        self.hash = Some(self.format());
+        Ok(())
+    }
+
+    /// The digest in text format
+    pub fn digest(&self) -> Result<&str> {
+        self.hash.as_deref().ok_or(Error::NotFinalized)
+    }
+
+    /// Whether this digest was parsed from a string rather than computed from data
+    pub fn is_parsed(&self) -> bool {
+        self.parsed
+    }
+
+    /// Similarity to `other` from 0 to 100, with the score of the reference tool
+    ///
+    /// Each filter of the digest with fewer filters, or with the smaller last filter if both
+    /// have as many, is scored against every filter of the other, and the best scores are
+    /// averaged. Filters with fewer than 16 features are ignored, and comparing a digest
+    /// without any larger filter fails with [`Error::InsufficientData`].
+    pub fn compare(&self, other: &Sdhash) -> Result<u32> {
+        if self.hash.is_none() || other.hash.is_none() {
+            return Err(Error::NotFinalized);
+        }
+        let last = |sdhash: &Sdhash| sdhash.filters.last().map_or(0, |f| f.elements);
+        let (first, second) =
+            if (self.filters.len(), last(self)) <= (other.filters.len(), last(other)) {
+                (self, other)
+            } else {
+                (other, self)
+            };
+
+        let candidates: Vec<&Filter> = second
+            .filters
+            .iter()
+            .filter(|f| f.elements >= MIN_ELEMENTS)
+            .collect();
+        let mut total = 0.0;
+        let mut scored = 0;
+        for filter in first.filters.iter().filter(|f| f.elements >= MIN_ELEMENTS) {
+            total += candidates
+                .iter()
+                .map(|candidate| filter.score(filter.elements, candidate, candidate.elements))
+                .fold(0.0, f64::max);
+            scored += 1;
+        }
+        if scored == 0 {
+            return Err(Error::InsufficientData);
+        }
+        Ok((100.0 * total / scored as f64).round() as u32)
+    }
+
+    fn format(&self) -> String {
+        let header = |magic: &str, max: u32| {
+            format!(
+                "{}:{}:{}:{}:{}:sha1:{}:{}:{:x}:{}:{}",
+                magic,
+                VERSION,
+                self.name.len(),
+                self.name,
+                self.len,
+                FILTER_SIZE,
+                HASH_COUNT,
+                FILTER_MASK,
+                max,
+                self.filters.len()
+            )
+        };
+        match self.mode {
+            Mode::Stream => {
+                let bits: Vec<u8> = self.filters.iter().flat_map(|f| f.bits).collect();
+                let last = self.filters.last().map_or(0, |f| f.elements);
+                format!(
+                    "{}:{}:{}",
+                    header(STREAM_MAGIC, MAX_ELEMENTS),
+                    last,
+                    base64::encode(&bits)
+                )
+            }
+            Mode::Block(block_size) => {
+                let mut digest =
+                    format!("{}:{}", header(BLOCK_MAGIC, MAX_ELEMENTS_BLOCK), block_size);
+                for filter in &self.filters {
+                    digest.push_str(&format!(
+                        ":{:02X}:{}",
+                        filter.elements,
+                        base64::encode(&filter.bits)
+                    ));
+                }
+                digest
+            }
+        }
+    }
+}
+
+/// Insert a feature in stream mode, starting a new filter when the last one is full
+fn insert(filters: &mut Vec<Filter>, feature: &[u8], max: u32) {
+    if filters.last().is_some_and(|f| f.elements >= max) {
+        filters.push(Filter::new());
+    }
+    if let Some(filter) = filters.last_mut() {
+        filter.insert(feature);
+    }
+}
+
+/// Insert a feature in block mode, ignoring features once the block's filter is full
+fn insert_block(filters: &mut [Filter], feature: &[u8]) {
+    if let Some(filter) = filters.last_mut() {
+        if filter.elements < MAX_ELEMENTS_BLOCK {
+            filter.insert(feature);
+        }
+    }
+}
+
+/// Select the remaining features of the current block and reset for the next one
+fn end_block(state: &mut State, filters: &mut [Filter]) {
+    state
+        .selector
+        .finish(&mut |feature| insert_block(filters, feature));
+    state.selector = Selector::new();
+    state.block_len = 0;
+}
+
+impl PartialEq for Sdhash {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash
+    }
+}
+
+impl Eq for Sdhash {}
+
+impl Hash for Sdhash {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.hash.hash(state);
+    }
+}
+
+impl fmt::Debug for Sdhash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Sdhash").field(&self.hash).finish()
+    }
+}
+
+impl fmt::Display for Sdhash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.hash.as_deref().unwrap_or(""))
+    }
+}
+
+/// Parse a digest in stream (`fhsd`) or block (`fhsd-dd`) mode. Digests of the reference tool,
+/// with the `sdbf` magic, are rejected. Only the parameters the reference tool uses are
+/// accepted: SHA-1, 256 byte filters and 5 hashes per feature. A trailing newline is ignored.
+impl FromStr for Sdhash {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim_end_matches(['\r', '\n']);
+        let (magic, rest) = s.split_once(':').ok_or(Error::MalformedInput)?;
+        let (version, rest) = rest.split_once(':').ok_or(Error::MalformedInput)?;
+        let (name_len, rest) = rest.split_once(':').ok_or(Error::MalformedInput)?;
+        let name_len: usize = name_len.parse().map_err(|_| Error::MalformedInput)?;
+        if version != VERSION || rest.len() <= name_len || !rest.is_char_boundary(name_len) {
+            return Err(Error::MalformedInput);
+        }
+        let name = &rest[..name_len];
+        let fields: Vec<&str> = match rest[name_len..].strip_prefix(':') {
+            Some(fields) => fields.split(':').collect(),
+            None => return Err(Error::MalformedInput),
+        };
+
+        let number = |i: usize| -> Result<u64> {
+            fields
+                .get(i)
+                .and_then(|field| field.parse().ok())
+                .ok_or(Error::MalformedInput)
+        };
+        let max = match magic {
+            STREAM_MAGIC => MAX_ELEMENTS,
+            BLOCK_MAGIC => MAX_ELEMENTS_BLOCK,
+            _ => return Err(Error::MalformedInput),
+        };
+        let parameters = (
+            fields.get(1),
+            number(2)?,
+            number(3)?,
+            fields.get(4),
+            number(5)?,
+        );
+        if parameters
+            != (
+                Some(&"sha1"),
+                FILTER_SIZE as u64,
+                HASH_COUNT as u64,
+                Some(&"7ff"),
+                max as u64,
+            )
+        {
+            return Err(Error::MalformedInput);
+        }
+        let len = number(0)?;
+        // counts, lengths and sizes come from the input, so they are converted and multiplied
+        // with checks rather than trusted
+        let count = usize::try_from(number(6)?).map_err(|_| Error::MalformedInput)?;
+        if count == 0 {
+            return Err(Error::MalformedInput);
+        }
+
+        let decode = |encoded: &str| -> Result<[u8; FILTER_SIZE]> {
+            let bits = base64::decode(encoded)?;
+            <[u8; FILTER_SIZE]>::try_from(bits.as_slice()).map_err(|_| Error::MalformedInput)
+        };
+        let (mode, filters) = if magic == STREAM_MAGIC {
+            let last = u32::try_from(number(7)?).map_err(|_| Error::MalformedInput)?;
+            let bits = base64::decode(fields.get(8).ok_or(Error::MalformedInput)?)?;
+            if fields.len() != 9 || count.checked_mul(FILTER_SIZE) != Some(bits.len()) || last > max
+            {
+                return Err(Error::MalformedInput);
+            }
+            let filters = bits
+                .chunks(FILTER_SIZE)
+                .enumerate()
+                .map(|(i, chunk)| Filter {
+                    bits: <[u8; FILTER_SIZE]>::try_from(chunk).expect("chunks are filter sized"),
+                    elements: if i + 1 == count { last } else { max },
+                })
+                .collect();
+            (Mode::Stream, filters)
+        } else {
+            let block_size = usize::try_from(number(7)?).map_err(|_| Error::MalformedInput)?;
+            let expected_fields = count.checked_mul(2).and_then(|n| n.checked_add(8));
+            if expected_fields != Some(fields.len()) || (block_size as u64) < MIN_DATA_LENGTH {
+                return Err(Error::MalformedInput);
+            }
+            let filters = fields[8..]
+                .chunks(2)
+                .map(|pair| {
+                    let elements =
+                        u32::from_str_radix(pair[0], 16).map_err(|_| Error::MalformedInput)?;
+                    if elements > max {
+                        return Err(Error::MalformedInput);
+                    }
+                    Ok(Filter {
+                        bits: decode(pair[1])?,
+                        elements,
+                    })
+                })
+                .collect::<Result<Vec<Filter>>>()?;
+            (Mode::Block(block_size), filters)
+        };
+
+        let mut this = Self {
+            mode,
+            parsed: true,
+            name: name.to_string(),
+            len,
+            filters,
+            state: None,
+            hash: None,
+        };
+        this.hash = Some(this.format());
+        Ok(this)
+    }
+}
+
+impl SimilarityDigest for Sdhash {
+    const NAME: &'static str = "sdhash";
+
+    fn update(&mut self, input: &[u8]) {
+        Sdhash::update(self, input)
+    }
+
+    fn finalize(&mut self) -> Result<()> {
+        Sdhash::finalize(self)
+    }
+
+    fn format(&self) -> Result<String> {
+        self.digest().map(str::to_string)
+    }
+
+    fn similarity(&self, other: &Self) -> Result<u32> {
+        self.compare(other)
+    }
+}
+
+/// Serializes as the digest in text format
+#[cfg(feature = "serde")]
+impl serde::Serialize for Sdhash {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        match self.hash {
+            Some(ref hash) => serializer.serialize_str(hash),
+            None => Err(serde::ser::Error::custom(
+                "sdhash must be finalized before serializing",
+            )),
+        }
+    }
+}
+
+/// Deserializes from a digest string, rejecting strings that are not valid sdhash digests
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Sdhash {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = Sdhash;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "an sdhash digest")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, s: &str) -> std::result::Result<Sdhash, E> {
+                s.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(Visitor)
+    }
+}
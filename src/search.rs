@@ -0,0 +1,128 @@
+//! Top-k nearest neighbour search.
+//!
+//! [`Search::top_k`] finds the `k` digests most similar to a query, in a plain slice of fuzzy
+//! hashes or in any of the crate's indexes. Before a candidate is scored, an upper bound on its
+//! score is derived from its block size and signature lengths: the edit distance between two
+//! signatures is at least the difference in their lengths. Candidates are scored in order of
+//! descending bound, and the search stops as soon as no remaining candidate can beat the
+//! current `k`-th result.
+//!
+//! # Example
+//! ```
+//! use fuzzyhash::{search::Search, FuzzyHash};
+//!
+//! let known: Vec<FuzzyHash> = vec![
+//!     "3:YKKGhR0tn:YRGRmn".parse().unwrap(),
+//!     "96:U57GjXnLt9co6pZwvLhJluvrszNgMFwO6MFG8SvkpjTWf:Hj3BeoEcNJ0TspgIG8SvkpjTg".parse().unwrap(),
+//! ];
+//! let query: FuzzyHash = "96:U57GjXnLt9co6pZwvLhJluvrs1eRTxYARdEallia:Hj3BeoEcNJ0TsI9xYeia3R"
+//!     .parse()
+//!     .unwrap();
+//!
+//! let nearest = known.top_k(&query, 10).unwrap();
+//! assert_eq!(nearest.len(), 1);
+//! assert_eq!((nearest[0].id, nearest[0].score), (1, 63));
+//! ```
+
+use super::{
+    compare::{self, Signatures},
+    index::{Index, Match},
+    FuzzyHash, Result,
+};
+use std::borrow::Borrow;
+
+#[cfg(feature = "mmap")]
+use super::index::disk::DiskIndex;
+
+/// Collections of fuzzy hashes which can be searched for the digests most similar to a query
+pub trait Search {
+    /// The `k` digests most similar to `hash`, sorted by descending score and then by ascending
+    /// identifier, so ties always resolve the same way. Digests scoring 0 are never returned.
+    ///
+    /// # Arguments
+    /// * `hash` - finalized fuzzy hash to search for
+    /// * `k` - maximum number of results
+    fn top_k(&self, hash: &FuzzyHash, k: usize) -> Result<Vec<Match>>;
+}
+
+/// Identifiers are positions in the slice. Digests which are not finalized never match.
+impl Search for [FuzzyHash] {
+    fn top_k(&self, hash: &FuzzyHash, k: usize) -> Result<Vec<Match>> {
+        let signatures = Signatures::parse(hash.digest()?)?;
+        let candidates = self.iter().enumerate().filter_map(|(id, other)| {
+            let other = Signatures::parse(other.digest().ok()?).ok()?;
+            if compare::block_sizes_compatible(signatures.block_size, other.block_size) {
+                Some((id, other))
+            } else {
+                None
+            }
+        });
+        Ok(select(&signatures, candidates, k))
+    }
+}
+
+impl Search for Vec<FuzzyHash> {
+    fn top_k(&self, hash: &FuzzyHash, k: usize) -> Result<Vec<Match>> {
+        self.as_slice().top_k(hash, k)
+    }
+}
+
+impl Search for Index {
+    fn top_k(&self, hash: &FuzzyHash, k: usize) -> Result<Vec<Match>> {
+        Index::top_k(self, hash, k)
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl Search for DiskIndex {
+    fn top_k(&self, hash: &FuzzyHash, k: usize) -> Result<Vec<Match>> {
+        DiskIndex::top_k(self, hash, k)
+    }
+}
+
+/// The `k` best matches for `query` among `candidates`, scoring as few of them as possible
+pub(crate) fn select<S, I>(query: &Signatures, candidates: I, k: usize) -> Vec<Match>
+where
+    S: Borrow<Signatures>,
+    I: IntoIterator<Item = (usize, S)>,
+{
+    if k == 0 {
+        return Vec::new();
+    }
+
+    let mut bounded: Vec<(u32, usize, S)> = candidates
+        .into_iter()
+        .filter_map(|(id, other)| {
+            let bound = compare::score_upper_bound(query, other.borrow());
+            if bound > 0 {
+                Some((bound, id, other))
+            } else {
+                None
+            }
+        })
+        .collect();
+    bounded.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+
+    let mut best: Vec<Match> = Vec::with_capacity(k + 1);
+    for (bound, id, other) in bounded {
+        // bounds only decrease from here on and the k-th result only improves
+        if best.len() == k {
+            let last = best[k - 1];
+            if bound < last.score || (bound == last.score && id > last.id) {
+                break;
+            }
+        }
+
+        let score = compare::compare_signatures(query, other.borrow()).unwrap_or(0);
+        if score == 0 {
+            continue;
+        }
+        let found = Match { id, score };
+        let position = best
+            .binary_search_by(|m| found.score.cmp(&m.score).then(m.id.cmp(&found.id)))
+            .unwrap_or_else(|position| position);
+        best.insert(position, found);
+        best.truncate(k);
+    }
+    best
+}
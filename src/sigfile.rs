@@ -0,0 +1,249 @@
+//! Reading and writing ssdeep signature files.
+//!
+//! `ssdeep` writes a header line followed by one line per hashed file:
+//!
+//! ```text
+//! ssdeep,1.1--blocksize:hash:hash,filename
+//! 96:U57GjXnLt9co6pZwvLhJluvrszNgMFwO6MFG8SvkpjTWf:Hj3BeoEcNJ0TspgIG8SvkpjTg,"/tmp/a.exe"
+//! ```
+//!
+//! File names are quoted, and quotes within them are escaped with a backslash. Other
+//! characters, including backslashes in Windows paths, are written as they are. File names are
+//! kept as bytes, since ssdeep writes paths which are not valid UTF-8 as they are, and a file
+//! name containing a line break cannot be written. The older
+//! `ssdeep,1.0` header is accepted too. A file read by [`Reader`] and written back by a
+//! [`Writer`] created with the same [`Header`] is identical, byte for byte, as long as each of
+//! its lines is terminated the same way as the header.
+//!
+//! # Example
+//! ```
+//! use fuzzyhash::sigfile::{Header, Reader, Writer};
+//!
+//! let mut writer = Writer::new(Vec::new(), Header::default()).unwrap();
+//! writer
+//!     .write(&"3:YKKGhR0tn:YRGRmn".parse().unwrap(), "say \"hi\".txt")
+//!     .unwrap();
+//! let bytes = writer.into_inner();
+//! assert_eq!(
+//!     String::from_utf8_lossy(&bytes),
+//!     "ssdeep,1.1--blocksize:hash:hash,filename\n3:YKKGhR0tn:YRGRmn,\"say \\\"hi\\\".txt\"\n"
+//! );
+//!
+//! let entries: Vec<_> = Reader::new(bytes.as_slice())
+//!     .unwrap()
+//!     .collect::<Result<_, _>>()
+//!     .unwrap();
+//! assert_eq!(entries[0].filename, b"say \"hi\".txt");
+//! ```
+
+use super::{error::Error, FuzzyHash, Result};
+use std::io::{BufRead, Write};
+
+const HEADER_1_0: &str = "ssdeep,1.0--blocksize:hash:hash,filename";
+const HEADER_1_1: &str = "ssdeep,1.1--blocksize:hash:hash,filename";
+
+/// Version of the signature file format, as named in its header
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Version {
+    /// `ssdeep,1.0`, written by ssdeep 1.x
+    V1_0,
+    /// `ssdeep,1.1`, written by current ssdeep releases
+    #[default]
+    V1_1,
+}
+
+/// The layout of a signature file: its format version and line terminator
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Header {
+    /// Format version named in the header line
+    pub version: Version,
+    /// Whether lines end with `\r\n` rather than `\n`, as ssdeep writes them on Windows
+    pub crlf: bool,
+}
+
+/// A hashed file listed in a signature file
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Entry {
+    /// Fuzzy hash of the file
+    pub hash: FuzzyHash,
+    /// Name of the file, unquoted and unescaped, as the bytes written in the file
+    pub filename: Vec<u8>,
+}
+
+/// Streams the entries of a signature file
+pub struct Reader<R> {
+    inner: R,
+    header: Header,
+    line: usize,
+    done: bool,
+}
+
+impl<R: BufRead> Reader<R> {
+    /// Read the header of a signature file, the entries are read by iterating over the reader
+    pub fn new(mut inner: R) -> Result<Self> {
+        let mut buffer = Vec::new();
+        inner.read_until(b'\n', &mut buffer)?;
+        let (line, crlf) = strip_terminator(&buffer);
+
+        let version = if line == HEADER_1_1.as_bytes() {
+            Version::V1_1
+        } else if line == HEADER_1_0.as_bytes() {
+            Version::V1_0
+        } else {
+            return Err(Error::InvalidSignatureFile(
+                1,
+                "missing ssdeep header".to_string(),
+            ));
+        };
+
+        Ok(Self {
+            inner,
+            header: Header { version, crlf },
+            line: 1,
+            done: false,
+        })
+    }
+
+    /// The header read from the file
+    pub fn header(&self) -> Header {
+        self.header
+    }
+
+    /// Number of the last line read, starting at 1 for the header
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    fn read_entry(&mut self, buffer: &[u8]) -> Result<Entry> {
+        let invalid = |reason: &str| Error::InvalidSignatureFile(self.line, reason.to_string());
+
+        let (line, _) = strip_terminator(buffer);
+        let comma = line
+            .iter()
+            .position(|c| *c == b',')
+            .ok_or_else(|| invalid("expected digest,\"filename\""))?;
+        let (digest, quoted) = (&line[..comma], &line[comma + 1..]);
+
+        let digest =
+            std::str::from_utf8(digest).map_err(|_| invalid("digest is not valid UTF-8"))?;
+        let hash = digest.parse().map_err(|e: Error| invalid(&e.to_string()))?;
+        if quoted.len() < 2 || !quoted.starts_with(b"\"") || !quoted.ends_with(b"\"") {
+            return Err(invalid("file name is not quoted"));
+        }
+        let filename = unescape(&quoted[1..quoted.len() - 1])
+            .ok_or_else(|| invalid("unescaped quote in file name"))?;
+
+        Ok(Entry { hash, filename })
+    }
+}
+
+impl<R: BufRead> Iterator for Reader<R> {
+    type Item = Result<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let mut buffer = Vec::new();
+        match self.inner.read_until(b'\n', &mut buffer) {
+            Ok(0) => {
+                self.done = true;
+                None
+            }
+            Ok(_) => {
+                self.line += 1;
+                Some(self.read_entry(&buffer))
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e.into()))
+            }
+        }
+    }
+}
+
+/// Writes entries to a signature file
+pub struct Writer<W: Write> {
+    inner: W,
+    terminator: &'static str,
+}
+
+impl<W: Write> Writer<W> {
+    /// Write the header line described by `header`
+    pub fn new(mut inner: W, header: Header) -> Result<Self> {
+        let terminator = if header.crlf { "\r\n" } else { "\n" };
+        let line = match header.version {
+            Version::V1_0 => HEADER_1_0,
+            Version::V1_1 => HEADER_1_1,
+        };
+        write!(inner, "{}{}", line, terminator)?;
+        Ok(Self { inner, terminator })
+    }
+
+    /// Write the entry for a file named `filename` with the finalized fuzzy hash `hash`. Fails
+    /// if the name contains a line break, which would end the entry early.
+    pub fn write<N: AsRef<[u8]>>(&mut self, hash: &FuzzyHash, filename: N) -> Result<()> {
+        let filename = filename.as_ref();
+        if filename.contains(&b'\n') {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "file name contains a line break",
+            )));
+        }
+
+        let mut line = Vec::with_capacity(filename.len() + 160);
+        line.extend_from_slice(hash.digest()?.as_bytes());
+        line.extend_from_slice(b",\"");
+        for c in filename {
+            if *c == b'"' {
+                line.push(b'\\');
+            }
+            line.push(*c);
+        }
+        line.push(b'"');
+        line.extend_from_slice(self.terminator.as_bytes());
+        self.inner.write_all(&line)?;
+        Ok(())
+    }
+
+    /// Flush the underlying writer
+    pub fn flush(&mut self) -> Result<()> {
+        Ok(self.inner.flush()?)
+    }
+
+    /// Unwrap the underlying writer
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+/// Split the line terminator from a line, returning whether it was `\r\n`
+fn strip_terminator(line: &[u8]) -> (&[u8], bool) {
+    if line.ends_with(b"\r\n") {
+        (&line[..line.len() - 2], true)
+    } else if line.ends_with(b"\n") {
+        (&line[..line.len() - 1], false)
+    } else {
+        (line, false)
+    }
+}
+
+/// Undo the escaping of quotes, every quote must be escaped
+fn unescape(escaped: &[u8]) -> Option<Vec<u8>> {
+    let mut filename = Vec::with_capacity(escaped.len());
+    let mut bytes = escaped.iter().peekable();
+    while let Some(c) = bytes.next() {
+        match c {
+            b'\\' if bytes.peek() == Some(&&b'"') => {
+                bytes.next();
+                filename.push(b'"');
+            }
+            b'"' => return None,
+            c => filename.push(*c),
+        }
+    }
+    Some(filename)
+}
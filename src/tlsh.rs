@@ -0,0 +1,527 @@
+//! TLSH, the Trend Micro locality sensitive hash.
+//!
+//! TLSH counts the Pearson hashes of byte triplets taken from a sliding window of 5 bytes into
+//! buckets, then encodes each bucket as its quartile. Unlike ssdeep, similar inputs always
+//! produce comparable digests, and small edits or reordered content change few buckets.
+//! Digests are compared with [`Tlsh::distance`], where 0 means identical and larger values
+//! mean less similar inputs; distances below roughly 100 usually indicate related content.
+//!
+//! The common variant uses 128 buckets and a 1 byte checksum, giving 72 character digests. 256
+//! buckets and a 3 byte checksum are also supported, see [`Tlsh::with_variant`].
+//!
+//! # Example
+//! ```
+//! use fuzzyhash::tlsh::Tlsh;
+//!
+//! let data: Vec<u8> = (0..1024u32).map(|i| (i * i % 251) as u8).collect();
+//! let mut tlsh = Tlsh::default();
+//! tlsh.update(&data[..512]);
+//! tlsh.update(&data[512..]);
+//! tlsh.finalize().unwrap();
+//!
+//! let digest = tlsh.digest().unwrap();
+//! assert!(digest.starts_with("T1"));
+//! assert_eq!(digest.len(), 72);
+//!
+//! let parsed: Tlsh = digest.parse().unwrap();
+//! assert_eq!(tlsh.distance(&parsed).unwrap(), 0);
+//! ```
+
+use super::{digest::SimilarityDigest, error::Error, Result};
+use std::{fmt, hash::Hash, str::FromStr};
+
+const WINDOW_SIZE: usize = 5;
+const MIN_DATA_LENGTH: u64 = 50;
+const MAX_CHECKSUM_LEN: usize = 3;
+const PREFIX: &str = "T1";
+const HEX_CHARS: &[u8; 16] = b"0123456789ABCDEF";
+
+/// Distance at and beyond which digests are considered to have nothing in common, for the
+/// 0 to 100 similarity of [`SimilarityDigest`]
+pub const UNRELATED_DISTANCE: u32 = 300;
+
+/// Pearson hashing permutation
+const PEARSON: [u8; 256] = [
+    1, 87, 49, 12, 176, 178, 102, 166, 121, 193, 6, 84, 249, 230, 44, 163, 14, 197, 213, 181, 161,
+    85, 218, 80, 64, 239, 24, 226, 236, 142, 38, 200, 110, 177, 104, 103, 141, 253, 255, 50, 77,
+    101, 81, 18, 45, 96, 31, 222, 25, 107, 190, 70, 86, 237, 240, 34, 72, 242, 20, 214, 244, 227,
+    149, 235, 97, 234, 57, 22, 60, 250, 82, 175, 208, 5, 127, 199, 111, 62, 135, 248, 174, 169,
+    211, 58, 66, 154, 106, 195, 245, 171, 17, 187, 182, 179, 0, 243, 132, 56, 148, 75, 128, 133,
+    158, 100, 130, 126, 91, 13, 153, 246, 216, 219, 119, 68, 223, 78, 83, 88, 201, 99, 122, 11, 92,
+    32, 136, 114, 52, 10, 138, 30, 48, 183, 156, 35, 61, 26, 143, 74, 251, 94, 129, 162, 63, 152,
+    170, 7, 115, 167, 241, 206, 3, 150, 55, 59, 151, 220, 90, 53, 23, 131, 125, 173, 15, 238, 79,
+    95, 89, 16, 105, 137, 225, 224, 217, 160, 37, 123, 118, 73, 2, 157, 46, 116, 9, 145, 134, 228,
+    207, 212, 202, 215, 69, 229, 27, 188, 67, 124, 168, 252, 42, 4, 29, 108, 21, 247, 19, 205, 39,
+    203, 233, 40, 186, 147, 198, 192, 155, 33, 164, 191, 98, 204, 165, 180, 117, 76, 140, 36, 210,
+    172, 41, 54, 159, 8, 185, 232, 113, 196, 231, 47, 146, 120, 51, 65, 28, 144, 254, 221, 93, 189,
+    194, 139, 112, 43, 71, 109, 184, 209,
+];
+
+/// Largest input length encoded by each length value, a logarithmic scale
+const LENGTH_BOUNDARIES: [u64; 170] = [
+    1, 2, 3, 5, 7, 11, 17, 25, 38, 57, 86, 129, 194, 291, 437, 656, 854, 1110, 1443, 1876, 2439,
+    3171, 3475, 3823, 4205, 4626, 5088, 5597, 6157, 6772, 7450, 8195, 9014, 9916, 10907, 11998,
+    13198, 14518, 15970, 17567, 19323, 21256, 23382, 25720, 28292, 31121, 34233, 37656, 41422,
+    45564, 50121, 55133, 60646, 66711, 73382, 80721, 88793, 97672, 107439, 118183, 130002, 143002,
+    157302, 173032, 190335, 209369, 230306, 253337, 278670, 306538, 337191, 370911, 408002, 448802,
+    493682, 543050, 597356, 657091, 722800, 795081, 874589, 962048, 1058252, 1164078, 1280486,
+    1408534, 1549388, 1704327, 1874759, 2062236, 2268459, 2495305, 2744836, 3019320, 3321252,
+    3653374, 4018711, 4420582, 4862641, 5348904, 5883795, 6472176, 7119394, 7831333, 8614467,
+    9475909, 10423501, 11465851, 12612437, 13873681, 15261050, 16787154, 18465870, 20312458,
+    22343706, 24578077, 27035886, 29739474, 32713425, 35984770, 39583245, 43541573, 47895730,
+    52685306, 57953837, 63749221, 70124148, 77136564, 84850228, 93335252, 102668779, 112935659,
+    124229227, 136652151, 150317384, 165349128, 181884040, 200072456, 220079703, 242087671,
+    266296456, 292926096, 322218735, 354440623, 389884688, 428873168, 471760495, 518936559,
+    570830240, 627913311, 690704607, 759775136, 835752671, 919327967, 1011260767, 1112386880,
+    1223623232, 1345985727, 1480584256, 1628642751, 1791507135, 1970657856, 2167723648, 2384496256,
+    2622945920, 2885240448, 3173764736, 3491141248, 3840255616, 4224281216,
+];
+
+/// Number of buckets encoded in the digest
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Buckets {
+    /// 128 buckets, the common variant
+    Compact,
+    /// 256 buckets, more precise but twice as long
+    Full,
+}
+
+impl Buckets {
+    fn count(self) -> usize {
+        match self {
+            Buckets::Compact => 128,
+            Buckets::Full => 256,
+        }
+    }
+}
+
+/// Length of the checksum encoded in the digest
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Checksum {
+    /// 1 byte, the common variant
+    Short,
+    /// 3 bytes, fewer collisions between different inputs
+    Long,
+}
+
+impl Checksum {
+    fn len(self) -> usize {
+        match self {
+            Checksum::Short => 1,
+            Checksum::Long => 3,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct State {
+    buckets: [u32; 256],
+    window: [u8; WINDOW_SIZE],
+    checksum: [u8; MAX_CHECKSUM_LEN],
+    len: u64,
+}
+
+/// The decoded fields of a digest
+#[derive(Clone)]
+struct Body {
+    checksum: Vec<u8>,
+    lvalue: u8,
+    q1_ratio: u8,
+    q2_ratio: u8,
+    code: Vec<u8>,
+}
+
+/// A TLSH digest, built by hashing data or parsed from its hex string
+///
+/// As with [`FuzzyHash`](crate::FuzzyHash), equality and hashing use the digest string.
+#[derive(Clone)]
+pub struct Tlsh {
+    buckets: Buckets,
+    checksum: Checksum,
+    state: Option<State>,
+    body: Option<Body>,
+    hash: Option<String>,
+}
+
+impl Default for Tlsh {
+    fn default() -> Self {
+        Self::with_variant(Buckets::Compact, Checksum::Short)
+    }
+}
+
+impl Tlsh {
+    /// Hash `input` with 128 buckets and a 1 byte checksum
+    pub fn new<S: AsRef<[u8]>>(input: S) -> Result<Self> {
+        let mut this = Self::default();
+        this.update(input);
+        this.finalize()?;
+        Ok(this)
+    }
+
+    /// Build an empty hasher for the given variant
+    pub fn with_variant(buckets: Buckets, checksum: Checksum) -> Self {
+        Self {
+            buckets,
+            checksum,
+            state: Some(State {
+                buckets: [0; 256],
+                window: [0; WINDOW_SIZE],
+                checksum: [0; MAX_CHECKSUM_LEN],
+                len: 0,
+            }),
+            body: None,
+            hash: None,
+        }
+    }
+
+    /// Number of buckets of this digest
+    pub fn buckets(&self) -> Buckets {
+        self.buckets
+    }
+
+    /// Checksum length of this digest
+    pub fn checksum(&self) -> Checksum {
+        self.checksum
+    }
+
+    /// Add chunk to the data source. Has no effect once finalized, or on a parsed digest.
+    pub fn update<S: AsRef<[u8]>>(&mut self, input: S) {
+        if self.hash.is_some() {
+            return;
+        }
+        let checksum_len = self.checksum.len();
+        let state = match self.state {
+            Some(ref mut state) => state,
+            None => return,
+        };
+
+        for byte in input.as_ref() {
+            let j = (state.len % WINDOW_SIZE as u64) as usize;
+            state.window[j] = *byte;
+
+            if state.len >= WINDOW_SIZE as u64 - 1 {
+                let back = |n: usize| state.window[(j + WINDOW_SIZE - n) % WINDOW_SIZE];
+                let (b0, b1, b2, b3, b4) = (back(0), back(1), back(2), back(3), back(4));
+
+                state.checksum[0] = pearson(0, b0, b1, state.checksum[0]);
+                for k in 1..checksum_len {
+                    state.checksum[k] = pearson(state.checksum[k - 1], b0, b1, state.checksum[k]);
+                }
+
+                for (salt, x, y) in &[
+                    (2, b1, b2),
+                    (3, b1, b3),
+                    (5, b2, b3),
+                    (7, b2, b4),
+                    (11, b1, b4),
+                    (13, b3, b4),
+                ] {
+                    state.buckets[pearson(*salt, b0, *x, *y) as usize] += 1;
+                }
+            }
+            state.len += 1;
+        }
+    }
+
+    /// Compute the digest. Fails with [`Error::InsufficientData`] if fewer than 50 bytes were
+    /// hashed, or if the data is too uniform to fill half of the buckets.
+    ///
+    /// Finalizing more than once is a no-op.
+    pub fn finalize(&mut self) -> Result<()> {
+        if self.hash.is_some() {
+            return Ok(());
+        }
+        let state = self.state.as_ref().ok_or(Error::NotFinalized)?;
+        if state.len < MIN_DATA_LENGTH {
+            return Err(Error::InsufficientData);
+        }
+
+        let count = self.buckets.count();
+        let buckets = &state.buckets[..count];
+        if buckets.iter().filter(|b| **b > 0).count() <= count / 2 {
+            return Err(Error::InsufficientData);
+        }
+
+        let mut sorted = buckets.to_vec();
+        sorted.sort_unstable();
+        let q1 = sorted[count / 4 - 1];
+        let q2 = sorted[count / 2 - 1];
+        let q3 = sorted[count - count / 4 - 1];
+
+        let code = buckets
+            .chunks(4)
+            .map(|chunk| {
+                chunk.iter().enumerate().fold(0u8, |h, (j, k)| {
+                    let quartile = if *k > q3 {
+                        3
+                    } else if *k > q2 {
+                        2
+                    } else if *k > q1 {
+                        1
+                    } else {
+                        0
+                    };
+                    h | quartile << (j * 2)
+                })
+            })
+            .collect();
+
+        let ratio = |q: u32| ((q as f32 * 100.0 / q3 as f32) as u32 % 16) as u8;
+        let body = Body {
+            checksum: state.checksum[..self.checksum.len()].to_vec(),
+            lvalue: length_value(state.len),
+            q1_ratio: ratio(q1),
+            q2_ratio: ratio(q2),
+            code,
+        };
+        self.hash = Some(format(&body));
+        self.body = Some(body);
+        Ok(())
+    }
+
+    /// The hex digest of a finalized hash, prefixed with `T1`
+    pub fn digest(&self) -> Result<&str> {
+        self.hash.as_deref().ok_or(Error::NotFinalized)
+    }
+
+    /// Whether this digest was parsed from a string rather than computed from data
+    pub fn is_parsed(&self) -> bool {
+        self.state.is_none()
+    }
+
+    /// The TLSH distance to `other`: 0 for identical digests, growing as the inputs differ.
+    /// Both digests must be finalized and use the same variant.
+    pub fn distance(&self, other: &Tlsh) -> Result<u32> {
+        let (first, second) = match (&self.body, &other.body) {
+            (Some(first), Some(second)) => (first, second),
+            _ => return Err(Error::NotFinalized),
+        };
+        if self.buckets != other.buckets || self.checksum != other.checksum {
+            return Err(Error::IncompatibleDigests);
+        }
+
+        let mut diff = match circular_diff(first.lvalue, second.lvalue, 256) {
+            0 => 0,
+            1 => 1,
+            d => d * 12,
+        };
+        for (q, r) in &[
+            (first.q1_ratio, second.q1_ratio),
+            (first.q2_ratio, second.q2_ratio),
+        ] {
+            diff += match circular_diff(*q, *r, 16) {
+                d if d <= 1 => d,
+                d => (d - 1) * 12,
+            };
+        }
+        if first.checksum != second.checksum {
+            diff += 1;
+        }
+        for (x, y) in first.code.iter().zip(&second.code) {
+            for shift in (0..8).step_by(2) {
+                diff += match ((x >> shift) & 3).max((y >> shift) & 3)
+                    - ((x >> shift) & 3).min((y >> shift) & 3)
+                {
+                    3 => 6,
+                    d => u32::from(d),
+                };
+            }
+        }
+        Ok(diff)
+    }
+}
+
+/// Pearson hash of a salt and three bytes
+fn pearson(salt: u8, i: u8, j: u8, k: u8) -> u8 {
+    let mut h = PEARSON[salt as usize];
+    h = PEARSON[(h ^ i) as usize];
+    h = PEARSON[(h ^ j) as usize];
+    PEARSON[(h ^ k) as usize]
+}
+
+/// Logarithmic encoding of the input length
+fn length_value(len: u64) -> u8 {
+    LENGTH_BOUNDARIES
+        .iter()
+        .position(|boundary| len <= *boundary)
+        .unwrap_or(LENGTH_BOUNDARIES.len()) as u8
+}
+
+/// Distance between two values on a circle of size `range`
+fn circular_diff(x: u8, y: u8, range: u32) -> u32 {
+    let d = (i32::from(x) - i32::from(y)).unsigned_abs();
+    d.min(range - d)
+}
+
+fn swap_nibbles(byte: u8) -> u8 {
+    byte.rotate_left(4)
+}
+
+/// Encode the digest as the reference implementation does: nibble-swapped header bytes
+/// followed by the bucket codes in reverse order.
+///
+/// The reference keeps the quartile ratios in a bitfield byte with Q1 in the low nibble and
+/// swaps that byte like the others before encoding, so Q1 ends up in the first hex digit.
+fn format(body: &Body) -> String {
+    let mut bytes: Vec<u8> = body.checksum.iter().map(|c| swap_nibbles(*c)).collect();
+    bytes.push(swap_nibbles(body.lvalue));
+    bytes.push(swap_nibbles(body.q2_ratio << 4 | body.q1_ratio));
+    bytes.extend(body.code.iter().rev());
+
+    let mut hash = String::with_capacity(PREFIX.len() + bytes.len() * 2);
+    hash.push_str(PREFIX);
+    for byte in bytes {
+        hash.push(HEX_CHARS[(byte >> 4) as usize] as char);
+        hash.push(HEX_CHARS[(byte & 0xf) as usize] as char);
+    }
+    hash
+}
+
+impl PartialEq for Tlsh {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash
+    }
+}
+
+impl Eq for Tlsh {}
+
+impl Hash for Tlsh {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.hash.hash(state);
+    }
+}
+
+impl fmt::Debug for Tlsh {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Tlsh").field(&self.hash).finish()
+    }
+}
+
+impl fmt::Display for Tlsh {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.hash.as_deref().unwrap_or(""))
+    }
+}
+
+/// Parse a hex digest of any variant, with or without the `T1` prefix. Parsed digests are
+/// displayed with the prefix and upper case hex.
+///
+/// # Example
+/// ```
+/// use fuzzyhash::tlsh::Tlsh;
+///
+/// let digest = "T16A112421C210AE5126A0EA0CC8AF0C1F1E1F9A9312509946A671BB29A3C621EB742D63";
+/// let tlsh: Tlsh = digest.parse().unwrap();
+/// assert_eq!(tlsh.to_string(), digest);
+/// assert!("T1ABC".parse::<Tlsh>().is_err());
+/// ```
+impl FromStr for Tlsh {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let hex = s.strip_prefix(PREFIX).unwrap_or(s);
+        let (buckets, checksum) = match hex.len() {
+            70 => (Buckets::Compact, Checksum::Short),
+            74 => (Buckets::Compact, Checksum::Long),
+            134 => (Buckets::Full, Checksum::Short),
+            138 => (Buckets::Full, Checksum::Long),
+            _ => return Err(Error::MalformedInput),
+        };
+        let bytes = hex
+            .as_bytes()
+            .chunks(2)
+            .map(|pair| {
+                // from_str_radix also accepts a sign
+                if !pair.iter().all(u8::is_ascii_hexdigit) {
+                    return Err(Error::MalformedInput);
+                }
+                std::str::from_utf8(pair)
+                    .ok()
+                    .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                    .ok_or(Error::MalformedInput)
+            })
+            .collect::<Result<Vec<u8>>>()?;
+
+        let checksum_len = checksum.len();
+        let body = Body {
+            checksum: bytes[..checksum_len]
+                .iter()
+                .map(|c| swap_nibbles(*c))
+                .collect(),
+            lvalue: swap_nibbles(bytes[checksum_len]),
+            q1_ratio: swap_nibbles(bytes[checksum_len + 1]) & 0xf,
+            q2_ratio: swap_nibbles(bytes[checksum_len + 1]) >> 4,
+            code: bytes[checksum_len + 2..].iter().rev().copied().collect(),
+        };
+
+        Ok(Self {
+            buckets,
+            checksum,
+            state: None,
+            hash: Some(format(&body)),
+            body: Some(body),
+        })
+    }
+}
+
+/// Similarities map distances from 0 to [`UNRELATED_DISTANCE`] linearly onto 100 to 0
+impl SimilarityDigest for Tlsh {
+    const NAME: &'static str = "tlsh";
+
+    fn update(&mut self, input: &[u8]) {
+        Tlsh::update(self, input)
+    }
+
+    fn finalize(&mut self) -> Result<()> {
+        Tlsh::finalize(self)
+    }
+
+    fn format(&self) -> Result<String> {
+        self.digest().map(str::to_string)
+    }
+
+    fn similarity(&self, other: &Self) -> Result<u32> {
+        let distance = self.distance(other)?.min(UNRELATED_DISTANCE);
+        Ok(100 - distance * 100 / UNRELATED_DISTANCE)
+    }
+}
+
+/// Serializes as the hex digest, e.g. `"T1A4C0..."`
+#[cfg(feature = "serde")]
+impl serde::Serialize for Tlsh {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        match self.hash {
+            Some(ref hash) => serializer.serialize_str(hash),
+            None => Err(serde::ser::Error::custom(
+                "TLSH must be finalized before serializing",
+            )),
+        }
+    }
+}
+
+/// Deserializes from a hex digest, rejecting strings that are not valid TLSH digests
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Tlsh {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = Tlsh;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a TLSH hex digest")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, s: &str) -> std::result::Result<Tlsh, E> {
+                s.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(Visitor)
+    }
+}
@@ -0,0 +1,62 @@
+use fuzzyhash::FuzzyHash;
+
+#[test]
+fn accessors_of_hashed_data() {
+    let fuzzy_hash = FuzzyHash::file("./tests/test_data.bin").unwrap();
+
+    assert_eq!(fuzzy_hash.block_size().unwrap(), 192);
+    assert_eq!(
+        fuzzy_hash.signature1().unwrap(),
+        "tEIFoBn+SbDjIZ6MUpH6rDjHPanaVGLGOvkdGep"
+    );
+    assert_eq!(fuzzy_hash.signature2().unwrap(), "tEIeBrbDjRAvDVEGOMGQ");
+    assert_eq!(
+        fuzzy_hash.input_len(),
+        Some(std::fs::metadata("./tests/test_data.bin").unwrap().len())
+    );
+    assert_eq!(
+        fuzzy_hash.compatible_block_sizes().unwrap(),
+        vec![96, 192, 384]
+    );
+}
+
+#[test]
+fn accessors_of_parsed_digest() {
+    let fuzzy_hash: FuzzyHash = "3:AAAAAAAAAB:BBBBC".parse().unwrap();
+
+    assert_eq!(fuzzy_hash.input_len(), None);
+    assert_eq!(fuzzy_hash.normalized_signature1().unwrap(), "AAAB");
+    assert_eq!(fuzzy_hash.normalized_signature2().unwrap(), "BBBC");
+    assert_eq!(fuzzy_hash.compatible_block_sizes().unwrap(), vec![3, 6]);
+}
+
+#[test]
+fn accessors_require_finalized_hash() {
+    let mut fuzzy_hash = FuzzyHash::default();
+    fuzzy_hash.update("some data");
+
+    assert!(fuzzy_hash.block_size().is_err());
+    assert!(fuzzy_hash.signature1().is_err());
+    assert_eq!(fuzzy_hash.input_len(), Some(9));
+}
+
+#[test]
+fn sequences_are_eliminated_before_comparing() {
+    assert_eq!(
+        FuzzyHash::compare("3:AAAAAAAAAABCDEFGH:", "3:AAABCDEFGH:").unwrap(),
+        100
+    );
+}
+
+#[test]
+fn characters_after_a_run_are_kept() {
+    // dropping the B after the run of A used to lower this score to 91
+    assert_eq!(
+        FuzzyHash::compare(
+            "48:AAAAAAAAAABCDEFGHIJKLMNOPQRST:",
+            "48:AAABCDEFGHIJKLMNOPQ:"
+        )
+        .unwrap(),
+        94
+    );
+}
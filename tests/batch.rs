@@ -0,0 +1,63 @@
+#![cfg(feature = "rayon")]
+
+mod common;
+
+use fuzzyhash::{batch, FuzzyHash};
+use rayon::prelude::*;
+
+fn sorted(mut pairs: Vec<batch::PairScore>) -> Vec<batch::PairScore> {
+    pairs.sort_by_key(|p| (p.first, p.second));
+    pairs
+}
+
+#[test]
+fn compare_many_matches_brute_force() {
+    let hashes = common::corpus();
+    let (first, second) = hashes.split_at(hashes.len() / 2);
+
+    for threshold in &[0, 1, 50] {
+        let mut expected = Vec::new();
+        for (i, hash) in first.iter().enumerate() {
+            for (j, score) in common::brute_force(hash, second) {
+                if score >= *threshold {
+                    expected.push(batch::PairScore {
+                        first: i,
+                        second: j,
+                        score,
+                    });
+                }
+            }
+        }
+        let expected = sorted(expected);
+
+        let pairs = sorted(
+            batch::compare_many(first, second, *threshold)
+                .unwrap()
+                .collect(),
+        );
+        assert_eq!(pairs, expected, "threshold {}", threshold);
+    }
+}
+
+#[test]
+fn compare_many_self() {
+    let hashes = common::corpus();
+    let pairs: Vec<batch::PairScore> = batch::compare_many(&hashes, &hashes, 100)
+        .unwrap()
+        .collect();
+    for i in 0..hashes.len() {
+        assert!(pairs.contains(&batch::PairScore {
+            first: i,
+            second: i,
+            score: 100
+        }));
+    }
+}
+
+#[test]
+fn compare_many_errors() {
+    let hashes = common::corpus();
+    assert!(batch::compare_many(&hashes, &[FuzzyHash::default()], 0).is_err());
+    assert!(batch::compare_many(&[FuzzyHash::default()], &hashes, 0).is_err());
+    assert_eq!(batch::compare_many(&[], &hashes, 0).unwrap().count(), 0);
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use fuzzyhash::{error::Error, Chunk, FuzzyHash};
+
+const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn tracked(data: &[u8]) -> FuzzyHash {
+    let mut fuzzy_hash = FuzzyHash::tracking_chunks();
+    for chunk in data.chunks(1_000) {
+        fuzzy_hash.update(chunk);
+    }
+    fuzzy_hash.finalize().unwrap();
+    fuzzy_hash
+}
+
+/// Chunks follow each other from the start of the input, and each character is the hash of
+/// its chunk's bytes
+fn check_chunks(data: &[u8], chunks: &[Chunk]) {
+    let mut offset = 0;
+    for chunk in chunks {
+        assert_eq!(chunk.offset, offset);
+        offset += chunk.len;
+        let bytes = &data[chunk.offset as usize..offset as usize];
+        let h = bytes.iter().fold(0x2802_1967u32, |h, byte| {
+            h.wrapping_mul(0x0100_0193) ^ u32::from(*byte)
+        });
+        assert_eq!(chunk.character, BASE64[(h % 64) as usize] as char);
+    }
+    assert!(offset <= data.len() as u64);
+}
+
+fn characters(chunks: &[Chunk]) -> String {
+    chunks.iter().map(|chunk| chunk.character).collect()
+}
+
+#[test]
+fn chunks_match_signatures() {
+    // tiny inputs only have one block size, and an empty second signature
+    let mut inputs: Vec<Vec<u8>> = (1..=6).map(|len| b"abcdef"[..len].to_vec()).collect();
+    inputs.extend(vec![
+        b"this is our test data!".to_vec(),
+        std::fs::read("./tests/test_data.bin").unwrap(),
+        common::random_data(40, 300),
+        common::random_data(41, 70_000),
+        common::random_data(42, 1_000_000),
+    ]);
+    // an input ending in zeros leaves the rolling hash empty
+    let mut zeros = common::random_data(43, 20_000);
+    zeros.extend_from_slice(&[0; 16]);
+    inputs.push(zeros);
+
+    for data in &inputs {
+        let fuzzy_hash = tracked(data);
+        assert_eq!(fuzzy_hash, FuzzyHash::new(data).unwrap());
+
+        let chunks1 = fuzzy_hash.chunks1().unwrap();
+        let chunks2 = fuzzy_hash.chunks2().unwrap();
+        assert_eq!(characters(&chunks1), fuzzy_hash.signature1().unwrap());
+        assert_eq!(characters(&chunks2), fuzzy_hash.signature2().unwrap());
+        check_chunks(data, &chunks1);
+        check_chunks(data, &chunks2);
+
+        let all = fuzzy_hash.all_chunks().unwrap();
+        let block_size = fuzzy_hash.block_size().unwrap();
+        let (_, at_block_size) = all.iter().find(|(size, _)| *size == block_size).unwrap();
+        assert_eq!(at_block_size, &chunks1);
+        for (_, chunks) in &all {
+            check_chunks(data, chunks);
+            assert!(chunks.len() <= 64);
+        }
+    }
+}
+
+#[test]
+fn chunks_cover_the_input() {
+    let data = common::random_data(44, 50_000);
+    let chunks = tracked(&data).chunks1().unwrap();
+    let last = chunks.last().unwrap();
+    assert_eq!(last.offset + last.len, 50_000);
+}
+
+#[test]
+fn chunks_errors() {
+    let mut fuzzy_hash = FuzzyHash::tracking_chunks();
+    fuzzy_hash.update("this is our test data!");
+    match fuzzy_hash.chunks1() {
+        Err(Error::NotFinalized) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    for untracked in &[
+        FuzzyHash::new("this is our test data!").unwrap(),
+        "3:YKKGhR0tn:YRGRmn".parse().unwrap(),
+    ] {
+        match untracked.chunks2() {
+            Err(Error::NotTracked) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert!(untracked.all_chunks().is_err());
+    }
+}
@@ -0,0 +1,237 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use fuzzyhash::{sigfile::Reader, FuzzyHash};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+};
+
+fn temporary_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("fuzzyhash-cli-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::write(dir.join("a.bin"), common::random_data(1, 5_000)).unwrap();
+    std::fs::write(
+        dir.join("sub").join("q\"uote.bin"),
+        common::random_data(2, 9_000),
+    )
+    .unwrap();
+    dir
+}
+
+fn run(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_fuzzyhash"))
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn entries(output: &Output) -> Vec<(String, String)> {
+    Reader::new(output.stdout.as_slice())
+        .unwrap()
+        .map(|entry| {
+            let entry = entry.unwrap();
+            let filename = String::from_utf8(entry.filename).unwrap();
+            (entry.hash.to_string(), filename)
+        })
+        .collect()
+}
+
+fn digest(path: &Path) -> String {
+    FuzzyHash::file(path).unwrap().to_string()
+}
+
+#[test]
+fn cli_hashes_directories() {
+    let dir = temporary_dir("recursive");
+    let a = dir.join("a.bin");
+    let quote = dir.join("sub").join("q\"uote.bin");
+
+    let output = run(&dir, &["-r", "."]);
+    assert!(output.status.success());
+    assert_eq!(
+        entries(&output),
+        vec![
+            (digest(&a), a.canonicalize().unwrap().display().to_string()),
+            (
+                digest(&quote),
+                quote.canonicalize().unwrap().display().to_string()
+            ),
+        ]
+    );
+
+    let output = run(&dir, &["-rl", "sub", "a.bin"]);
+    assert_eq!(
+        entries(&output),
+        vec![
+            (
+                digest(&quote),
+                format!("sub{}q\"uote.bin", std::path::MAIN_SEPARATOR)
+            ),
+            (digest(&a), "a.bin".to_string()),
+        ]
+    );
+
+    let output = run(&dir, &["-rb", "."]);
+    let names: Vec<String> = entries(&output).into_iter().map(|(_, name)| name).collect();
+    assert_eq!(names, vec!["a.bin", "q\"uote.bin"]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn cli_reports_errors_and_continues() {
+    let dir = temporary_dir("errors");
+
+    let output = run(&dir, &["-l", "missing", "sub", "a.bin"]);
+    assert!(!output.status.success());
+    assert_eq!(
+        entries(&output),
+        vec![(digest(&dir.join("a.bin")), "a.bin".to_string())]
+    );
+    let errors = String::from_utf8(output.stderr).unwrap();
+    assert!(errors.contains("fuzzyhash: missing: "));
+    assert!(errors.contains("fuzzyhash: sub: Is a directory"));
+
+    let output = run(&dir, &["-s", "missing"]);
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(output.stderr.is_empty());
+
+    let output = run(&dir, &["-q"]);
+    assert!(!output.status.success());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn cli_hashes_stdin() {
+    let data = common::random_data(3, 4_000);
+    let mut child = Command::new(env!("CARGO_BIN_EXE_fuzzyhash"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(&data).unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        entries(&output),
+        vec![(
+            FuzzyHash::new(&data).unwrap().to_string(),
+            "stdin".to_string()
+        )]
+    );
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn cli_matching_modes() {
+    let dir = temporary_dir("modes");
+    let a = common::random_data(1, 5_000);
+    std::fs::write(dir.join("variant.bin"), common::mutate(&a, 5, 2)).unwrap();
+    let score = FuzzyHash::new(&a)
+        .unwrap()
+        .compare_to(&FuzzyHash::file(dir.join("variant.bin")).unwrap())
+        .unwrap();
+    assert!(score > 0 && score < 100);
+
+    let known = run(&dir, &["-l", "a.bin"]);
+    std::fs::write(dir.join("known.txt"), &known.stdout).unwrap();
+
+    let output = run(&dir, &["-l", "-m", "known.txt", "variant.bin", "a.bin"]);
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "variant.bin matches known.txt:a.bin ({})\na.bin matches known.txt:a.bin (100)\n",
+            score
+        )
+    );
+
+    let threshold = score.to_string();
+    let output = run(
+        &dir,
+        &["-l", "-t", &threshold, "-mknown.txt", "variant.bin"],
+    );
+    assert_eq!(stdout(&output), "");
+
+    let output = run(&dir, &["-lrd", "."]);
+    let sub = format!(
+        ".{}sub{}q\"uote.bin",
+        std::path::MAIN_SEPARATOR,
+        std::path::MAIN_SEPARATOR
+    );
+    let a_name = format!(".{}a.bin", std::path::MAIN_SEPARATOR);
+    let variant_name = format!(".{}variant.bin", std::path::MAIN_SEPARATOR);
+    assert_eq!(
+        stdout(&output),
+        format!("{} matches {} ({})\n", variant_name, a_name, score)
+    );
+
+    let output = run(&dir, &["-la", "-d", "a.bin", "sub/q\"uote.bin"]);
+    assert_eq!(stdout(&output), "sub/q\"uote.bin matches a.bin (0)\n");
+
+    let output = run(&dir, &["-lp", "a.bin", "variant.bin", "sub/q\"uote.bin"]);
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "a.bin matches variant.bin ({score})\n\nvariant.bin matches a.bin ({score})\n\n",
+            score = score
+        )
+    );
+
+    let output = run(&dir, &["-lrg", "."]);
+    assert_eq!(
+        stdout(&output),
+        format!("** Cluster size 2\n{}\n{}\n\n", a_name, variant_name)
+    );
+    assert!(!stdout(&output).contains(&sub));
+
+    let signatures = run(&dir, &["-l", "variant.bin"]);
+    std::fs::write(dir.join("signatures.txt"), &signatures.stdout).unwrap();
+    let output = run(&dir, &["-k", "known.txt", "signatures.txt"]);
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "signatures.txt:variant.bin matches known.txt:a.bin ({})\n",
+            score
+        )
+    );
+    let output = run(&dir, &["-x", "known.txt", "signatures.txt"]);
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "signatures.txt:variant.bin matches known.txt:a.bin ({})\n",
+            score
+        )
+    );
+
+    assert!(!run(&dir, &["-d", "-g", "a.bin"]).status.success());
+    assert!(!run(&dir, &["-t", "101", "a.bin"]).status.success());
+    assert!(!run(&dir, &["-m", "missing.txt", "a.bin"]).status.success());
+
+    // -k and -x read signature files, not standard input
+    for args in &[&["-k", "known.txt"][..], &["-x"], &["-x", "--"]] {
+        let output = run(&dir, args);
+        assert_eq!(output.status.code(), Some(1), "{:?}", args);
+        assert_eq!(stdout(&output), "");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains("need at least one signature file"),
+            "{}",
+            stderr
+        );
+        assert!(stderr.contains("Usage: fuzzyhash"), "{}", stderr);
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
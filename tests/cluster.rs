@@ -0,0 +1,126 @@
+mod common;
+
+use fuzzyhash::{cluster, FuzzyHash};
+
+/// Connected components of the brute force similarity graph, as sorted member lists
+fn brute_force_components(hashes: &[FuzzyHash], threshold: u32) -> Vec<Vec<usize>> {
+    let mut component: Vec<usize> = (0..hashes.len()).collect();
+    for (i, hash) in hashes.iter().enumerate() {
+        for (j, score) in common::brute_force(hash, hashes) {
+            if score >= threshold && component[i] != component[j] {
+                let (from, to) = (component[j], component[i]);
+                for c in component.iter_mut() {
+                    if *c == from {
+                        *c = to;
+                    }
+                }
+            }
+        }
+    }
+
+    let mut components: Vec<Vec<usize>> = Vec::new();
+    for i in 0..hashes.len() {
+        if let Some(members) = components
+            .iter_mut()
+            .find(|members| component[members[0]] == component[i])
+        {
+            members.push(i);
+        } else {
+            components.push(vec![i]);
+        }
+    }
+    components
+}
+
+#[test]
+fn single_linkage_matches_brute_force() {
+    let hashes = common::corpus();
+    for threshold in &[1, 30, 60, 90] {
+        let clustering = cluster::single_linkage(&hashes, *threshold).unwrap();
+        let components = brute_force_components(&hashes, *threshold);
+
+        let clusters: Vec<Vec<usize>> = components
+            .iter()
+            .filter(|members| members.len() > 1)
+            .cloned()
+            .collect();
+        let unclustered: Vec<usize> = components
+            .iter()
+            .filter(|members| members.len() == 1)
+            .map(|members| members[0])
+            .collect();
+
+        assert_eq!(
+            clustering
+                .clusters
+                .iter()
+                .map(|c| c.members.clone())
+                .collect::<Vec<_>>(),
+            clusters,
+            "threshold {}",
+            threshold
+        );
+        assert_eq!(clustering.unclustered, unclustered);
+
+        for c in &clustering.clusters {
+            assert!(c.members.contains(&c.representative));
+            assert!(c.stats.pairs >= c.members.len() - 1);
+            assert!(c.stats.min_score >= *threshold);
+            assert!(c.stats.min_score <= c.stats.max_score);
+            assert!(c.stats.mean_score >= f64::from(c.stats.min_score));
+            assert!(c.stats.mean_score <= f64::from(c.stats.max_score));
+        }
+    }
+}
+
+#[test]
+fn cluster_representative() {
+    let base = common::random_data(7, 8192);
+    let hashes: Vec<FuzzyHash> = (0..4)
+        .map(|i| FuzzyHash::new(common::mutate(&base, i, 4 * i as usize)).unwrap())
+        .collect();
+
+    let clustering = cluster::single_linkage(&hashes, 1).unwrap();
+    assert_eq!(clustering.clusters.len(), 1);
+    let c = &clustering.clusters[0];
+    assert_eq!(c.members, vec![0, 1, 2, 3]);
+
+    let total = |i: usize| -> u32 {
+        (0..hashes.len())
+            .filter(|j| *j != i)
+            .map(|j| hashes[i].compare_to(&hashes[j]).unwrap_or(0))
+            .sum()
+    };
+    let best = (0..hashes.len()).map(total).max().unwrap();
+    assert_eq!(total(c.representative), best);
+}
+
+#[test]
+fn density_requires_core_digests() {
+    let hashes = common::corpus();
+    let linked = cluster::single_linkage(&hashes, 30).unwrap();
+
+    // with a single neighbour required every linked digest is core, like single linkage
+    assert_eq!(cluster::density(&hashes, 30, 1).unwrap(), linked);
+
+    let strict = cluster::density(&hashes, 30, hashes.len()).unwrap();
+    assert!(strict.clusters.is_empty());
+    assert_eq!(strict.unclustered, (0..hashes.len()).collect::<Vec<_>>());
+
+    let dense = cluster::density(&hashes, 30, 3).unwrap();
+    for c in &dense.clusters {
+        assert!(linked
+            .clusters
+            .iter()
+            .any(|l| c.members.iter().all(|m| l.members.contains(m))));
+    }
+}
+
+#[test]
+fn cluster_empty_and_unfinalized() {
+    let clustering = cluster::single_linkage(&[], 50).unwrap();
+    assert!(clustering.clusters.is_empty());
+    assert!(clustering.unclustered.is_empty());
+
+    assert!(cluster::single_linkage(&[FuzzyHash::default()], 50).is_err());
+}
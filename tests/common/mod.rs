@@ -0,0 +1,71 @@
+#![allow(dead_code)]
+
+use fuzzyhash::FuzzyHash;
+
+/// Deterministic pseudo random bytes
+pub fn random_data(seed: u64, len: usize) -> Vec<u8> {
+    let mut state = seed.wrapping_mul(6_364_136_223_846_793_005) | 1;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 24) as u8
+        })
+        .collect()
+}
+
+/// Copy of `data` with `edits` runs of bytes overwritten
+pub fn mutate(data: &[u8], seed: u64, edits: usize) -> Vec<u8> {
+    let mut data = data.to_vec();
+    let noise = random_data(seed, edits * 2 + 64);
+    for edit in 0..edits {
+        let position = (noise[edit * 2] as usize * 257 + noise[edit * 2 + 1] as usize * 7919)
+            % data.len().max(1);
+        for (offset, byte) in noise.iter().take(32).enumerate() {
+            if let Some(b) = data.get_mut(position + offset) {
+                *b = *byte;
+            }
+        }
+    }
+    data
+}
+
+/// A corpus of related fuzzy hashes across several block sizes
+pub fn corpus() -> Vec<FuzzyHash> {
+    let mut hashes = Vec::new();
+    for (family, len) in [200, 3_000, 6_500, 12_000, 25_000, 50_000, 100_000]
+        .iter()
+        .enumerate()
+    {
+        let base = random_data(family as u64 + 1, *len);
+        hashes.push(FuzzyHash::new(&base).unwrap());
+        for variant in 0..6 {
+            let mut data = mutate(&base, (family * 10 + variant) as u64, variant * 3);
+            data.truncate(len - len * variant / 12);
+            hashes.push(FuzzyHash::new(&data).unwrap());
+        }
+    }
+    hashes.push("3::".parse().unwrap());
+    hashes.push("3:abc:de".parse().unwrap());
+    hashes.push("3:abc:xyz".parse().unwrap());
+    hashes.push("6:abc:de".parse().unwrap());
+    hashes
+}
+
+/// Score every pair the slow way
+pub fn brute_force(query: &FuzzyHash, hashes: &[FuzzyHash]) -> Vec<(usize, u32)> {
+    let mut scores: Vec<(usize, u32)> = hashes
+        .iter()
+        .enumerate()
+        .map(|(i, hash)| {
+            (
+                i,
+                FuzzyHash::compare(query.to_string(), hash.to_string()).unwrap_or(0),
+            )
+        })
+        .filter(|(_, score)| *score > 0)
+        .collect();
+    scores.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    scores
+}
@@ -6,3 +6,12 @@ use fuzzyhash::FuzzyHash;
 fn compare_empty() {
     assert!(FuzzyHash::compare("", "").is_err());
 }
+
+#[test]
+fn compare_malformed_on_one_side() {
+    let valid = "3:AXGBicFlgVNhBGcL6wCrFQEv:AXGHsNhxLsr2C";
+    for malformed in &["3:AXGBicFlgVNhBGcL6wCrFQEv", "3", "3:A:B:C", ""] {
+        assert!(FuzzyHash::compare(valid, malformed).is_err());
+        assert!(FuzzyHash::compare(malformed, valid).is_err());
+    }
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use fuzzyhash::{
+    diff::{self, Region, RegionDiff},
+    error::Error,
+    FuzzyHash,
+};
+use std::ops::Range;
+
+/// Regions cover each input without gaps, in order
+fn assert_covers(diff: &RegionDiff, first_len: u64, second_len: u64) {
+    let (mut first_end, mut second_end) = (0, 0);
+    let follow = |end: &mut u64, range: &Range<u64>| {
+        assert_eq!(range.start, *end, "{:?}", diff.regions);
+        assert!(range.end > range.start);
+        *end = range.end;
+    };
+    for region in &diff.regions {
+        match region {
+            Region::Matched { first, second } => {
+                follow(&mut first_end, first);
+                follow(&mut second_end, second);
+            }
+            Region::Removed(range) => follow(&mut first_end, range),
+            Region::Inserted(range) => follow(&mut second_end, range),
+        }
+    }
+    assert_eq!((first_end, second_end), (first_len, second_len));
+}
+
+#[test]
+fn diff_identical() {
+    let data = common::random_data(50, 30_000);
+    let diff = diff::diff(&data, &data).unwrap();
+    assert_eq!(diff.distance, 0);
+    assert_covers(&diff, 30_000, 30_000);
+    assert_eq!(diff.matched_len(), (30_000, 30_000));
+}
+
+#[test]
+fn diff_insertion() {
+    let first = common::random_data(51, 60_000);
+    let inserted = common::random_data(52, 5_000);
+    let mut second = first[..20_000].to_vec();
+    second.extend_from_slice(&inserted);
+    second.extend_from_slice(&first[20_000..]);
+
+    let diff = diff::diff(&first, &second).unwrap();
+    assert_covers(&diff, 60_000, 65_000);
+    assert!(diff.distance > 0);
+
+    // matched regions line up with the shift of the insertion
+    for region in &diff.regions {
+        if let Region::Matched { first, second } = region {
+            let shift = if first.start < 20_000 { 0 } else { 5_000 };
+            assert_eq!(second.start, first.start + shift, "{:?}", diff.regions);
+            assert_eq!(second.end, first.end + shift, "{:?}", diff.regions);
+        }
+    }
+    // only the chunks around the insertion differ
+    let (matched, _) = diff.matched_len();
+    assert!(matched > 50_000, "matched {}", matched);
+    assert!(diff.regions.iter().any(|region| match region {
+        Region::Inserted(range) => range.start <= 20_000 && range.end >= 25_000,
+        _ => false,
+    }));
+}
+
+#[test]
+fn diff_unrelated() {
+    let first = common::random_data(53, 40_000);
+    let second = common::random_data(54, 40_000);
+    let diff = diff::diff(&first, &second).unwrap();
+    assert_covers(&diff, 40_000, 40_000);
+    let (matched, _) = diff.matched_len();
+    assert!(matched < 10_000, "matched {}", matched);
+}
+
+#[test]
+fn diff_files_and_hashes() {
+    let path = "./tests/test_data.bin";
+    let len = std::fs::metadata(path).unwrap().len();
+    let diff = diff::diff_files(path, path).unwrap();
+    assert_covers(&diff, len, len);
+    assert_eq!(diff.matched_len(), (len, len));
+
+    let mut tracked = FuzzyHash::tracking_chunks();
+    tracked.update(std::fs::read(path).unwrap());
+    tracked.finalize().unwrap();
+    assert_eq!(diff::diff_hashes(&tracked, &tracked).unwrap(), diff);
+}
+
+#[test]
+fn diff_errors() {
+    let untracked = FuzzyHash::new("this is our test data!").unwrap();
+    match diff::diff_hashes(&untracked, &untracked) {
+        Err(Error::NotTracked) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    let small = common::random_data(55, 100);
+    let large = common::random_data(56, 2_000_000);
+    match diff::diff(&small, &large) {
+        Err(Error::IncompatibleBlockSizes(3, _)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
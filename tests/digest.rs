@@ -0,0 +1,99 @@
+mod common;
+
+use fuzzyhash::{
+    digest::SimilarityDigest, lzjd::Lzjd, mrsh::Mrsh, multi::MultiHash, nilsimsa::Nilsimsa,
+    tlsh::Tlsh, FuzzyHash,
+};
+use std::io::Cursor;
+
+fn check_digest<D: SimilarityDigest>() {
+    let data = common::random_data(11, 30_000);
+
+    let mut streamed = D::default();
+    for chunk in data.chunks(1000) {
+        SimilarityDigest::update(&mut streamed, chunk);
+    }
+    SimilarityDigest::finalize(&mut streamed).unwrap();
+    let computed = D::compute(&data).unwrap();
+    let read = D::read(&mut Cursor::new(&data)).unwrap();
+
+    let digest = computed.format().unwrap();
+    assert_eq!(streamed.format().unwrap(), digest, "{}", D::NAME);
+    assert_eq!(read.format().unwrap(), digest, "{}", D::NAME);
+    assert_eq!(computed.to_string(), digest, "{}", D::NAME);
+
+    let parsed = D::parse(&digest).unwrap();
+    assert_eq!(parsed.format().unwrap(), digest, "{}", D::NAME);
+    assert_eq!(parsed.similarity(&computed).unwrap(), 100, "{}", D::NAME);
+
+    let edited = D::compute(common::mutate(&data, 2, 3)).unwrap();
+    let unrelated = D::compute(common::random_data(12, 30_000)).unwrap();
+    let near = computed.similarity(&edited).unwrap();
+    let far = computed.similarity(&unrelated).unwrap();
+    assert!(near > 50 && near < 100, "{} near {}", D::NAME, near);
+    assert!(far < 30, "{} far {}", D::NAME, far);
+    assert_eq!(edited.similarity(&computed).unwrap(), near, "{}", D::NAME);
+
+    assert!(D::default().format().is_err(), "{}", D::NAME);
+    assert!(computed.similarity(&D::default()).is_err(), "{}", D::NAME);
+    assert!(D::parse("not a digest").is_err(), "{}", D::NAME);
+}
+
+#[test]
+fn similarity_digest_ssdeep() {
+    check_digest::<FuzzyHash>();
+    assert_eq!(<FuzzyHash as SimilarityDigest>::NAME, "ssdeep");
+}
+
+#[test]
+fn similarity_digest_tlsh() {
+    check_digest::<Tlsh>();
+    assert_eq!(<Tlsh as SimilarityDigest>::NAME, "tlsh");
+}
+
+#[test]
+fn similarity_digest_lzjd() {
+    check_digest::<Lzjd>();
+    assert_eq!(<Lzjd as SimilarityDigest>::NAME, "lzjd");
+}
+
+#[test]
+fn similarity_digest_mrsh() {
+    check_digest::<Mrsh>();
+    assert_eq!(<Mrsh as SimilarityDigest>::NAME, "mrsh");
+}
+
+#[test]
+fn similarity_digest_multi() {
+    check_digest::<MultiHash>();
+    assert_eq!(<MultiHash as SimilarityDigest>::NAME, "ssdeep-multi");
+}
+
+#[test]
+fn similarity_digest_nilsimsa() {
+    check_digest::<Nilsimsa>();
+    assert_eq!(<Nilsimsa as SimilarityDigest>::NAME, "nilsimsa");
+}
+
+#[cfg(feature = "sdhash")]
+#[test]
+fn similarity_digest_sdhash() {
+    use fuzzyhash::sdhash::Sdhash;
+
+    check_digest::<Sdhash>();
+    assert_eq!(<Sdhash as SimilarityDigest>::NAME, "sdhash");
+}
+
+#[test]
+fn similarity_digest_file() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_data.bin");
+    assert_eq!(
+        <FuzzyHash as SimilarityDigest>::file(path).unwrap(),
+        FuzzyHash::file(path).unwrap()
+    );
+    assert_eq!(
+        <Tlsh as SimilarityDigest>::file(path).unwrap(),
+        Tlsh::new(std::fs::read(path).unwrap()).unwrap()
+    );
+    assert!(<Tlsh as SimilarityDigest>::file("/does/not/exist").is_err());
+}
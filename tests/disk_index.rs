@@ -0,0 +1,221 @@
+#![cfg(feature = "mmap")]
+
+mod common;
+
+use fuzzyhash::{
+    error::Error,
+    index::{disk::DiskIndex, Index},
+};
+use std::path::PathBuf;
+
+fn temporary_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("fuzzyhash-{}-{}.idx", std::process::id(), name))
+}
+
+#[test]
+fn disk_index_matches_memory_index() {
+    let path = temporary_path("matches");
+    let hashes = common::corpus();
+    let disk = DiskIndex::create(&path, &hashes).unwrap();
+    disk.verify().unwrap();
+
+    let mut memory = Index::new();
+    for hash in &hashes {
+        memory.insert(hash.clone()).unwrap();
+    }
+
+    assert_eq!(disk.len(), hashes.len());
+    for (id, query) in hashes.iter().enumerate() {
+        assert_eq!(&disk.get(id).unwrap().unwrap(), query);
+        assert_eq!(
+            disk.query(query, 0).unwrap(),
+            memory.query(query, 0).unwrap()
+        );
+        assert_eq!(
+            disk.top_k(query, 2).unwrap(),
+            memory.top_k(query, 2).unwrap()
+        );
+    }
+    assert!(disk.get(hashes.len()).is_none());
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn disk_index_append_and_compact() {
+    let path = temporary_path("append");
+    let hashes = common::corpus();
+    let (first, second) = hashes.split_at(hashes.len() / 2);
+
+    DiskIndex::create(&path, first).unwrap();
+    let mut disk = DiskIndex::open(&path).unwrap();
+    assert_eq!(disk.append(second).unwrap(), first.len());
+    assert_eq!(disk.segment_count(), 2);
+    assert_eq!(disk.len(), hashes.len());
+
+    let expected: Vec<_> = hashes
+        .iter()
+        .map(|query| disk.query(query, 1).unwrap())
+        .collect();
+    for (query, expected) in hashes.iter().zip(&expected) {
+        let brute_force = common::brute_force(query, &hashes);
+        let found: Vec<(usize, u32)> = expected.iter().map(|m| (m.id, m.score)).collect();
+        assert_eq!(found, brute_force);
+    }
+
+    disk.compact().unwrap();
+    assert_eq!(disk.segment_count(), 1);
+    assert_eq!(disk.len(), hashes.len());
+    disk.verify().unwrap();
+    for (query, expected) in hashes.iter().zip(&expected) {
+        assert_eq!(&disk.query(query, 1).unwrap(), expected);
+    }
+
+    let reopened = DiskIndex::open(&path).unwrap();
+    assert_eq!(reopened.len(), hashes.len());
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn disk_index_ignores_interrupted_append() {
+    let path = temporary_path("interrupted");
+    let other = temporary_path("interrupted-other");
+    let hashes = common::corpus();
+    let (first, second) = hashes.split_at(hashes.len() / 2);
+
+    // a crash part way through an append leaves part of a segment past the committed end
+    DiskIndex::create(&path, first).unwrap();
+    DiskIndex::create(&other, second).unwrap();
+    let committed = std::fs::read(&path).unwrap();
+    let segment = std::fs::read(&other).unwrap();
+    let mut data = committed.clone();
+    data.extend_from_slice(&segment[32..segment.len() / 2]);
+    std::fs::write(&path, &data).unwrap();
+
+    let mut disk = DiskIndex::open(&path).unwrap();
+    disk.verify().unwrap();
+    assert_eq!(disk.len(), first.len());
+    assert_eq!(disk.segment_count(), 1);
+    assert!(disk.get(first.len()).is_none());
+
+    // the next append replaces the partial segment
+    assert_eq!(disk.append(second).unwrap(), first.len());
+    let reopened = DiskIndex::open(&path).unwrap();
+    reopened.verify().unwrap();
+    assert_eq!(reopened.len(), hashes.len());
+    for query in &hashes {
+        let found: Vec<(usize, u32)> = reopened
+            .query(query, 1)
+            .unwrap()
+            .iter()
+            .map(|m| (m.id, m.score))
+            .collect();
+        assert_eq!(found, common::brute_force(query, &hashes));
+    }
+
+    std::fs::remove_file(path).unwrap();
+    std::fs::remove_file(other).unwrap();
+}
+
+#[test]
+fn disk_index_remove_and_compact() {
+    let path = temporary_path("remove");
+    let hashes = common::corpus();
+    let (first, second) = hashes.split_at(hashes.len() / 2);
+
+    let mut disk = DiskIndex::create(&path, first).unwrap();
+    disk.append(second).unwrap();
+    let mut memory = Index::new();
+    for hash in &hashes {
+        memory.insert(hash.clone()).unwrap();
+    }
+
+    // one digest from each segment, and the last one so its identifier must not be reused
+    let removed = [0, first.len(), hashes.len() - 1];
+    for id in &removed {
+        assert_eq!(disk.remove(*id).unwrap(), memory.remove(*id));
+        assert_eq!(disk.remove(*id).unwrap(), None);
+    }
+    assert_eq!(disk.remove(hashes.len()).unwrap(), None);
+    assert_eq!(disk.len(), memory.len());
+    disk.verify().unwrap();
+
+    let check = |disk: &DiskIndex| {
+        assert_eq!(disk.len(), memory.len());
+        for id in 0..hashes.len() {
+            assert_eq!(
+                disk.get(id).map(Result::unwrap).as_ref(),
+                memory.get(id),
+                "{}",
+                id
+            );
+        }
+        for query in &hashes {
+            assert_eq!(
+                disk.query(query, 0).unwrap(),
+                memory.query(query, 0).unwrap()
+            );
+            assert_eq!(
+                disk.top_k(query, 2).unwrap(),
+                memory.top_k(query, 2).unwrap()
+            );
+        }
+    };
+    check(&disk);
+    check(&DiskIndex::open(&path).unwrap());
+
+    let size = std::fs::metadata(&path).unwrap().len();
+    disk.compact().unwrap();
+    assert_eq!(disk.segment_count(), 1);
+    assert!(std::fs::metadata(&path).unwrap().len() < size);
+    disk.verify().unwrap();
+    check(&disk);
+
+    assert_eq!(disk.append(&hashes[..1]).unwrap(), hashes.len());
+    assert_eq!(disk.len(), memory.len() + 1);
+    assert!(disk.get(0).is_none());
+    assert_eq!(&disk.get(hashes.len()).unwrap().unwrap(), &hashes[0]);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn disk_index_detects_corruption() {
+    let path = temporary_path("corrupt");
+    let hashes = common::corpus();
+    DiskIndex::create(&path, &hashes).unwrap();
+    let original = std::fs::read(&path).unwrap();
+
+    let mut data = original.clone();
+    let last = data.len() - 1;
+    data[last] ^= 0xff;
+    std::fs::write(&path, &data).unwrap();
+    let index = DiskIndex::open(&path).unwrap();
+    assert!(matches!(index.verify(), Err(Error::CorruptIndex(_))));
+    drop(index);
+
+    let mut data = original.clone();
+    data.truncate(data.len() - 10);
+    std::fs::write(&path, &data).unwrap();
+    assert!(matches!(
+        DiskIndex::open(&path),
+        Err(Error::CorruptIndex(_))
+    ));
+
+    let mut data = original.clone();
+    data[8] ^= 0xff;
+    std::fs::write(&path, &data).unwrap();
+    assert!(matches!(
+        DiskIndex::open(&path),
+        Err(Error::CorruptIndex(_))
+    ));
+
+    std::fs::write(&path, b"not an index").unwrap();
+    assert!(matches!(
+        DiskIndex::open(&path),
+        Err(Error::CorruptIndex(_))
+    ));
+
+    std::fs::remove_file(path).unwrap();
+}
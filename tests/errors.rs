@@ -0,0 +1,31 @@
+use fuzzyhash::{error::Error, tlsh::Tlsh, FuzzyHash};
+use std::error::Error as _;
+
+#[test]
+fn error_messages_include_context() {
+    let error = FuzzyHash::compare("3:abc:def", "12:abc:def").unwrap_err();
+    match error {
+        Error::IncompatibleBlockSizes(3, 12) => {}
+        ref other => panic!("unexpected error {:?}", other),
+    }
+    assert_eq!(
+        error.to_string(),
+        "Fuzzy hashes have incompatible block sizes 3 and 12"
+    );
+
+    let error = "x3:abc:def".parse::<FuzzyHash>().err().unwrap();
+    assert_eq!(error.to_string(), "Could not parse block size \"x3\"");
+
+    // every digest kind reports malformed input the same way
+    let error = "3:abc".parse::<FuzzyHash>().err().unwrap();
+    assert_eq!(error.to_string(), "Malformed digest");
+    let error = "T1XYZ".parse::<Tlsh>().err().unwrap();
+    assert_eq!(error.to_string(), "Malformed digest");
+}
+
+#[test]
+fn error_sources() {
+    let error = FuzzyHash::file("./tests/does_not_exist.bin").err().unwrap();
+    assert!(error.source().is_some());
+    assert!(Error::MalformedInput.source().is_none());
+}
@@ -1,4 +1,31 @@
-use fuzzyhash::FuzzyHash;
+mod common;
+
+use fuzzyhash::{error::Error, FuzzyHash};
+use std::io::{self, Read};
+
+/// A reader returning at most a few bytes at a time and interrupted every other call, like a
+/// pipe fed in bursts
+struct ShortReads {
+    data: Vec<u8>,
+    position: usize,
+    calls: usize,
+}
+
+impl Read for ShortReads {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        self.calls += 1;
+        if self.calls % 2 == 0 {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "interrupted"));
+        }
+        let len = buffer
+            .len()
+            .min(1 + self.calls % 700)
+            .min(self.data.len() - self.position);
+        buffer[..len].copy_from_slice(&self.data[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
 
 #[test]
 fn hash_test_data() {
@@ -9,3 +36,25 @@ fn hash_test_data() {
         "192:tEIFoBn+SbDjIZ6MUpH6rDjHPanaVGLGOvkdGep:tEIeBrbDjRAvDVEGOMGQ".to_owned()
     );
 }
+
+#[test]
+fn hash_missing_file() {
+    match FuzzyHash::file("./tests/does_not_exist.bin") {
+        Err(Error::Io(e)) => assert_eq!(e.kind(), std::io::ErrorKind::NotFound),
+        _ => panic!("expected an I/O error"),
+    }
+}
+
+#[test]
+fn hash_short_reads() {
+    let data = common::random_data(25, 20_000);
+    let mut reader = ShortReads {
+        data: data.clone(),
+        position: 0,
+        calls: 0,
+    };
+    assert_eq!(
+        FuzzyHash::read(&mut reader).unwrap(),
+        FuzzyHash::new(&data).unwrap()
+    );
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use fuzzyhash::index::{Index, Match};
+
+fn matches(scores: Vec<(usize, u32)>) -> Vec<Match> {
+    scores
+        .into_iter()
+        .map(|(id, score)| Match { id, score })
+        .collect()
+}
+
+#[test]
+fn index_matches_brute_force() {
+    let hashes = common::corpus();
+    let mut index = Index::new();
+    for hash in &hashes {
+        index.insert(hash.clone()).unwrap();
+    }
+    assert_eq!(index.len(), hashes.len());
+
+    let mut nontrivial = 0;
+    for query in &hashes {
+        let expected = matches(common::brute_force(query, &hashes));
+        nontrivial += expected.len() - 1;
+        assert_eq!(index.query(query, 0).unwrap(), expected, "query {}", query);
+
+        let above: Vec<Match> = expected.iter().filter(|m| m.score >= 40).copied().collect();
+        assert_eq!(index.query(query, 40).unwrap(), above);
+
+        let top: Vec<Match> = expected.iter().take(3).copied().collect();
+        assert_eq!(index.top_k(query, 3).unwrap(), top);
+    }
+    assert!(nontrivial > 0);
+}
+
+#[test]
+fn index_remove() {
+    let hashes = common::corpus();
+    let mut index = Index::new();
+    for hash in &hashes {
+        index.insert(hash.clone()).unwrap();
+    }
+
+    let removed = index.remove(1).unwrap();
+    assert_eq!(removed, hashes[1]);
+    assert!(index.remove(1).is_none());
+    assert!(index.get(1).is_none());
+    assert_eq!(index.len(), hashes.len() - 1);
+
+    let results = index.query(&hashes[1], 1).unwrap();
+    assert!(results.iter().all(|m| m.id != 1));
+    assert_eq!(results[0].id, 0);
+    assert_eq!(index.iter().count(), hashes.len() - 1);
+}
+
+#[test]
+fn index_requires_finalized_hash() {
+    let mut index = Index::new();
+    assert!(index.insert(fuzzyhash::FuzzyHash::default()).is_err());
+    assert!(index.query(&fuzzyhash::FuzzyHash::default(), 0).is_err());
+}
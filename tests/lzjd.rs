@@ -0,0 +1,101 @@
+mod common;
+
+use fuzzyhash::{error::Error, lzjd::Lzjd};
+
+#[test]
+fn lzjd_round_trip() {
+    let data = common::random_data(13, 20_000);
+    let lzjd = Lzjd::new(&data).unwrap();
+    assert!(!lzjd.is_parsed());
+    assert_eq!(lzjd.sketch().unwrap().len(), 1024);
+    assert!(lzjd.digest().unwrap().starts_with("lzjd:1024:"));
+
+    let parsed: Lzjd = lzjd.digest().unwrap().parse().unwrap();
+    assert!(parsed.is_parsed());
+    assert_eq!(parsed, lzjd);
+    assert_eq!(parsed.sketch().unwrap(), lzjd.sketch().unwrap());
+    assert_eq!(parsed.jaccard(&lzjd).unwrap(), 1.0);
+
+    // short inputs keep every phrase, padding covers every remainder of the base64 groups
+    for len in 1..8 {
+        let small = Lzjd::new(&data[..len]).unwrap();
+        assert_eq!(small.sketch().unwrap().len(), len);
+        assert_eq!(small.to_string().parse::<Lzjd>().unwrap(), small);
+    }
+}
+
+#[test]
+fn lzjd_streaming_matches_one_shot() {
+    let data = common::random_data(14, 10_000);
+    let mut lzjd = Lzjd::default();
+    for chunk in data.chunks(77) {
+        lzjd.update(chunk);
+    }
+    assert!(lzjd.digest().is_err());
+    lzjd.finalize().unwrap();
+    assert_eq!(lzjd, Lzjd::new(&data).unwrap());
+
+    let digest = lzjd.to_string();
+    lzjd.update(b"more");
+    lzjd.finalize().unwrap();
+    assert_eq!(lzjd.to_string(), digest);
+}
+
+#[test]
+fn lzjd_similarity() {
+    let base = common::random_data(15, 50_000);
+    let original = Lzjd::new(&base).unwrap();
+    let edited = Lzjd::new(common::mutate(&base, 3, 10)).unwrap();
+    let unrelated = Lzjd::new(common::random_data(16, 50_000)).unwrap();
+
+    let near = original.jaccard(&edited).unwrap();
+    assert!(near > 0.7 && near < 1.0, "near {}", near);
+    assert_eq!(edited.jaccard(&original).unwrap(), near);
+    // unrelated random inputs still share most of their one and two byte phrases
+    let far = original.jaccard(&unrelated).unwrap();
+    assert!(far < 0.2, "far {}", far);
+
+    // phrases do not depend on offsets, so swapped halves still share many of them
+    let mut reordered = base[25_000..].to_vec();
+    reordered.extend_from_slice(&base[..25_000]);
+    let reordered = Lzjd::new(&reordered).unwrap();
+    let swapped = original.jaccard(&reordered).unwrap();
+    assert!(swapped > far * 2.0, "swapped {}", swapped);
+
+    // sketches of different sizes are compared at the smaller size
+    let mut small = Lzjd::with_sketch_size(256);
+    small.update(&base);
+    small.finalize().unwrap();
+    assert_eq!(small.sketch_size(), 256);
+    assert_eq!(small.sketch().unwrap(), &original.sketch().unwrap()[..256]);
+    assert_eq!(small.jaccard(&original).unwrap(), 1.0);
+}
+
+#[test]
+fn lzjd_errors() {
+    match Lzjd::new(b"").unwrap_err() {
+        Error::InsufficientData => {}
+        other => panic!("unexpected error {:?}", other),
+    }
+    match Lzjd::default().jaccard(&Lzjd::new(b"data").unwrap()) {
+        Err(Error::NotFinalized) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    for bad in &[
+        "",
+        "lzjd",
+        "lzjd:1024",
+        "lzjd:0:AAAAAQ==",
+        "lzjd:x:AAAAAQ==",
+        "lzjd:4:",
+        "lzjd:4:AAAAAQ",
+        "lzjd:4:AAAA",
+        "lzjd:4:AAAAAg==AAAAAQ==",
+        "lzjd:4:AAAAAgAAAAE=",
+        "tlsh:4:AAAAAQ==",
+        "lzjd:4:AAAA*Q==",
+    ] {
+        assert!(bad.parse::<Lzjd>().is_err(), "{:?}", bad);
+    }
+}
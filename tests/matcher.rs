@@ -0,0 +1,122 @@
+mod common;
+
+use fuzzyhash::{
+    error::Error,
+    matcher::{KnownMatch, Matcher},
+    sigfile::{Header, Writer},
+    FuzzyHash,
+};
+use std::path::PathBuf;
+
+fn temporary_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("fuzzyhash-{}-{}", std::process::id(), name))
+}
+
+fn write_sigfile(name: &str, entries: &[(&FuzzyHash, &str)]) -> PathBuf {
+    let path = temporary_path(name);
+    let mut writer = Writer::new(std::fs::File::create(&path).unwrap(), Header::default()).unwrap();
+    for (hash, filename) in entries {
+        writer.write(hash, filename).unwrap();
+    }
+    path
+}
+
+#[test]
+fn matcher_reports_known_files() {
+    let base = common::random_data(11, 20_000);
+    let variant = common::mutate(&base, 3, 6);
+    let unrelated = common::random_data(12, 20_000);
+
+    let known = [
+        FuzzyHash::new(&unrelated).unwrap(),
+        FuzzyHash::new(&variant).unwrap(),
+        FuzzyHash::new(&base).unwrap(),
+    ];
+    let first = write_sigfile(
+        "known1.txt",
+        &[(&known[0], "unrelated"), (&known[1], "variant")],
+    );
+    let second = write_sigfile("known2.txt", &[(&known[2], "dir\\base \"v1\"")]);
+
+    let mut matcher = Matcher::new();
+    assert_eq!(matcher.load(&first).unwrap(), 2);
+    assert_eq!(matcher.load(&second).unwrap(), 1);
+    assert_eq!(matcher.len(), 3);
+
+    let input = temporary_path("input.bin");
+    std::fs::write(&input, &base).unwrap();
+    let found = matcher.match_file(&input, 0).unwrap();
+    let variant_score = known[2].compare_to(&known[1]).unwrap();
+    assert!(variant_score > 0 && variant_score < 100);
+
+    assert_eq!(found.filename, input.display().to_string());
+    assert_eq!(
+        found.matches,
+        vec![
+            KnownMatch {
+                filename: "variant".to_string(),
+                source: first.display().to_string(),
+                score: variant_score,
+            },
+            KnownMatch {
+                filename: "dir\\base \"v1\"".to_string(),
+                source: second.display().to_string(),
+                score: 100,
+            },
+        ]
+    );
+    assert_eq!(
+        found.to_string(),
+        format!(
+            "{input} matches {first}:variant ({score})\n{input} matches {second}:dir\\base \"v1\" (100)\n",
+            input = input.display(),
+            first = first.display(),
+            second = second.display(),
+            score = variant_score
+        )
+    );
+
+    // like ssdeep, the score must be above the threshold
+    let found = matcher.match_file(&input, variant_score).unwrap();
+    assert_eq!(found.matches.len(), 1);
+    assert!(matcher.match_file(&input, 100).unwrap().matches.is_empty());
+
+    for path in &[first, second, input] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn matcher_added_directly() {
+    let hash: FuzzyHash = "3:YKKGhR0tn:YRGRmn".parse().unwrap();
+    let mut matcher = Matcher::new();
+    matcher.add(hash.clone(), "known", "").unwrap();
+
+    let found = matcher.match_hash(&hash, "input", 0).unwrap();
+    assert_eq!(found.to_string(), "input matches known (100)\n");
+    assert!(matcher
+        .add(FuzzyHash::default(), "unfinalized", "")
+        .is_err());
+}
+
+#[test]
+fn matcher_load_errors() {
+    let mut matcher = Matcher::new();
+    match matcher.load(temporary_path("missing.txt")).unwrap_err() {
+        Error::Io(_) => {}
+        other => panic!("unexpected error {:?}", other),
+    }
+
+    let path = temporary_path("bad.txt");
+    std::fs::write(
+        &path,
+        "ssdeep,1.1--blocksize:hash:hash,filename\n3:YKKGhR0tn:YRGRmn,\"a\"\nbad\n",
+    )
+    .unwrap();
+    match matcher.load(&path).unwrap_err() {
+        Error::InvalidSignatureFile(3, _) => {}
+        other => panic!("unexpected error {:?}", other),
+    }
+    assert!(matcher.is_empty());
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,122 @@
+mod common;
+
+use fuzzyhash::{
+    error::Error,
+    mrsh::{Mrsh, DEFAULT_BLOCK_SIZE},
+};
+
+#[test]
+fn mrsh_round_trip() {
+    let data = common::random_data(27, 100_000);
+    let mrsh = Mrsh::new(&data).unwrap();
+    assert!(!mrsh.is_parsed());
+    assert_eq!(mrsh.block_size(), DEFAULT_BLOCK_SIZE);
+    assert_eq!(mrsh.input_len(), 100_000);
+    assert!(mrsh.filter_count() > 1);
+    assert!(mrsh.digest().unwrap().starts_with("mrsh:160:100000:"));
+
+    let parsed: Mrsh = mrsh.digest().unwrap().parse().unwrap();
+    assert!(parsed.is_parsed());
+    assert_eq!(parsed, mrsh);
+    assert_eq!(parsed.filter_count(), mrsh.filter_count());
+    assert_eq!(parsed.containment(&mrsh).unwrap(), 100);
+    assert_eq!(parsed.compare(&mrsh).unwrap(), 100);
+}
+
+#[test]
+fn mrsh_streaming_matches_one_shot() {
+    let data = common::random_data(28, 50_000);
+    let mut mrsh = Mrsh::default();
+    for chunk in data.chunks(100) {
+        mrsh.update(chunk);
+    }
+    assert!(mrsh.digest().is_err());
+    mrsh.finalize().unwrap();
+    assert_eq!(mrsh, Mrsh::new(&data).unwrap());
+
+    let digest = mrsh.to_string();
+    mrsh.update(b"more");
+    mrsh.finalize().unwrap();
+    assert_eq!(mrsh.to_string(), digest);
+}
+
+#[test]
+fn mrsh_containment() {
+    let file = common::random_data(29, 300_000);
+    let whole = Mrsh::new(&file).unwrap();
+
+    for (start, len) in &[
+        (100_000, 40_000),
+        (150_000, 5_000),
+        (0, 20_000),
+        (290_000, 10_000),
+    ] {
+        let fragment = Mrsh::new(&file[*start..start + len]).unwrap();
+        let found = fragment.containment(&whole).unwrap();
+        assert!(found > 80, "fragment at {} found {}", start, found);
+        // the file as a whole does not resemble a small part of it
+        assert!(fragment.compare(&whole).unwrap() < 20);
+    }
+
+    let edited = Mrsh::new(common::mutate(&file, 5, 20)).unwrap();
+    let score = whole.compare(&edited).unwrap();
+    assert!(score > 90, "edited {}", score);
+    assert_eq!(edited.compare(&whole).unwrap(), score);
+
+    let unrelated = common::random_data(30, 300_000);
+    assert_eq!(whole.compare(&Mrsh::new(&unrelated).unwrap()).unwrap(), 0);
+    for start in (0..290_000).step_by(29_000) {
+        let fragment = Mrsh::new(&unrelated[start..start + 5_000]).unwrap();
+        assert_eq!(fragment.containment(&whole).unwrap(), 0);
+    }
+}
+
+#[test]
+fn mrsh_block_sizes() {
+    let file = common::random_data(31, 100_000);
+    let mut small = Mrsh::with_block_size(64);
+    small.update(&file);
+    small.finalize().unwrap();
+    assert!(small.filter_count() > Mrsh::new(&file).unwrap().filter_count());
+    assert!(small.digest().unwrap().starts_with("mrsh:64:"));
+
+    let mut fragment = Mrsh::with_block_size(64);
+    fragment.update(&file[40_000..42_000]);
+    fragment.finalize().unwrap();
+    assert!(fragment.containment(&small).unwrap() > 80);
+
+    match fragment
+        .containment(&Mrsh::new(&file).unwrap())
+        .unwrap_err()
+    {
+        Error::IncompatibleDigests => {}
+        other => panic!("unexpected error {:?}", other),
+    }
+}
+
+#[test]
+fn mrsh_errors() {
+    match Mrsh::new(b"").unwrap_err() {
+        Error::InsufficientData => {}
+        other => panic!("unexpected error {:?}", other),
+    }
+    let mrsh = Mrsh::new(b"a short input").unwrap();
+    match mrsh.containment(&Mrsh::default()).unwrap_err() {
+        Error::NotFinalized => {}
+        other => panic!("unexpected error {:?}", other),
+    }
+
+    let digest = mrsh.to_string();
+    for bad in &[
+        "",
+        "mrsh",
+        "mrsh:160:13",
+        &digest.replacen("mrsh:", "mrsx:", 1),
+        &digest.replacen(":160:", ":2:", 1),
+        &digest.replacen(":13:", ":0:", 1),
+        &digest[..digest.len() - 4],
+        &format!("{}:x", digest),
+    ] {
+        assert!(bad.parse::<Mrsh>().is_err(), "{:?}", bad);
+    }
+}
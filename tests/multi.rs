@@ -0,0 +1,99 @@
+mod common;
+
+use fuzzyhash::{error::Error, multi::MultiHash, FuzzyHash};
+
+#[test]
+fn multi_round_trip() {
+    for len in &[0, 22, 5_000, 100_000] {
+        let data = common::random_data(60, *len);
+        let multi = MultiHash::new(&data).unwrap();
+        assert!(!multi.is_parsed());
+        assert!(multi.digest().unwrap().starts_with("multi:"));
+
+        let parsed: MultiHash = multi.digest().unwrap().parse().unwrap();
+        assert!(parsed.is_parsed());
+        assert_eq!(parsed, multi);
+        assert_eq!(parsed.block_sizes().unwrap(), multi.block_sizes().unwrap());
+
+        // the signature at the ssdeep block size is the first ssdeep signature
+        let fuzzy_hash = FuzzyHash::new(&data).unwrap();
+        let block_size = fuzzy_hash.block_size().unwrap();
+        assert_eq!(
+            parsed.signature(block_size).unwrap(),
+            Some(fuzzy_hash.signature1().unwrap()),
+            "{}",
+            len
+        );
+    }
+}
+
+#[test]
+fn multi_streaming_matches_one_shot() {
+    let data = common::random_data(61, 50_000);
+    let mut multi = MultiHash::default();
+    for chunk in data.chunks(333) {
+        multi.update(chunk);
+    }
+    assert!(multi.digest().is_err());
+    multi.finalize().unwrap();
+    assert_eq!(multi, MultiHash::new(&data).unwrap());
+
+    let digest = multi.to_string();
+    multi.update(b"more");
+    multi.finalize().unwrap();
+    assert_eq!(multi.to_string(), digest);
+}
+
+#[test]
+fn multi_compares_inputs_of_different_sizes() {
+    let header = common::random_data(62, 20_000);
+    let mut file = header.clone();
+    file.extend_from_slice(&common::random_data(63, 60_000));
+
+    let header_hash = FuzzyHash::new(&header).unwrap();
+    let file_hash = FuzzyHash::new(&file).unwrap();
+    match FuzzyHash::compare(header_hash.to_string(), file_hash.to_string()) {
+        Err(Error::IncompatibleBlockSizes(_, _)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    let header = MultiHash::new(&header).unwrap();
+    let file = MultiHash::new(&file).unwrap();
+    let score = header.compare(&file).unwrap();
+    assert!(score > 20, "score {}", score);
+    assert_eq!(file.compare(&header).unwrap(), score);
+
+    let unrelated = MultiHash::new(common::random_data(64, 80_000)).unwrap();
+    assert_eq!(header.compare(&unrelated).unwrap(), 0);
+}
+
+#[test]
+fn multi_errors() {
+    let small = MultiHash::new(common::random_data(65, 100)).unwrap();
+    let large = MultiHash::new(common::random_data(66, 2_000_000)).unwrap();
+    match small.compare(&large) {
+        Err(Error::IncompatibleBlockSizes(3, _)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+    match small.compare(&MultiHash::default()) {
+        Err(Error::NotFinalized) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    for bad in &[
+        "",
+        "multi",
+        "multi:3",
+        "multi:4:abc",
+        "multi:x:abc",
+        "multi:3:ab*",
+        "ssdeep:3:abc",
+        "3:abc:def",
+    ] {
+        assert!(bad.parse::<MultiHash>().is_err(), "{:?}", bad);
+    }
+    let too_long = format!("multi:3:{}", "A".repeat(65));
+    assert!(too_long.parse::<MultiHash>().is_err());
+    let too_many = format!("multi:6:{}", vec!["A"; 31].join(":"));
+    assert!(too_many.parse::<MultiHash>().is_err());
+}
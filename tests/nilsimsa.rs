@@ -0,0 +1,102 @@
+mod common;
+
+use fuzzyhash::{error::Error, nilsimsa::Nilsimsa};
+
+#[test]
+fn nilsimsa_digests() {
+    // the example of the reference Python implementation
+    assert_eq!(
+        Nilsimsa::new("abcdefgh").unwrap().digest().unwrap(),
+        "14c8118000000000030800000004042004189020001308014088003280000078"
+    );
+    assert_eq!(
+        Nilsimsa::new("The quick brown fox jumps over the lazy dog")
+            .unwrap()
+            .digest()
+            .unwrap(),
+        "02b0b4ae03001086d100c660ab88503545c14ae760282108390a2928020120db"
+    );
+
+    // a single trigram, and the 4 trigrams of 4 bytes
+    assert_eq!(
+        Nilsimsa::new("abc").unwrap().digest().unwrap(),
+        "0040000000000000000000000000000000000000000000000000000000000000"
+    );
+    assert_eq!(
+        Nilsimsa::new("abcd").unwrap().digest().unwrap(),
+        "0440000000000000000000000000000000100000000000000008000000000000"
+    );
+    assert_eq!(
+        Nilsimsa::new("ab").unwrap().digest().unwrap(),
+        "0".repeat(64)
+    );
+}
+
+#[test]
+fn nilsimsa_round_trip() {
+    let nilsimsa = Nilsimsa::new(common::random_data(25, 500)).unwrap();
+    assert!(!nilsimsa.is_parsed());
+
+    let parsed: Nilsimsa = nilsimsa.digest().unwrap().parse().unwrap();
+    assert!(parsed.is_parsed());
+    assert_eq!(parsed, nilsimsa);
+    assert_eq!(parsed.compare(&nilsimsa).unwrap(), 128);
+
+    let upper: Nilsimsa = nilsimsa.digest().unwrap().to_uppercase().parse().unwrap();
+    assert_eq!(upper, nilsimsa);
+
+    for bad in &["", "14c8", &"g".repeat(64), &"0".repeat(66)] {
+        assert!(bad.parse::<Nilsimsa>().is_err(), "{:?}", bad);
+    }
+}
+
+#[test]
+fn nilsimsa_streaming_matches_one_shot() {
+    let data = common::random_data(26, 3_000);
+    let mut nilsimsa = Nilsimsa::default();
+    for chunk in data.chunks(7) {
+        nilsimsa.update(chunk);
+    }
+    assert!(nilsimsa.digest().is_err());
+    nilsimsa.finalize().unwrap();
+    assert_eq!(nilsimsa, Nilsimsa::new(&data).unwrap());
+
+    let digest = nilsimsa.to_string();
+    nilsimsa.update(b"more");
+    nilsimsa.finalize().unwrap();
+    assert_eq!(nilsimsa.to_string(), digest);
+}
+
+#[test]
+fn nilsimsa_compare() {
+    let message = "Dear customer, your account has been suspended. Please verify your \
+                   details within 24 hours at http://example.com/verify to restore access.";
+    let variant = "Dear client, your account has been suspended! Please verify your \
+                   details within 48 hours at http://example.net/login to restore access.";
+    let unrelated = "Minutes of the weekly planning meeting: the release moves to Thursday \
+                     and the build farm upgrade is postponed until next month.";
+
+    let first = Nilsimsa::new(message).unwrap();
+    let second = Nilsimsa::new(variant).unwrap();
+    let third = Nilsimsa::new(unrelated).unwrap();
+
+    let near = first.compare(&second).unwrap();
+    let far = first.compare(&third).unwrap();
+    assert!(near > 64, "near {}", near);
+    assert!(far < 32, "far {}", far);
+    assert_eq!(second.compare(&first).unwrap(), near);
+
+    // complementary digests are as far apart as possible, the score is clamped to -127
+    let zeros: Nilsimsa = "0".repeat(64).parse().unwrap();
+    let ones: Nilsimsa = "f".repeat(64).parse().unwrap();
+    assert_eq!(zeros.compare(&ones).unwrap(), -127);
+    assert_eq!(ones.compare(&zeros).unwrap(), -127);
+    let alternating: Nilsimsa = "5".repeat(64).parse().unwrap();
+    let opposite: Nilsimsa = "a".repeat(64).parse().unwrap();
+    assert_eq!(alternating.compare(&opposite).unwrap(), -127);
+
+    match first.compare(&Nilsimsa::default()).unwrap_err() {
+        Error::NotFinalized => {}
+        other => panic!("unexpected error {:?}", other),
+    }
+}
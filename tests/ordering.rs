@@ -0,0 +1,78 @@
+use fuzzyhash::FuzzyHash;
+use std::collections::HashSet;
+
+#[test]
+fn deduplicate_in_hash_set() {
+    let hashes: HashSet<FuzzyHash> = vec![
+        FuzzyHash::new("this is our test data!").unwrap(),
+        "3:YKKGhR0tn:YRGRmn".parse().unwrap(),
+        "3:HEREar5MFUul0U0KMP:knl8lkKMP".parse().unwrap(),
+    ]
+    .into_iter()
+    .collect();
+
+    assert_eq!(hashes.len(), 2);
+    assert!(hashes.contains(&"3:YKKGhR0tn:YRGRmn".parse().unwrap()));
+}
+
+#[test]
+fn unfinalized_hashes_are_equal() {
+    // without a digest there is nothing to tell in-progress hashes apart
+    let mut first = FuzzyHash::default();
+    first.update("this is our test data!");
+    let mut second = FuzzyHash::default();
+    second.update("some other data altogether");
+    assert_eq!(first, second);
+    assert_eq!(first.cmp(&second), std::cmp::Ordering::Equal);
+    let hashes: HashSet<FuzzyHash> = vec![first.clone(), second.clone()].into_iter().collect();
+    assert_eq!(hashes.len(), 1);
+
+    first.finalize().unwrap();
+    second.finalize().unwrap();
+    assert_ne!(first, second);
+}
+
+#[test]
+fn sort_by_block_size() {
+    let mut hashes: Vec<FuzzyHash> = vec![
+        "192:tEIFoBn+SbDjIZ6MUpH6rDjHPanaVGLGOvkdGep:tEIeBrbDjRAvDVEGOMGQ",
+        "96:U57GjXnLt9co6pZwvLhJluvrszNgMFwO6MFG8SvkpjTWf:Hj3BeoEcNJ0TspgIG8SvkpjTg",
+        "3:YKKGhR0tn:YRGRmn",
+        "3:HEREar5MFUul0U0KMP:knl8lkKMP",
+    ]
+    .into_iter()
+    .map(|s| s.parse().unwrap())
+    .collect();
+    hashes.push(FuzzyHash::default());
+    hashes.sort();
+
+    let sorted: Vec<String> = hashes.iter().map(|h| h.to_string()).collect();
+    assert_eq!(
+        sorted,
+        vec![
+            "",
+            "3:HEREar5MFUul0U0KMP:knl8lkKMP",
+            "3:YKKGhR0tn:YRGRmn",
+            "96:U57GjXnLt9co6pZwvLhJluvrszNgMFwO6MFG8SvkpjTWf:Hj3BeoEcNJ0TspgIG8SvkpjTg",
+            "192:tEIFoBn+SbDjIZ6MUpH6rDjHPanaVGLGOvkdGep:tEIeBrbDjRAvDVEGOMGQ",
+        ]
+    );
+}
+
+#[test]
+fn normalized_equality() {
+    let first: FuzzyHash = "6:AAAAAAAAxyz:BBBBBBq".parse().unwrap();
+    let second: FuzzyHash = "6:AAAxyz:BBBBq".parse().unwrap();
+    let third: FuzzyHash = "12:AAAxyz:BBBBq".parse().unwrap();
+
+    assert_ne!(first, second);
+    assert!(first.eq_normalized(&second));
+    assert!(!first.eq_normalized(&third));
+    assert_eq!(first.normalized().unwrap(), second.normalized().unwrap());
+}
+
+#[test]
+fn non_canonical_block_sizes_are_rejected() {
+    assert!("03:YKKGhR0tn:YRGRmn".parse::<FuzzyHash>().is_err());
+    assert!("+3:YKKGhR0tn:YRGRmn".parse::<FuzzyHash>().is_err());
+}
@@ -0,0 +1,44 @@
+use fuzzyhash::{error::Error, FuzzyHash};
+use std::convert::TryFrom;
+
+#[test]
+fn parse_valid_hashes() {
+    let fuzzy_hash: FuzzyHash = "3:YKKGhR0tn:YRGRmn".parse().unwrap();
+    assert_eq!(fuzzy_hash.to_string(), "3:YKKGhR0tn:YRGRmn");
+    assert!(fuzzy_hash.is_parsed());
+
+    let empty = FuzzyHash::try_from("3::").unwrap();
+    assert_eq!(empty.to_string(), FuzzyHash::new("").unwrap().to_string());
+
+    let owned = FuzzyHash::try_from(
+        "192:tEIFoBn+SbDjIZ6MUpH6rDjHPanaVGLGOvkdGep:tEIeBrbDjRAvDVEGOMGQ".to_string(),
+    )
+    .unwrap();
+    assert_eq!(
+        owned.to_string(),
+        "192:tEIFoBn+SbDjIZ6MUpH6rDjHPanaVGLGOvkdGep:tEIeBrbDjRAvDVEGOMGQ"
+    );
+}
+
+#[test]
+fn parse_invalid_hashes() {
+    match "".parse::<FuzzyHash>() {
+        Err(Error::MalformedInput) => {}
+        other => panic!("unexpected result {:?}", other.map(|h| h.to_string())),
+    }
+    assert!("garbage".parse::<FuzzyHash>().is_err());
+    assert!("3:YKKGhR0tn".parse::<FuzzyHash>().is_err());
+    assert!("3:YKKGhR0tn:YRGRmn:extra".parse::<FuzzyHash>().is_err());
+    assert!("-3:YKKGhR0tn:YRGRmn".parse::<FuzzyHash>().is_err());
+    assert!("7:YKKGhR0tn:YRGRmn".parse::<FuzzyHash>().is_err());
+    assert!("3:YKKG R0tn:YRGRmn".parse::<FuzzyHash>().is_err());
+    assert!(FuzzyHash::try_from(format!("3:{}:", "A".repeat(65))).is_err());
+}
+
+#[test]
+fn parsed_hashes_ignore_updates() {
+    let mut fuzzy_hash: FuzzyHash = "3:YKKGhR0tn:YRGRmn".parse().unwrap();
+    fuzzy_hash.update("more data");
+    fuzzy_hash.finalize().unwrap();
+    assert_eq!(fuzzy_hash.to_string(), "3:YKKGhR0tn:YRGRmn");
+}
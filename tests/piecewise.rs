@@ -0,0 +1,130 @@
+mod common;
+
+use fuzzyhash::{
+    piecewise::{self, Piecewise, Segment, Segmentation},
+    FuzzyHash,
+};
+use std::io::Cursor;
+
+fn segments(data: &[u8], segmentation: Segmentation, chunk: usize) -> Vec<Segment> {
+    let mut piecewise = Piecewise::new(segmentation);
+    for chunk in data.chunks(chunk) {
+        piecewise.update(chunk).unwrap();
+    }
+    piecewise.finalize().unwrap();
+    piecewise.into_segments()
+}
+
+/// Segments must cover the input without gaps, in order
+fn assert_covers(segments: &[Segment], len: u64) {
+    let mut offset = 0;
+    for segment in segments {
+        assert_eq!(segment.offset, offset);
+        assert!(segment.len > 0);
+        offset += segment.len;
+    }
+    assert_eq!(offset, len);
+}
+
+#[test]
+fn piecewise_fixed_segments() {
+    let data = common::random_data(32, 100_000);
+    let found = segments(&data, Segmentation::Fixed(30_000), 7_777);
+    assert_covers(&found, 100_000);
+    assert_eq!(
+        found.iter().map(|s| s.len).collect::<Vec<_>>(),
+        vec![30_000, 30_000, 30_000, 10_000]
+    );
+    for segment in &found {
+        let start = segment.offset as usize;
+        let expected = FuzzyHash::new(&data[start..start + segment.len as usize]).unwrap();
+        assert_eq!(segment.hash, expected);
+    }
+
+    // an input of whole segments has no empty last segment
+    assert_eq!(segments(&data, Segmentation::Fixed(25_000), 1_000).len(), 4);
+}
+
+#[test]
+fn piecewise_content_defined_segments() {
+    let data = common::random_data(33, 500_000);
+    let segmentation = Segmentation::content_defined(16 * 1024);
+    let found = segments(&data, segmentation, 500_000);
+    assert_covers(&found, 500_000);
+    assert!(found.len() > 10, "{} segments", found.len());
+    for segment in &found[..found.len() - 1] {
+        assert!(segment.len >= 4 * 1024 && segment.len <= 64 * 1024);
+    }
+
+    // boundaries do not depend on how the input is fed
+    assert_eq!(segments(&data, segmentation, 1_000), found);
+    assert_eq!(
+        Piecewise::read(&mut Cursor::new(&data), segmentation).unwrap(),
+        found
+    );
+
+    // inserting data only moves the boundaries around the insertion
+    let mut edited = data[..200_000].to_vec();
+    edited.extend_from_slice(b"inserted data");
+    edited.extend_from_slice(&data[200_000..]);
+    let moved = segments(&edited, segmentation, 4_096);
+    let shared = found
+        .iter()
+        .filter(|s| moved.iter().any(|m| m.len == s.len && m.hash == s.hash))
+        .count();
+    assert!(shared >= found.len() - 2, "{} of {}", shared, found.len());
+}
+
+#[test]
+fn piecewise_compare() {
+    let image = common::random_data(34, 400_000);
+    let segmentation = Segmentation::content_defined(32 * 1024);
+    let first = segments(&image, segmentation, 10_000);
+
+    // the second image holds a damaged copy of the first one's data after its own content
+    let mut second_image = common::random_data(35, 100_000);
+    second_image.extend_from_slice(&common::mutate(&image, 6, 3));
+    let second = segments(&second_image, segmentation, 10_000);
+
+    let matches = piecewise::compare(&first, &second, 50).unwrap();
+    let matched: Vec<usize> = matches.iter().map(|m| m.first).collect();
+    assert!(matched.len() >= first.len() - 2, "{:?}", matched);
+    for m in &matches {
+        assert!(m.score >= 50);
+        // the segment holding the start of the copy may begin before it
+        assert!(second[m.second].offset + second[m.second].len > 100_000);
+        assert_eq!(
+            Some(m.score),
+            first[m.first].hash.compare_to(&second[m.second].hash)
+        );
+    }
+    assert!(matches
+        .windows(2)
+        .all(|w| (w[0].first, w[0].second) < (w[1].first, w[1].second)));
+
+    let unrelated = segments(&common::random_data(36, 400_000), segmentation, 10_000);
+    assert!(piecewise::compare(&first, &unrelated, 1)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn piecewise_edge_cases() {
+    let mut empty = Piecewise::new(Segmentation::Fixed(10));
+    empty.finalize().unwrap();
+    assert!(empty.segments().is_empty());
+
+    let mut piecewise = Piecewise::new(Segmentation::Fixed(10));
+    piecewise.update(b"0123456789abc").unwrap();
+    assert_eq!(piecewise.segments().len(), 1);
+    piecewise.finalize().unwrap();
+    piecewise.update(b"ignored").unwrap();
+    piecewise.finalize().unwrap();
+    assert_eq!(piecewise.segments().len(), 2);
+    assert_eq!(piecewise.segments()[1].len, 3);
+
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_data.bin");
+    let found = Piecewise::file(path, Segmentation::Fixed(1 << 30)).unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].hash, FuzzyHash::file(path).unwrap());
+}
@@ -75,8 +75,8 @@ a5500b26b3fd3f77c433c0d85978c667898832f12709d5d79b1d90f62510e109
 "#;
 
     let bytes = data.as_bytes().to_vec();
-    let mut fuzzy_hash = FuzzyHash::new(bytes);
-    fuzzy_hash.finalize();
+    let mut fuzzy_hash = FuzzyHash::new(bytes).unwrap();
+    fuzzy_hash.finalize().unwrap();
 
     assert_eq!(
         fuzzy_hash.to_string(),
@@ -172,7 +172,7 @@ a5500b26b3fd3f77c433c0d85978c667898832f12709d5d79b1d90f62510e109
         hasher.update(some);
     }
 
-    hasher.finalize();
+    hasher.finalize().unwrap();
 
     assert_eq!(
         hasher.to_string(),
@@ -0,0 +1,212 @@
+#![cfg(feature = "sdhash")]
+
+mod common;
+
+use fuzzyhash::{
+    error::Error,
+    sdhash::{Mode, Sdhash, DEFAULT_BLOCK_SIZE},
+};
+
+fn block_hash(data: &[u8]) -> Sdhash {
+    let mut sdhash = Sdhash::with_block_size(DEFAULT_BLOCK_SIZE);
+    sdhash.update(data);
+    sdhash.finalize().unwrap();
+    sdhash
+}
+
+#[test]
+fn sdhash_stream_digest() {
+    let data = common::random_data(17, 100_000);
+    let sdhash = Sdhash::new(&data).unwrap();
+    assert_eq!(sdhash.mode(), Mode::Stream);
+    assert_eq!(sdhash.input_len(), 100_000);
+    assert!(sdhash.filter_count() > 1);
+
+    let digest = sdhash.digest().unwrap();
+    let prefix = format!(
+        "fhsd:03:0::100000:sha1:256:5:7ff:160:{}:",
+        sdhash.filter_count()
+    );
+    assert!(digest.starts_with(&prefix), "{}", digest);
+
+    let parsed: Sdhash = digest.parse().unwrap();
+    assert!(parsed.is_parsed());
+    assert_eq!(parsed, sdhash);
+    assert_eq!(parsed.compare(&sdhash).unwrap(), 100);
+
+    // the reference tool ends each digest with a newline
+    assert_eq!(format!("{}\n", digest).parse::<Sdhash>().unwrap(), sdhash);
+}
+
+#[test]
+fn sdhash_block_digest() {
+    let data = common::random_data(18, 100_000);
+    let sdhash = block_hash(&data);
+    assert_eq!(sdhash.mode(), Mode::Block(DEFAULT_BLOCK_SIZE));
+    assert_eq!(sdhash.filter_count(), 7);
+
+    let digest = sdhash.digest().unwrap();
+    assert!(digest.starts_with("fhsd-dd:03:0::100000:sha1:256:5:7ff:192:7:16384:"));
+    let parsed: Sdhash = digest.parse().unwrap();
+    assert_eq!(parsed, sdhash);
+    assert_eq!(parsed.mode(), Mode::Block(DEFAULT_BLOCK_SIZE));
+
+    // block and stream digests of the same data are comparable
+    assert!(sdhash.compare(&Sdhash::new(&data).unwrap()).unwrap() > 50);
+}
+
+#[test]
+fn sdhash_names() {
+    let mut sdhash = Sdhash::new(common::random_data(19, 5_000)).unwrap();
+    let unnamed = sdhash.to_string();
+    sdhash.set_name("dir/a:b.bin");
+    assert_eq!(sdhash.name(), "dir/a:b.bin");
+    assert!(sdhash
+        .to_string()
+        .starts_with("fhsd:03:11:dir/a:b.bin:5000:"));
+    assert_ne!(sdhash.to_string(), unnamed);
+
+    let parsed: Sdhash = sdhash.to_string().parse().unwrap();
+    assert_eq!(parsed.name(), "dir/a:b.bin");
+    assert_eq!(parsed, sdhash);
+}
+
+#[test]
+fn sdhash_streaming_matches_one_shot() {
+    let data = common::random_data(20, 70_000);
+    for mode in &[Mode::Stream, Mode::Block(DEFAULT_BLOCK_SIZE)] {
+        let mut sdhash = match mode {
+            Mode::Stream => Sdhash::default(),
+            Mode::Block(size) => Sdhash::with_block_size(*size),
+        };
+        for chunk in data.chunks(999) {
+            sdhash.update(chunk);
+        }
+        assert!(sdhash.digest().is_err());
+        sdhash.finalize().unwrap();
+
+        let one_shot = match mode {
+            Mode::Stream => Sdhash::new(&data).unwrap(),
+            Mode::Block(_) => block_hash(&data),
+        };
+        assert_eq!(sdhash, one_shot);
+    }
+}
+
+#[test]
+fn sdhash_similarity() {
+    let base = common::random_data(21, 200_000);
+    let original = Sdhash::new(&base).unwrap();
+
+    let edited = Sdhash::new(common::mutate(&base, 4, 20)).unwrap();
+    let score = original.compare(&edited).unwrap();
+    assert!(score > 80 && score <= 100, "edited {}", score);
+    assert_eq!(edited.compare(&original).unwrap(), score);
+
+    // a fragment at the start of the input fills the same filters as the whole input
+    let fragment = Sdhash::new(&base[..20_000]).unwrap();
+    assert_eq!(fragment.compare(&original).unwrap(), 100);
+    // elsewhere it is still found, though its filters each overlap two of the whole input's
+    // filters, so less than the reference cut-off of each pair is above chance
+    let fragment = Sdhash::new(&base[120_000..140_000]).unwrap();
+    let score = fragment.compare(&original).unwrap();
+    assert!(score > 15, "fragment {}", score);
+
+    let unrelated = Sdhash::new(common::random_data(22, 200_000)).unwrap();
+    assert!(original.compare(&unrelated).unwrap() < 10);
+}
+
+/// A stream digest with one filter holding `elements` features, with the bits of the `(start,
+/// end)` ranges set
+fn filter_digest(elements: u32, ranges: &[(usize, usize)]) -> Sdhash {
+    const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut bits = [0u8; 256];
+    for bit in ranges.iter().flat_map(|(start, end)| *start..*end) {
+        bits[bit / 8] |= 1 << (bit % 8);
+    }
+    let mut encoded = String::new();
+    for group in bits.chunks(3) {
+        let mut word = [0u8; 3];
+        word[..group.len()].copy_from_slice(group);
+        let word = u32::from(word[0]) << 16 | u32::from(word[1]) << 8 | u32::from(word[2]);
+        for i in 0..4 {
+            encoded.push(if i <= group.len() {
+                BASE64[(word >> (18 - 6 * i) & 63) as usize] as char
+            } else {
+                '='
+            });
+        }
+    }
+    format!(
+        "fhsd:03:0::10000:sha1:256:5:7ff:160:1:{}:{}",
+        elements, encoded
+    )
+    .parse()
+    .unwrap()
+}
+
+#[test]
+fn sdhash_reference_score() {
+    // 160 and 160 features: 214 bits expected in common, a cut-off of 329 and at most 600
+    // bits in common, so 500 common bits score (500 - 329) / (600 - 329)
+    let first = filter_digest(160, &[(0, 600)]);
+    let second = filter_digest(160, &[(0, 500), (1000, 1100)]);
+    assert_eq!(first.compare(&second).unwrap(), 63);
+    assert_eq!(second.compare(&first).unwrap(), 63);
+
+    // 160 and 40 features: 62 bits expected, a cut-off of 133 and at most 300 bits
+    let third = filter_digest(40, &[(0, 250), (1500, 1550)]);
+    assert_eq!(first.compare(&third).unwrap(), 70);
+
+    // overlaps below the cut-off score 0
+    let fourth = filter_digest(160, &[(0, 300), (1000, 1300)]);
+    assert_eq!(first.compare(&fourth).unwrap(), 0);
+}
+
+#[test]
+fn sdhash_errors() {
+    match Sdhash::new(common::random_data(23, 511)).unwrap_err() {
+        Error::InsufficientData => {}
+        other => panic!("unexpected error {:?}", other),
+    }
+
+    // uniform data has no features to compare
+    let uniform = Sdhash::new(vec![0u8; 10_000]).unwrap();
+    let random = Sdhash::new(common::random_data(24, 10_000)).unwrap();
+    match uniform.compare(&random).unwrap_err() {
+        Error::InsufficientData => {}
+        other => panic!("unexpected error {:?}", other),
+    }
+    match random.compare(&Sdhash::default()).unwrap_err() {
+        Error::NotFinalized => {}
+        other => panic!("unexpected error {:?}", other),
+    }
+
+    let digest = random.to_string();
+    let block = block_hash(&common::random_data(24, 10_000)).to_string();
+    for bad in &[
+        "",
+        "fhsd",
+        "fhsd:03:0::",
+        "fhsd:02:0::10000:sha1:256:5:7ff:160:1:0:AAAA",
+        &digest.replacen("sha1", "md5", 1),
+        &digest.replacen(":256:", ":512:", 1),
+        &digest.replacen(":7ff:", ":3ff:", 1),
+        &digest.replacen(":160:", ":192:", 1),
+        &digest.replacen("fhsd:03:0:", "fhsd:03:5:", 1),
+        &digest[..digest.len() - 4],
+        &format!("{}:AAAA", digest),
+        &block.replacen("fhsd-dd", "fhsd", 1),
+        &block.replacen(":16384:", ":100:", 1),
+        &block[..block.len() - 4],
+        // counts that overflow once multiplied by the filter size or the fields per filter
+        "fhsd:03:0::1:sha1:256:5:7ff:160:72057594037927936:0:",
+        "fhsd-dd:03:0::1:sha1:256:5:7ff:192:9223372036854775808:16384",
+        "fhsd:03:0::1:sha1:256:5:7ff:160:1:4294967296:AAAA",
+        // digests of the reference tool select different features
+        &digest.replacen("fhsd", "sdbf", 1),
+        &block.replacen("fhsd-dd", "sdbf-dd", 1),
+    ] {
+        assert!(bad.parse::<Sdhash>().is_err(), "{:?}", bad);
+    }
+}
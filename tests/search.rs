@@ -0,0 +1,88 @@
+mod common;
+
+use fuzzyhash::{
+    index::{Index, Match},
+    search::Search,
+    FuzzyHash,
+};
+
+fn expected(query: &FuzzyHash, hashes: &[FuzzyHash], k: usize) -> Vec<Match> {
+    common::brute_force(query, hashes)
+        .into_iter()
+        .take(k)
+        .map(|(id, score)| Match { id, score })
+        .collect()
+}
+
+fn check<S: Search + ?Sized>(collection: &S, hashes: &[FuzzyHash]) {
+    for query in hashes {
+        for k in &[0, 1, 2, 5, hashes.len() + 1] {
+            assert_eq!(
+                collection.top_k(query, *k).unwrap(),
+                expected(query, hashes, *k),
+                "query {} k {}",
+                query,
+                k
+            );
+        }
+    }
+}
+
+#[test]
+fn top_k_slice() {
+    let hashes = common::corpus();
+    check(hashes.as_slice(), &hashes);
+    check(&hashes, &hashes);
+}
+
+#[test]
+fn top_k_index() {
+    let hashes = common::corpus();
+    let mut index = Index::new();
+    for hash in &hashes {
+        index.insert(hash.clone()).unwrap();
+    }
+    check(&index, &hashes);
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn top_k_disk_index() {
+    use fuzzyhash::index::disk::DiskIndex;
+
+    let hashes = common::corpus();
+    let path = std::env::temp_dir().join(format!("fuzzyhash-search-{}.idx", std::process::id()));
+    let (head, tail) = hashes.split_at(hashes.len() / 2);
+    let mut index = DiskIndex::create(&path, head).unwrap();
+    index.append(tail).unwrap();
+    check(&index, &hashes);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn top_k_ties_are_deterministic() {
+    let hash: FuzzyHash =
+        "96:U57GjXnLt9co6pZwvLhJluvrszNgMFwO6MFG8SvkpjTWf:Hj3BeoEcNJ0TspgIG8SvkpjTg"
+            .parse()
+            .unwrap();
+    let hashes = vec![hash.clone(); 5];
+
+    let top = hashes.top_k(&hash, 3).unwrap();
+    assert_eq!(
+        top,
+        (0..3)
+            .map(|id| Match { id, score: 100 })
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn top_k_skips_unfinalized() {
+    let hashes = vec![FuzzyHash::default(), "3:abcdefgh:abcdefgh".parse().unwrap()];
+    let query: FuzzyHash = "3:abcdefgh:abcdefgh".parse().unwrap();
+    assert_eq!(
+        hashes.top_k(&query, 5).unwrap(),
+        vec![Match { id: 1, score: 100 }]
+    );
+    assert!(hashes.top_k(&FuzzyHash::default(), 5).is_err());
+}
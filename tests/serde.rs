@@ -0,0 +1,48 @@
+#![cfg(feature = "serde")]
+
+use fuzzyhash::{error::Error, FuzzyHash};
+
+#[test]
+fn serde_json_round_trip() {
+    let fuzzy_hash = FuzzyHash::new("this is our test data!").unwrap();
+
+    let json = serde_json::to_string(&fuzzy_hash).unwrap();
+    assert_eq!(json, "\"3:YKKGhR0tn:YRGRmn\"");
+
+    let parsed: FuzzyHash = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed.to_string(), fuzzy_hash.to_string());
+}
+
+#[test]
+fn serde_msgpack_round_trip() {
+    let fuzzy_hash: FuzzyHash =
+        "96:U57GjXnLt9co6pZwvLhJluvrszNgMFwO6MFG8SvkpjTWf:Hj3BeoEcNJ0TspgIG8SvkpjTg"
+            .parse()
+            .unwrap();
+
+    let packed = rmp_serde::to_vec(&fuzzy_hash).unwrap();
+    let parsed: FuzzyHash = rmp_serde::from_slice(&packed).unwrap();
+    assert_eq!(parsed.to_string(), fuzzy_hash.to_string());
+}
+
+#[test]
+fn serde_rejects_invalid_hashes() {
+    assert!(serde_json::from_str::<FuzzyHash>("\"\"").is_err());
+    assert!(serde_json::from_str::<FuzzyHash>("\"garbage\"").is_err());
+    assert!(serde_json::from_str::<FuzzyHash>("\"abc:YKKGhR0tn:YRGRmn\"").is_err());
+    assert!(serde_json::from_str::<FuzzyHash>("\"5:YKKGhR0tn:YRGRmn\"").is_err());
+    assert!(serde_json::from_str::<FuzzyHash>("\"3:YKK,hR0tn:YRGRmn\"").is_err());
+}
+
+#[test]
+fn serde_errors() {
+    let error = FuzzyHash::compare("3:abc:def", "12:abc:def").unwrap_err();
+    assert_eq!(
+        serde_json::to_string(&error).unwrap(),
+        "\"IncompatibleBlockSizes\""
+    );
+    assert_eq!(
+        serde_json::to_string(&Error::MalformedInput).unwrap(),
+        "\"MalformedInput\""
+    );
+}
@@ -0,0 +1,145 @@
+use fuzzyhash::{
+    error::Error,
+    sigfile::{Entry, Header, Reader, Version, Writer},
+};
+
+fn read_all(bytes: &[u8]) -> (Header, Vec<Entry>) {
+    let reader = Reader::new(bytes).unwrap();
+    let header = reader.header();
+    (header, reader.collect::<Result<_, _>>().unwrap())
+}
+
+fn write_all(header: Header, entries: &[Entry]) -> Vec<u8> {
+    let mut writer = Writer::new(Vec::new(), header).unwrap();
+    for entry in entries {
+        writer.write(&entry.hash, &entry.filename).unwrap();
+    }
+    writer.into_inner()
+}
+
+fn invalid_line(bytes: &[u8]) -> usize {
+    let error = match Reader::new(bytes) {
+        Err(error) => error,
+        Ok(reader) => reader
+            .filter_map(|entry| entry.err())
+            .next()
+            .expect("an invalid line"),
+    };
+    match error {
+        Error::InvalidSignatureFile(line, _) => line,
+        other => panic!("unexpected error {:?}", other),
+    }
+}
+
+#[test]
+fn sigfile_round_trip() {
+    let files: &[&[u8]] = &[
+        b"ssdeep,1.1--blocksize:hash:hash,filename\n\
+          96:U57GjXnLt9co6pZwvLhJluvrszNgMFwO6MFG8SvkpjTWf:Hj3BeoEcNJ0TspgIG8SvkpjTg,\"/tmp/a.exe\"\n\
+          3:YKKGhR0tn:YRGRmn,\"/tmp/say \\\"hi\\\"\"\n\
+          3::,\"C:\\Users\\empty\\\"\n",
+        b"ssdeep,1.0--blocksize:hash:hash,filename\r\n\
+          3:YKKGhR0tn:YRGRmn,\"C:\\dir\\a,b.txt\"\r\n",
+        b"ssdeep,1.1--blocksize:hash:hash,filename\n",
+        // ssdeep writes paths as they are, even when they are not valid UTF-8
+        b"ssdeep,1.1--blocksize:hash:hash,filename\n\
+          3:YKKGhR0tn:YRGRmn,\"/tmp/caf\xe9 \\\"\xff\\\".txt\"\n",
+    ];
+
+    for bytes in files {
+        let (header, entries) = read_all(bytes);
+        assert_eq!(write_all(header, &entries), *bytes);
+    }
+
+    let (header, entries) = read_all(files[0]);
+    assert_eq!(header, Header::default());
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[1].filename, b"/tmp/say \"hi\"");
+    assert_eq!(entries[2].filename, b"C:\\Users\\empty\\");
+
+    let (header, entries) = read_all(files[1]);
+    assert_eq!(
+        header,
+        Header {
+            version: Version::V1_0,
+            crlf: true
+        }
+    );
+    assert_eq!(entries[0].hash.digest().unwrap(), "3:YKKGhR0tn:YRGRmn");
+    assert_eq!(entries[0].filename, b"C:\\dir\\a,b.txt");
+
+    let (_, entries) = read_all(files[3]);
+    assert_eq!(entries[0].filename, b"/tmp/caf\xe9 \"\xff\".txt");
+}
+
+#[test]
+fn sigfile_written_names_read_back() {
+    let hash = "3:YKKGhR0tn:YRGRmn".parse().unwrap();
+    let names: &[&[u8]] = &[
+        b"plain",
+        b"\"quoted\"",
+        b"\"",
+        b"ends with a backslash\\",
+        b"\\\"",
+        b"carriage\rreturn",
+        b"\xe9\xff",
+        b"",
+    ];
+    let mut writer = Writer::new(Vec::new(), Header::default()).unwrap();
+    for name in names {
+        writer.write(&hash, name).unwrap();
+    }
+    let (_, entries) = read_all(&writer.into_inner());
+    let read: Vec<&[u8]> = entries.iter().map(|e| e.filename.as_slice()).collect();
+    assert_eq!(read, names);
+
+    // a line break would end the entry early, so it is refused and nothing is written
+    let mut writer = Writer::new(Vec::new(), Header::default()).unwrap();
+    for name in &["line\nbreak", "\n", "crlf\r\n"] {
+        assert!(matches!(writer.write(&hash, name), Err(Error::Io(_))));
+    }
+    assert_eq!(
+        writer.into_inner(),
+        b"ssdeep,1.1--blocksize:hash:hash,filename\n"
+    );
+}
+
+#[test]
+fn sigfile_error_lines() {
+    assert_eq!(invalid_line(b""), 1);
+    assert_eq!(
+        invalid_line(b"ssdeep,2.0--blocksize:hash:hash,filename\n"),
+        1
+    );
+
+    let header = "ssdeep,1.1--blocksize:hash:hash,filename\n3:YKKGhR0tn:YRGRmn,\"ok\"\n";
+    for line in &[
+        "3:YKKGhR0tn:YRGRmn\n",
+        "3:YKKGhR0tn:YRGRmn,ok\n",
+        "3:YKKGhR0tn:YRGRmn,\"\n",
+        "3:YKKGhR0tn:YRGRmn,\"a\"b\"\n",
+        "4:YKKGhR0tn:YRGRmn,\"ok\"\n",
+        "\n",
+    ] {
+        let bytes = format!("{}{}", header, line);
+        assert_eq!(invalid_line(bytes.as_bytes()), 3, "line {:?}", line);
+    }
+
+    let error = Reader::new(&b"nope\n"[..]).err().unwrap();
+    assert_eq!(
+        error.to_string(),
+        "Invalid signature file at line 1: missing ssdeep header"
+    );
+}
+
+#[test]
+fn sigfile_keeps_reading_after_bad_line() {
+    let bytes = b"ssdeep,1.1--blocksize:hash:hash,filename\n\
+                  garbage\n\
+                  3:YKKGhR0tn:YRGRmn,\"ok\"\n";
+    let mut reader = Reader::new(&bytes[..]).unwrap();
+    assert!(reader.next().unwrap().is_err());
+    assert_eq!(reader.next().unwrap().unwrap().filename, b"ok");
+    assert_eq!(reader.line(), 3);
+    assert!(reader.next().is_none());
+}
@@ -0,0 +1,174 @@
+mod common;
+
+use fuzzyhash::{
+    error::Error,
+    tlsh::{Buckets, Checksum, Tlsh},
+};
+
+fn squares() -> Vec<u8> {
+    (0..1024u32).map(|i| (i * i % 251) as u8).collect()
+}
+
+fn hash(data: &[u8], buckets: Buckets, checksum: Checksum) -> Tlsh {
+    let mut tlsh = Tlsh::with_variant(buckets, checksum);
+    tlsh.update(data);
+    tlsh.finalize().unwrap();
+    tlsh
+}
+
+#[test]
+fn tlsh_digests() {
+    let data = squares();
+    assert_eq!(
+        Tlsh::new(&data).unwrap().digest().unwrap(),
+        "T16A112421C210AE5126A0EA0CC8AF0C1F1E1F9A9312509946A671BB29A3C621EB742D63"
+    );
+    assert_eq!(
+        hash(&data, Buckets::Full, Checksum::Long).digest().unwrap(),
+        "T16A7F93112B628A83E2ED83DF5057BD92D9D237F5C8A33346A0050459B99860320C4801A4A021C2109D51\
+         1660EA0CC46F0C1F1D1F56931250954555717B2593C621D7742D63"
+    );
+}
+
+#[test]
+fn tlsh_length_header() {
+    // the reference l_capturing: floor(ln(len) / ln(1.3) - 8.72777) for 2000 bytes is 20,
+    // floor(ln(len) / ln(1.1) - 62.5472) for 60000 bytes is 52, written nibble-swapped
+    for (len, lvalue) in &[(2_000, "41"), (60_000, "43")] {
+        let tlsh = Tlsh::new(common::random_data(4, *len)).unwrap();
+        assert_eq!(&tlsh.digest().unwrap()[4..6], *lvalue);
+    }
+}
+
+#[test]
+fn tlsh_variants_round_trip() {
+    let data = common::random_data(5, 3_000);
+    for (buckets, checksum, len) in &[
+        (Buckets::Compact, Checksum::Short, 72),
+        (Buckets::Compact, Checksum::Long, 76),
+        (Buckets::Full, Checksum::Short, 136),
+        (Buckets::Full, Checksum::Long, 140),
+    ] {
+        let tlsh = hash(&data, *buckets, *checksum);
+        let digest = tlsh.digest().unwrap();
+        assert_eq!(digest.len(), *len);
+
+        let parsed: Tlsh = digest.parse().unwrap();
+        assert!(parsed.is_parsed());
+        assert_eq!(parsed, tlsh);
+        assert_eq!((parsed.buckets(), parsed.checksum()), (*buckets, *checksum));
+        assert_eq!(parsed.distance(&tlsh).unwrap(), 0);
+
+        // the prefix is optional and hex may be lower case
+        let legacy: Tlsh = digest[2..].to_lowercase().parse().unwrap();
+        assert_eq!(legacy, tlsh);
+    }
+}
+
+#[test]
+fn tlsh_streaming_matches_one_shot() {
+    let data = common::random_data(6, 10_000);
+    let mut tlsh = Tlsh::default();
+    for chunk in data.chunks(333) {
+        tlsh.update(chunk);
+    }
+    assert!(tlsh.digest().is_err());
+    tlsh.finalize().unwrap();
+    assert_eq!(tlsh, Tlsh::new(&data).unwrap());
+
+    let digest = tlsh.to_string();
+    tlsh.update(b"more");
+    tlsh.finalize().unwrap();
+    assert_eq!(tlsh.to_string(), digest);
+}
+
+#[test]
+fn tlsh_distance() {
+    let base = common::random_data(7, 20_000);
+    let original = Tlsh::new(&base).unwrap();
+    let edited = Tlsh::new(common::mutate(&base, 1, 4)).unwrap();
+    let unrelated = Tlsh::new(common::random_data(8, 20_000)).unwrap();
+
+    let near = original.distance(&edited).unwrap();
+    let far = original.distance(&unrelated).unwrap();
+    assert!(near > 0 && near < 50, "near {}", near);
+    assert!(far > 150, "far {}", far);
+    assert_eq!(edited.distance(&original).unwrap(), near);
+
+    // reordering the content barely changes the digest
+    let mut reordered = base[10_000..].to_vec();
+    reordered.extend_from_slice(&base[..10_000]);
+    assert!(original.distance(&Tlsh::new(&reordered).unwrap()).unwrap() < 30);
+}
+
+/// A 128 bucket digest with a 1 byte checksum, from the hex of its header bytes and of its
+/// first two body bytes, the rest of the body being zero
+fn crafted(checksum: &str, lvalue: &str, ratios: &str, body: &str) -> Tlsh {
+    format!(
+        "T1{}{}{}{}{}",
+        checksum,
+        lvalue,
+        ratios,
+        body,
+        "00".repeat(30)
+    )
+    .parse()
+    .unwrap()
+}
+
+#[test]
+fn tlsh_reference_distance() {
+    // distances worked out by hand from the scoring rules of the reference implementation
+    let zero = crafted("00", "00", "00", "0000");
+
+    // checksum +1, length 2 apart 2 * 12, q1 ratio 3 apart (3 - 1) * 12, q2 ratio 1 apart +1,
+    // bucket pairs 3 apart 6 each and 1 apart 1 each
+    let far = crafted("01", "20", "31", "C355");
+    assert_eq!(zero.distance(&far).unwrap(), 1 + 24 + 24 + 1 + 12 + 4);
+    assert_eq!(far.distance(&zero).unwrap(), 66);
+
+    // lengths and ratios are compared on a circle, so 255 and 15 are 1 away from 0
+    let wrapped = crafted("00", "FF", "FF", "0000");
+    assert_eq!(zero.distance(&wrapped).unwrap(), 3);
+
+    // a length 1 apart costs 1 and a ratio 2 apart costs 12
+    let close = crafted("00", "10", "20", "0000");
+    assert_eq!(zero.distance(&close).unwrap(), 1 + 12);
+}
+
+#[test]
+fn tlsh_errors() {
+    match Tlsh::new(common::random_data(9, 49)).unwrap_err() {
+        Error::InsufficientData => {}
+        other => panic!("unexpected error {:?}", other),
+    }
+    match Tlsh::new(vec![0u8; 10_000]).unwrap_err() {
+        Error::InsufficientData => {}
+        other => panic!("unexpected error {:?}", other),
+    }
+    assert!(Tlsh::new(common::random_data(9, 50)).is_ok());
+
+    let data = common::random_data(10, 1_000);
+    let compact = Tlsh::new(&data).unwrap();
+    match compact
+        .distance(&hash(&data, Buckets::Full, Checksum::Short))
+        .unwrap_err()
+    {
+        Error::IncompatibleDigests => {}
+        other => panic!("unexpected error {:?}", other),
+    }
+    assert!(compact.distance(&Tlsh::default()).is_err());
+
+    for bad in &[
+        "",
+        "T1",
+        "T1XYZ",
+        &compact.digest().unwrap()[..70],
+        "3:abc:def",
+        // a sign is not a hex digit
+        &format!("T1+1{}", &compact.digest().unwrap()[4..]),
+        &format!("+1{}", &compact.digest().unwrap()[4..]),
+    ] {
+        assert!(bad.parse::<Tlsh>().is_err(), "{:?}", bad);
+    }
+}